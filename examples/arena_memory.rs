@@ -0,0 +1,117 @@
+//! This example compares the memory used by the domains of a 10k variables
+//! model when each domain is a `ReversibleSparseSet` (which owns its vectors)
+//! and when all of them are `ArenaSparseSet`s carved out of one single arena.
+//! A counting allocator reports the number of allocations and the number of
+//! bytes which are live once the domains are built (note that each
+//! `ReversibleSparseSet` also holds the two reversibles caching its bounds,
+//! which an `ArenaSparseSet` does without).
+//!
+//! It then times repeated sweeps, each of which pushes a level, removes a
+//! value from every domain and tests the membership of all its values, and
+//! finally pops the level. The domains are visited in the order of their
+//! creation, then in a scattered order (which defeats the prefetcher and thus
+//! exposes how close to each other the domains lie in memory).
+//!
+//! Run it with `cargo run --release --example arena_memory`.
+extern crate trail;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::Instant;
+use std::hint::black_box;
+
+use trail::context::Trail;
+use trail::reversible::{ReversibleSparseSet, ArenaSparseSet};
+
+/// The system allocator, counting the allocations and the live bytes
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BYTES : AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static COUNTING: Counting = Counting;
+
+const N_VARS  : usize = 10_000;
+const CAPACITY: usize = 10;
+const ROUNDS  : usize = 100;
+/// The multiplier of the scattered order (coprime with N_VARS)
+const SCATTER : usize = 7_919;
+
+/// Returns the number of allocations and of live bytes
+fn snapshot() -> (usize, usize) {
+    (ALLOCATIONS.load(Ordering::Relaxed), LIVE_BYTES.load(Ordering::Relaxed))
+}
+
+/// Prints the allocations and bytes spent since `before`
+fn report(what: &str, before: (usize, usize)) {
+    let (allocs, bytes) = snapshot();
+    println!("{:>6}: {:>6} allocations, {:>8} live bytes ({:.1} bytes per variable)",
+        what, allocs - before.0, bytes - before.1, (bytes - before.1) as f64 / N_VARS as f64);
+}
+
+/// Times ROUNDS sweeps over all the domains, visited in the given order. The
+/// visit removes the given value from the given domain and returns the number
+/// of values it still contains.
+fn sweeps<F>(what: &str, trail: &Rc<RefCell<Trail>>, order: &[usize], mut visit: F)
+    where F: FnMut(usize, usize) -> usize {
+    let start = Instant::now();
+    let mut present = 0;
+    for round in 0..ROUNDS {
+        trail.borrow_mut().push();
+        for &var in order {
+            present += visit(var, (var + round) % CAPACITY);
+        }
+        trail.borrow_mut().pop();
+    }
+    black_box(present);
+    let nanos = start.elapsed().as_nanos() as f64 / (ROUNDS * N_VARS) as f64;
+    println!("{:>6}: {:>6.1} ns per domain", what, nanos);
+}
+
+fn main() {
+    let trail = Rc::new(RefCell::new(Trail::new()));
+
+    let before   = snapshot();
+    let mut vecs : Vec<ReversibleSparseSet> = (0..N_VARS)
+        .map(|_| ReversibleSparseSet::new(Rc::clone(&trail), CAPACITY))
+        .collect();
+    report("vec", before);
+
+    let before    = snapshot();
+    let mut arena = vec![0; 2 * N_VARS * CAPACITY];
+    let mut sets  = ArenaSparseSet::from_arena(&trail, &mut arena, &[CAPACITY; N_VARS]);
+    report("arena", before);
+
+    let sequential : Vec<usize> = (0..N_VARS).collect();
+    let scattered  : Vec<usize> = (0..N_VARS).map(|i| i * SCATTER % N_VARS).collect();
+    for (name, order) in [("sequential", &sequential), ("scattered", &scattered)].iter() {
+        println!("{} sweeps:", name);
+        sweeps("vec", &trail, order, |var, v| {
+            vecs[var].remove(v);
+            (0..CAPACITY).filter(|&x| vecs[var].contains(x)).count()
+        });
+        sweeps("arena", &trail, order, |var, v| {
+            sets[var].remove(v);
+            (0..CAPACITY).filter(|&x| sets[var].contains(x)).count()
+        });
+    }
+
+    assert!(vecs.iter().all(|s| s.size() == CAPACITY));
+    assert!(sets.iter().all(|s| s.size() == CAPACITY));
+}
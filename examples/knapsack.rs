@@ -0,0 +1,77 @@
+//! This example shows how a `ReversibleObjective` drives a branch-and-bound
+//! search: a plain depth first search over a tiny 0/1 knapsack, which prunes
+//! the branches that cannot improve on the incumbent. The incumbent improves
+//! each time a better solution is found, and is not reverted as the search
+//! backtracks.
+//!
+//! Run it with `cargo run --example knapsack`.
+extern crate trail;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use trail::context::Trail;
+use trail::reversible::Reversible;
+use trail::search::{ReversibleObjective, Statistics};
+
+/// The weight and the value of each item
+const ITEMS   : [(isize, isize); 5] = [(4, 5), (3, 4), (5, 6), (2, 3), (6, 8)];
+const CAPACITY: isize = 10;
+
+/// The reversible state of a node of the search
+struct Knapsack<'a> {
+    weight: Reversible<'a, isize>,
+    value : Reversible<'a, isize>
+}
+
+/// Decides whether to take each item from `item` on, and records the
+/// solutions which improve on the incumbent.
+fn dfs(trail: &Rc<RefCell<Trail>>, sack: &mut Knapsack, obj: &mut ReversibleObjective, stats: &mut Statistics, item: usize, improvements: &mut Vec<isize>) {
+    stats.observe(&trail.borrow());
+    let value = sack.value.get_value();
+    // the optimistic value of this node: take all the remaining items
+    let optimistic = value + ITEMS[item..].iter().map(|&(_, v)| v).sum::<isize>();
+    if !obj.accepts(optimistic) {
+        stats.failure();
+        return;
+    }
+    if item == ITEMS.len() {
+        stats.solution();
+        if obj.tighten(value) {
+            improvements.push(value);
+        }
+        return;
+    }
+
+    let (w, v) = ITEMS[item];
+    if sack.weight.get_value() + w <= CAPACITY {
+        trail.borrow_mut().push();
+        sack.weight.set_value(sack.weight.get_value() + w);
+        sack.value.set_value(sack.value.get_value() + v);
+        dfs(trail, sack, obj, stats, item + 1, improvements);
+        trail.borrow_mut().pop();
+    }
+    dfs(trail, sack, obj, stats, item + 1, improvements);
+}
+
+fn main() {
+    let trail = Rc::new(RefCell::new(Trail::new()));
+    let mut sack = Knapsack {
+        weight: Reversible::new(Rc::clone(&trail), 0),
+        value : Reversible::new(Rc::clone(&trail), 0)
+    };
+    let mut obj = ReversibleObjective::maximize(Rc::clone(&trail));
+
+    let mut stats = Statistics::new();
+    let mut improvements = vec![];
+    dfs(&trail, &mut sack, &mut obj, &mut stats, 0, &mut improvements);
+
+    for v in improvements.iter() {
+        println!("new incumbent: {}", v);
+    }
+    println!("best value: {:?}", obj.incumbent());
+    println!("{}", stats);
+    assert_eq!(obj.incumbent(), Some(13));
+    assert!(improvements.windows(2).all(|w| w[0] < w[1]));
+    assert_eq!((sack.weight.get_value(), sack.value.get_value()), (0, 0));
+}
@@ -0,0 +1,110 @@
+//! This example shows how the trail and the reversible types compose into an
+//! actual constraint: a bounds consistent `sum(xs) == total` propagator, used
+//! by a plain depth first search to enumerate the solutions of a tiny problem.
+//!
+//! Run it with `cargo run --example sum_constraint`.
+extern crate trail;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use trail::context::Trail;
+use trail::reversible::{TrailedIntVar, Wipeout};
+
+/// The constraint `sum(xs) == total`
+struct Sum {
+    total: isize
+}
+
+impl Sum {
+    /// Tightens the bounds of the variables until a fix point is reached.
+    /// Each variable must lie within `total - (sum of the max of the others)`
+    /// and `total - (sum of the min of the others)`. Reports a wipeout when a
+    /// domain becomes empty.
+    fn propagate(&self, xs: &mut [TrailedIntVar]) -> Result<(), Wipeout> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let sum_min : isize = xs.iter().map(|x| x.min()).sum();
+            let sum_max : isize = xs.iter().map(|x| x.max()).sum();
+            for x in xs.iter_mut() {
+                let (lo, hi) = (x.min(), x.max());
+                changed |= x.remove_below(self.total - (sum_max - hi)).into_result()?;
+                changed |= x.remove_above(self.total - (sum_min - lo)).into_result()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Enumerates all the solutions of the problem (by depth first search) and
+/// returns them in lexicographic order
+fn solve(trail: &Rc<RefCell<Trail>>, xs: &mut [TrailedIntVar], sum: &Sum) -> Vec<Vec<isize>> {
+    let mut solutions = vec![];
+    if sum.propagate(xs).is_ok() {
+        dfs(trail, xs, sum, &mut solutions);
+    }
+    solutions
+}
+
+fn dfs(trail: &Rc<RefCell<Trail>>, xs: &mut [TrailedIntVar], sum: &Sum, solutions: &mut Vec<Vec<isize>>) {
+    let var = match xs.iter().position(|x| !x.is_fixed()) {
+        Some(var) => var,
+        None      => {
+            solutions.push(xs.iter().map(|x| x.min()).collect());
+            return;
+        }
+    };
+    let (lo, hi) = (xs[var].min(), xs[var].max());
+    for v in lo..=hi {
+        if !xs[var].contains(v) {
+            continue;
+        }
+        trail.borrow_mut().push();
+        if !xs[var].fix(v).is_failure() && sum.propagate(xs).is_ok() {
+            dfs(trail, xs, sum, solutions);
+        }
+        trail.borrow_mut().pop();
+    }
+}
+
+fn main() {
+    let trail = Rc::new(RefCell::new(Trail::new()));
+    let mut xs : Vec<TrailedIntVar> = (0..3).map(|_| TrailedIntVar::new(Rc::clone(&trail), 0, 3)).collect();
+    for solution in solve(&trail, &mut xs, &Sum { total: 7 }) {
+        println!("{:?}", solution);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_propagation_tightens_bounds() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut xs = vec![TrailedIntVar::new(Rc::clone(&trail), 0, 5), TrailedIntVar::new(Rc::clone(&trail), 0, 5)];
+
+        trail.borrow_mut().push();
+        assert!(Sum { total: 9 }.propagate(&mut xs).is_ok());
+        assert_eq!((xs[0].min(), xs[0].max(), xs[1].min(), xs[1].max()), (4, 5, 4, 5));
+        assert!(Sum { total: 11 }.propagate(&mut xs).is_err());
+
+        trail.borrow_mut().pop();
+        assert_eq!((xs[0].min(), xs[0].max()), (0, 5));
+    }
+
+    #[test]
+    fn test_solves_small_instance() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut xs : Vec<TrailedIntVar> = (0..3).map(|_| TrailedIntVar::new(Rc::clone(&trail), 0, 3)).collect();
+        let solutions = solve(&trail, &mut xs, &Sum { total: 7 });
+        assert_eq!(solutions, vec![
+            vec![1, 3, 3], vec![2, 2, 3], vec![2, 3, 2],
+            vec![3, 1, 3], vec![3, 2, 2], vec![3, 3, 1]
+        ]);
+        // the search leaves the variables as it found them
+        assert_eq!(trail.borrow().level(), 0);
+        assert!(xs.iter().all(|x| x.min() == 1 && x.max() == 3));
+    }
+}
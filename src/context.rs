@@ -1,55 +1,215 @@
 //! This module provides the trailing context at the heart of a trailing solver.
 //!
 //! Its code is *heavily* inspired from that of minicp (and Oscar, and Comet, ...)
-use std::boxed::Box;
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Cell, RefCell};
+use core::any::Any;
+use alloc::collections::BTreeSet;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use ::reversible::Reversible;
+
+/// A function returning the current value of a tracked reversible (None if the
+/// reversible has been dropped in the meantime)
+type Tracker<'a> = Box<dyn Fn() -> Option<Box<dyn Any>> + 'a>;
 
 /// This structure implements the trail, aka the reversible context.
 ///
+/// # Levels:
+/// The levels are counted from 0: a fresh trail is at level 0 (the root), each
+/// push increases the level by one and each pop decreases it by one.
+///
 /// # Note:
 /// The lifetime <'a> is only present to ensure that any data referred to by the
 /// restoration closures placed on the trail are still accessible when the closure
 /// is executed.
 pub struct Trail<'a> {
-    clock : usize,
     trail : Vec< Box<dyn FnMut() + 'a>  >,
-    limit : Vec< usize >
+    limit : Vec< usize >,
+    /// The (optional) id of the reversible which posted each entry of the trail
+    ids   : Vec< Option<usize> >,
+    /// The reversibles which are copied upon fork
+    tracked: Vec< Tracker<'a> >,
+    /// The hooks which are run after the entries of a level have been undone,
+    /// along with the number of hooks at the time each level was pushed
+    post  : Vec< Box<dyn FnMut() + 'a> >,
+    post_limit: Vec< usize >,
+    /// The callbacks which run at the end of each pop (they are never dropped)
+    finalizers: Vec< Box<dyn FnMut(usize) + 'a> >,
+    /// The clock and the restoration flag. These are shared so that the
+    /// reversibles can read them even though the trail itself is mutably
+    /// borrowed during a pop.
+    status: Rc<TrailStatus>,
+    /// The structural operations performed on this trail
+    #[cfg(feature = "op_log")]
+    log   : Vec< TrailOp >
+}
+
+/// A structural operation performed on a trail, as recorded in its log (see
+/// `Trail::op_log`)
+#[cfg(feature = "op_log")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailOp {
+    /// A call to `push`
+    Push,
+    /// A call to `pop`
+    Pop,
+    /// A call to `pop_until` with the given target level. The calls to
+    /// `pop_all`, `pop_n` and (successful) `try_pop_until` are recorded with
+    /// the target level they computed.
+    PopUntil(usize)
+}
+
+/// The part of the state of a trail which the reversibles may read at any time
+pub(crate) struct TrailStatus {
+    /// The clock ticks upon each push and pop
+    pub(crate) clock    : Cell<usize>,
+    /// This flag is raised while the restoration closures are being run
+    pub(crate) restoring: Cell<bool>
 }
 
 impl<'a> Trail<'a> {
     /// Create a new reversible context.
-    /// The current level is -1
+    /// The current level is 0
     pub fn new() -> Trail<'a> {
         Trail {
-            clock: 0,
             trail: vec![],
-            limit: vec![]
+            limit: vec![],
+            ids  : vec![],
+            tracked: vec![],
+            post : vec![],
+            post_limit: vec![],
+            finalizers: vec![],
+            status: Rc::new(TrailStatus { clock: Cell::new(0), restoring: Cell::new(false) }),
+            #[cfg(feature = "op_log")]
+            log   : vec![]
         }
     }
 
+    /// Creates a new reversible context which can hold the given number of
+    /// entries and levels without reallocating (see `reserve_entries` and
+    /// `reserve_levels`).
+    pub fn with_capacity(entries: usize, levels: usize) -> Trail<'a> {
+        let mut trail = Trail::new();
+        trail.reserve_entries(entries);
+        trail.reserve_levels(levels);
+        trail
+    }
+
+    /// Reserves the room for at least `additional` more entries on the trail
+    pub fn reserve_entries(&mut self, additional: usize) {
+        self.trail.reserve(additional);
+        self.ids.reserve(additional);
+    }
+
+    /// Reserves the room for at least `additional` more levels
+    pub fn reserve_levels(&mut self, additional: usize) {
+        self.limit.reserve(additional);
+        self.post_limit.reserve(additional);
+    }
+
+    /// Returns the number of entries the trail can hold without reallocating
+    pub fn entries_capacity(&self) -> usize {
+        self.trail.capacity().min(self.ids.capacity())
+    }
+
+    /// Returns the number of levels the trail can hold without reallocating
+    pub fn levels_capacity(&self) -> usize {
+        self.limit.capacity().min(self.post_limit.capacity())
+    }
+
     /// Callback to remember what needs to be undone upon restoration of the state
     pub fn push_on_trail(&mut self, entry: Box<dyn FnMut() + 'a> ) {
-        self.trail.push(entry)
+        self.trail.push(entry);
+        self.ids.push(None);
+    }
+
+    /// Same as `push_on_trail`, but remembers that the entry was posted by the
+    /// reversible bearing the given id (see `changed_since`).
+    pub fn push_on_trail_with_id(&mut self, id: usize, entry: Box<dyn FnMut() + 'a> ) {
+        self.trail.push(entry);
+        self.ids.push(Some(id));
     }
 
     /// Saves the current state so that it can be restored
     /// with a pop. Increases the level by one.
     pub fn push(&mut self) {
-        self.clock += 1;
+        #[cfg(feature = "op_log")]
+        self.log.push(TrailOp::Push);
+        self.tick();
         self.limit.push( self.trail.len() );
+        self.post_limit.push( self.post.len() );
+    }
+
+    /// Callback to run once the state of the current level has been restored.
+    /// Upon pop, the post-restore hooks of the popped level run exactly once,
+    /// in the order they were registered, after *all* the entries of that
+    /// level have been undone (e.g. to recompute a cache derived from several
+    /// reversibles). Just like the entries, the hooks must not mutate any
+    /// reversible.
+    pub fn push_post_restore(&mut self, hook: Box<dyn FnMut() + 'a>) {
+        self.post.push(hook);
+    }
+
+    /// Registers a callback which runs at the end of *each* level that is
+    /// popped from now on, with the level being left as argument (that is,
+    /// the level before the pop). Unlike a post-restore hook which runs once
+    /// for the level it was registered in, a finalizer is persistent: it is
+    /// typically used to flush some deferred work (e.g. to give freed ids back
+    /// to a pool) whenever the search backtracks.
+    ///
+    /// # Order
+    /// Upon pop, the entries of the level are undone first, then its
+    /// post-restore hooks run, and the finalizers run last (in the order they
+    /// were registered). When several levels are popped at once (`pop_until`,
+    /// ...), this happens for each level in turn, from the deepest one. Just
+    /// like the entries, the finalizers must not mutate any reversible.
+    pub fn register_level_finalizer(&mut self, finalizer: Box<dyn FnMut(usize) + 'a>) {
+        self.finalizers.push(finalizer);
     }
 
     /// Restores state as it was at level()-1
     /// Decrease the level by 1
+    ///
+    /// The entries of the level are undone in the reverse order of their
+    /// posting (LIFO), which is part of the contract: an entry posted after
+    /// another one is always undone before it. The post-restore hooks of the
+    /// level run afterwards (see `push_post_restore`), followed by the level
+    /// finalizers (see `register_level_finalizer`).
     pub fn pop(&mut self) {
+        #[cfg(feature = "op_log")]
+        self.log.push(TrailOp::Pop);
+        self.restore_level();
+    }
+
+    /// Runs the restoration closures of the current level and leaves it
+    fn restore_level(&mut self) {
+        let level = self.level();
         let sz = self.limit.pop().unwrap_or(0);
+        self.status.restoring.set(true);
         while self.trail.len() > sz {
             self.trail.pop().unwrap()();
+            self.ids.pop();
         }
-        self.clock += 1;
+        let from = self.post_limit.pop().unwrap_or(0);
+        for mut hook in self.post.drain(from..) {
+            hook();
+        }
+        if level > 0 {
+            for finalizer in self.finalizers.iter_mut() {
+                finalizer(level);
+            }
+        }
+        self.status.restoring.set(false);
+        self.tick();
     }
 
-    /// Restores the state as it was at level 0 (first push)
-    /// The level is now -1.
+    /// Restores the state as it was at level 0 (before the first push)
+    /// The level is now 0.
     ///
     /// Note: You'll probably want to push after this operation.
     pub fn pop_all(&mut self) {
@@ -58,9 +218,29 @@ impl<'a> Trail<'a> {
 
     /// Restores the state as it was at level
     pub fn pop_until(&mut self, level: usize) {
+        #[cfg(feature = "op_log")]
+        self.log.push(TrailOp::PopUntil(level));
         while self.level() > level {
-            self.pop()
+            self.restore_level()
+        }
+    }
+
+    /// Same as `pop_until`, except that asking to pop *up* to a level which is
+    /// higher than the current one is reported as an error rather than being
+    /// silently ignored. Targeting the current level is a valid no-op.
+    pub fn try_pop_until(&mut self, level: usize) -> Result<(), PopError> {
+        if level > self.level() {
+            return Err(PopError { target: level, level: self.level() });
         }
+        self.pop_until(level);
+        Ok(())
+    }
+
+    /// Restores the state as it was n levels ago. When n exceeds the current
+    /// level, this simply restores the state as it was at level 0.
+    pub fn pop_n(&mut self, n: usize) {
+        let level = self.level().saturating_sub(n);
+        self.pop_until(level)
     }
 
     /// Returns the current level
@@ -70,6 +250,759 @@ impl<'a> Trail<'a> {
 
     /// Returns the current value of the clock
     pub fn clock(&self) -> usize {
-        self.clock
+        self.status.clock.get()
+    }
+
+    /// Increments the clock
+    fn tick(&mut self) {
+        self.status.clock.set(self.status.clock.get() + 1);
+    }
+
+    /// Returns the structural operations (push, pop, ...) which have been
+    /// performed on this trail, in chronological order
+    #[cfg(feature = "op_log")]
+    pub fn op_log(&self) -> &[TrailOp] {
+        &self.log
+    }
+
+    /// Returns the number of entries currently on the trail
+    pub fn len(&self) -> usize {
+        self.trail.len()
+    }
+
+    /// Returns the position (in the trail) of the first entry of the current
+    /// level: `len() - current_frame_start()` is the number of entries posted
+    /// since the last push. This is 0 at level 0. The value is only meaningful
+    /// until the next push or pop.
+    pub fn current_frame_start(&self) -> usize {
+        self.limit.last().copied().unwrap_or(0)
+    }
+
+    /// Registers a tracker function which is called upon fork to copy the value
+    /// of some reversible. Returns the handle to use with `Fork::take`.
+    /// (You will want to use `Reversible::track` rather than this method).
+    pub fn track(&mut self, tracker: Tracker<'a>) -> usize {
+        self.tracked.push(tracker);
+        self.tracked.len() - 1
+    }
+
+    /// Copies the current value of all the tracked reversibles into a brand new
+    /// trail. This is effectively a copying checkpoint. Its cost is O(number of
+    /// tracked reversibles), and the fork shares nothing with this trail (both
+    /// of them evolve independently afterwards). The restoration closures can
+    /// not be cloned: the fork starts with an empty trail at level 0.
+    pub fn fork<'b>(&self) -> Fork<'b> {
+        Fork {
+            trail : Rc::new(RefCell::new(Trail::new())),
+            values: self.tracked.iter().map(|tracker| tracker()).collect()
+        }
+    }
+
+    /// Returns the (sorted, deduplicated) ids of the reversibles which have
+    /// been modified since the given level was left. That is, the ids of the
+    /// reversibles which would be restored by `pop_until(level)`. Only the
+    /// reversibles which bear an id are reported.
+    pub fn changed_since(&self, level: usize) -> Vec<usize> {
+        let from = self.limit.get(level).cloned().unwrap_or(self.ids.len());
+        let mut ids : Vec<usize> = self.ids[from..].iter().filter_map(|&id| id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Drops the redundant entries of the current level: for each id, only the
+    /// earliest entry posted with that id since the last push is kept (as it is
+    /// the one restoring the oldest state). The entries without an id are all
+    /// kept. Returns the number of entries which have been dropped.
+    ///
+    /// # Note
+    /// This assumes that an id identifies one single piece of state, that is,
+    /// all the entries bearing the same id restore the same cell. That is always
+    /// the case for reversibles with distinct ids (although these never post
+    /// more than one entry per level on their own), but it must be guaranteed by
+    /// whoever calls `push_on_trail_with_id` or shares an id between reversibles.
+    pub fn compact_current_level(&mut self) -> usize {
+        let from  = self.limit.last().cloned().unwrap_or(0);
+        let size  = self.trail.len();
+        let trail : Vec<_> = self.trail.drain(from..).collect();
+        let ids   : Vec<_> = self.ids.drain(from..).collect();
+
+        let mut seen = BTreeSet::new();
+        for (entry, id) in trail.into_iter().zip(ids) {
+            if id.is_none_or(|id| seen.insert(id)) {
+                self.trail.push(entry);
+                self.ids.push(id);
+            }
+        }
+        size - self.trail.len()
+    }
+
+    /// Returns true iff there is no entry on the trail
+    pub fn is_empty(&self) -> bool {
+        self.trail.is_empty()
+    }
+
+    /// Returns true iff the restoration closures are currently being run
+    pub fn is_restoring(&self) -> bool {
+        self.status.restoring.get()
+    }
+
+    /// Returns the status (clock and restoration flag) of the trail
+    pub(crate) fn status(&self) -> Rc<TrailStatus> {
+        Rc::clone(&self.status)
+    }
+
+    /// Pushes a new level, runs f and then restores the state as it was before
+    /// the call, no matter how f exits (even if it panics). Any level that f
+    /// left pushed is popped as well. Returns the result of f.
+    ///
+    /// # Note
+    /// Because f receives the trail mutably, the reversibles which share this
+    /// trail through an `Rc<RefCell<Trail>>` cannot post any entry from within
+    /// f (the trail is already borrowed). For these, push and pop by hand.
+    pub fn transaction<F, R>(&mut self, f: F) -> R
+        where F: FnOnce(&mut Trail<'a>) -> R {
+        let guard = TransactionGuard::begin(self);
+        f(guard.trail)
+    }
+
+    /// Same as `transaction`, except that an `Ok` result commits the changes
+    /// made by f: the level pushed for f is kept (so that the changes are still
+    /// undone by a later pop). An `Err` result (or a panic) restores the state
+    /// as it was before the call.
+    pub fn try_transaction<F, R, E>(&mut self, f: F) -> Result<R, E>
+        where F: FnOnce(&mut Trail<'a>) -> Result<R, E> {
+        let mut guard = TransactionGuard::begin(self);
+        let result = f(guard.trail);
+        guard.commit = result.is_ok();
+        result
+    }
+}
+
+/// The guard which restores the state of a trail at the end of a transaction
+/// unless the transaction has been committed
+struct TransactionGuard<'t, 'a: 't> {
+    trail : &'t mut Trail<'a>,
+    level : usize,
+    commit: bool
+}
+
+impl<'t, 'a> TransactionGuard<'t, 'a> {
+    fn begin(trail: &'t mut Trail<'a>) -> TransactionGuard<'t, 'a> {
+        let level = trail.level();
+        trail.push();
+        TransactionGuard { trail, level, commit: false }
+    }
+}
+
+impl<'t, 'a> Drop for TransactionGuard<'t, 'a> {
+    fn drop(&mut self) {
+        if self.commit {
+            self.trail.pop_until(self.level + 1);
+        } else {
+            self.trail.pop_until(self.level);
+        }
+    }
+}
+
+impl<'a> Default for Trail<'a> {
+    fn default() -> Trail<'a> {
+        Trail::new()
+    }
+}
+
+/// The error reported by `try_pop_until` when the target level is higher than
+/// the current level of the trail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PopError {
+    /// The level that was asked for
+    pub target: usize,
+    /// The level of the trail when the pop was attempted
+    pub level : usize
+}
+
+impl fmt::Display for PopError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot pop up to level {} from level {}", self.target, self.level)
+    }
+}
+
+/// Shows the level, the clock and the number of entries of the trail
+impl<'a> fmt::Debug for Trail<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Trail")
+            .field("level", &self.level())
+            .field("clock", &self.clock())
+            .field("entries", &self.len())
+            .finish()
+    }
+}
+
+/// The result of `Trail::fork`: a brand new trail along with the values of the
+/// reversibles which were tracked by the original trail.
+pub struct Fork<'b> {
+    /// The new (independent) trail
+    pub trail: Rc<RefCell<Trail<'b>>>,
+    values   : Vec<Option<Box<dyn Any>>>
+}
+
+impl<'b> Fork<'b> {
+    /// Returns the fresh counterpart of the reversible having the given handle
+    /// in the original trail. The fresh reversible is associated with the new
+    /// trail and holds the value the original one had when the fork was made.
+    /// Returns None if that reversible had been dropped or was already taken.
+    ///
+    /// # Panics
+    /// When T is not the type of the tracked reversible.
+    pub fn take<T>(&mut self, handle: usize) -> Option<Reversible<'b, T>>
+        where T: Copy + PartialEq + 'static {
+        let value = self.values.get_mut(handle)?.take()?;
+        let value = *value.downcast::<T>().expect("the tracked reversible has another type");
+        Some(Reversible::new(Rc::clone(&self.trail), value))
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// The stack of the trails installed on the current thread (the last one
+    /// is the ambient trail)
+    static AMBIENT: RefCell<Vec<Rc<RefCell<Trail<'static>>>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Uninstalls the innermost ambient trail, even when the closure panics
+#[cfg(feature = "std")]
+struct AmbientGuard;
+
+#[cfg(feature = "std")]
+impl Drop for AmbientGuard {
+    fn drop(&mut self) {
+        AMBIENT.with(|ambient| ambient.borrow_mut().pop());
+    }
+}
+
+/// # Ambient trail
+/// The ambient trail spares you from plumbing the shared trail through every
+/// function that builds a part of a model: the `new_ambient` constructors
+/// (e.g. `Reversible::new_ambient`) associate the objects they create with the
+/// trail which is installed on the current thread.
+///
+/// # Note:
+/// The ambient trail is thread-local and only lasts for the duration of the
+/// `install` call, but the objects which are created there remain associated
+/// with it afterwards. Since it is stored in a thread-local, only the trails
+/// whose entries are `'static` can be installed.
+#[cfg(feature = "std")]
+impl Trail<'static> {
+    /// Makes the given trail the ambient trail of the current thread while
+    /// `f` runs. Installs can be nested: the innermost one shadows the outer
+    /// ones until it returns.
+    pub fn install<R, F: FnOnce() -> R>(trail: Rc<RefCell<Trail<'static>>>, f: F) -> R {
+        AMBIENT.with(|ambient| ambient.borrow_mut().push(trail));
+        let _guard = AmbientGuard;
+        f()
+    }
+
+    /// Returns the ambient trail of the current thread (None when no trail
+    /// is installed)
+    pub fn ambient() -> Option<Rc<RefCell<Trail<'static>>>> {
+        AMBIENT.with(|ambient| ambient.borrow().last().cloned())
+    }
+
+    /// Returns the ambient trail of the current thread, or panics with a
+    /// message naming the constructor that needed it
+    pub(crate) fn expect_ambient(what: &str) -> Rc<RefCell<Trail<'static>>> {
+        Trail::ambient().unwrap_or_else(||
+            panic!("{}::new_ambient requires an ambient trail: call it within Trail::install", what))
+    }
+}
+
+/// This structure implements a thread-safe flavor of the trail. Unlike `Trail`
+/// which is meant to be shared through an `Rc<RefCell<Trail>>`, a `SyncTrail`
+/// is a cheap handle (an `Arc<Mutex<..>>`) which can be cloned and sent across
+/// thread boundaries. Its semantics are identical to those of `Trail`.
+///
+/// # Note:
+/// This type is meant for coarse-grained parallelism (e.g. a portfolio where
+/// each worker owns its own trail, but where the trail must be created in one
+/// thread and moved to another one). Every operation acquires a lock, which
+/// makes it noticeably slower than `Trail`.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct SyncTrail<'a> {
+    state: Arc<Mutex<SyncTrailState<'a>>>
+}
+
+/// The actual state of a `SyncTrail`
+#[cfg(feature = "std")]
+struct SyncTrailState<'a> {
+    clock : usize,
+    trail : Vec< Box<dyn FnMut() + Send + 'a>  >,
+    limit : Vec< usize >
+}
+
+#[cfg(feature = "std")]
+impl<'a> SyncTrail<'a> {
+    /// Create a new thread-safe reversible context.
+    pub fn new() -> SyncTrail<'a> {
+        let state = SyncTrailState { clock: 0, trail: vec![], limit: vec![] };
+        SyncTrail { state: Arc::new(Mutex::new(state)) }
+    }
+
+    /// Acquires the lock on the state of the trail
+    fn lock(&self) -> MutexGuard<'_, SyncTrailState<'a>> {
+        self.state.lock().expect("the trail lock was poisoned")
+    }
+
+    /// Callback to remember what needs to be undone upon restoration of the state
+    pub fn push_on_trail(&self, entry: Box<dyn FnMut() + Send + 'a> ) {
+        self.lock().trail.push(entry)
+    }
+
+    /// Saves the current state so that it can be restored
+    /// with a pop. Increases the level by one.
+    pub fn push(&self) {
+        let mut state = self.lock();
+        state.clock += 1;
+        let size = state.trail.len();
+        state.limit.push( size );
+    }
+
+    /// Restores state as it was at level()-1
+    /// Decrease the level by 1
+    pub fn pop(&self) {
+        let mut state = self.lock();
+        let sz = state.limit.pop().unwrap_or(0);
+        while state.trail.len() > sz {
+            state.trail.pop().unwrap()();
+        }
+        state.clock += 1;
+    }
+
+    /// Restores the state as it was at level 0 (before the first push)
+    pub fn pop_all(&self) {
+        self.pop_until(0)
+    }
+
+    /// Restores the state as it was at level
+    pub fn pop_until(&self, level: usize) {
+        while self.level() > level {
+            self.pop()
+        }
+    }
+
+    /// Restores the state as it was n levels ago. When n exceeds the current
+    /// level, this simply restores the state as it was at level 0.
+    pub fn pop_n(&self, n: usize) {
+        let level = self.level().saturating_sub(n);
+        self.pop_until(level)
+    }
+
+    /// Returns the current level
+    pub fn level(&self) -> usize {
+        self.lock().limit.len()
+    }
+
+    /// Returns the current value of the clock
+    pub fn clock(&self) -> usize {
+        self.lock().clock
+    }
+
+    /// Returns the number of entries currently on the trail
+    pub fn len(&self) -> usize {
+        self.lock().trail.len()
+    }
+
+    /// Returns true iff there is no entry on the trail
+    pub fn is_empty(&self) -> bool {
+        self.lock().trail.is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Default for SyncTrail<'a> {
+    fn default() -> SyncTrail<'a> {
+        SyncTrail::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::panic;
+
+    #[test]
+    fn test_levels_are_zero_based() {
+        let mut trail = Trail::new();
+        assert_eq!(trail.level(), 0);
+        trail.push();
+        assert_eq!(trail.level(), 1);
+        trail.push();
+        trail.pop_all();
+        assert_eq!(trail.level(), 0);
+    }
+
+    #[test]
+    fn test_pop_n() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let value = Rc::new(Cell::new(0));
+        for i in 1..=4 {
+            trail.borrow_mut().push();
+            let dst = Rc::clone(&value);
+            let old = value.get();
+            trail.borrow_mut().push_on_trail(Box::new(move || dst.set(old)));
+            value.set(i);
+        }
+
+        trail.borrow_mut().pop_n(0);
+        assert_eq!(trail.borrow().level(), 4);
+        assert_eq!(value.get(), 4);
+
+        trail.borrow_mut().pop_n(1);
+        assert_eq!(trail.borrow().level(), 3);
+        assert_eq!(value.get(), 3);
+
+        trail.borrow_mut().pop_n(3);
+        assert_eq!(trail.borrow().level(), 0);
+        assert_eq!(value.get(), 0);
+
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        trail.borrow_mut().pop_n(42);
+        assert_eq!(trail.borrow().level(), 0);
+    }
+
+    /// Sets the value of the given cell and posts the entry that restores it
+    fn set(trail: &mut Trail, cell: &Rc<Cell<i32>>, value: i32) {
+        let dst = Rc::clone(cell);
+        let old = cell.get();
+        trail.push_on_trail(Box::new(move || dst.set(old)));
+        cell.set(value);
+    }
+
+    #[test]
+    fn test_nested_transactions() {
+        let mut trail = Trail::new();
+        let value = Rc::new(Cell::new(0));
+        set(&mut trail, &value, 1);
+
+        let seen = trail.transaction(|t| {
+            set(t, &value, 2);
+            let inner = t.transaction(|t| {
+                set(t, &value, 3);
+                t.push();
+                set(t, &value, 4);
+                value.get()
+            });
+            assert_eq!(inner, 4);
+            assert_eq!(t.level(), 1);
+            value.get()
+        });
+        assert_eq!(seen, 2);
+        assert_eq!(value.get(), 1);
+        assert_eq!(trail.level(), 0);
+    }
+
+    #[test]
+    fn test_try_transaction() {
+        let mut trail = Trail::new();
+        let value = Rc::new(Cell::new(0));
+
+        let err : Result<(), &str> = trail.try_transaction(|t| { set(t, &value, 1); Err("nope") });
+        assert!(err.is_err());
+        assert_eq!((value.get(), trail.level()), (0, 0));
+
+        let ok : Result<i32, ()> = trail.try_transaction(|t| {
+            set(t, &value, 2);
+            let _ = t.try_transaction(|t| { set(t, &value, 3); Err(()) as Result<(), ()> });
+            assert_eq!(value.get(), 2);
+            Ok(value.get())
+        });
+        assert_eq!(ok, Ok(2));
+        assert_eq!((value.get(), trail.level()), (2, 1));
+
+        trail.pop();
+        assert_eq!(value.get(), 0);
+    }
+
+    #[test]
+    fn test_transaction_pops_upon_panic() {
+        let mut trail = Trail::new();
+        let value = Rc::new(Cell::new(0));
+        let outcome = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            trail.transaction(|t| {
+                set(t, &value, 1);
+                panic!("boom");
+            })
+        }));
+        assert!(outcome.is_err());
+        assert_eq!((value.get(), trail.level()), (0, 0));
+    }
+
+    #[test]
+    fn test_compact_current_level() {
+        let mut trail = Trail::new();
+        let cells : Vec<Rc<Cell<i32>>> = (0..3).map(|_| Rc::new(Cell::new(0))).collect();
+        // a pathological propagator posting an entry upon each write
+        let write = |trail: &mut Trail, i: usize, value: i32| {
+            let dst = Rc::clone(&cells[i]);
+            let old = cells[i].get();
+            trail.push_on_trail_with_id(i, Box::new(move || dst.set(old)));
+            cells[i].set(value);
+        };
+
+        write(&mut trail, 0, 1);
+        trail.push();
+        for round in 1..=10 {
+            write(&mut trail, 0, 10 + round);
+            write(&mut trail, 1, 20 + round);
+        }
+        set(&mut trail, &cells[2], 30);
+        set(&mut trail, &cells[2], 31);
+        assert_eq!(trail.len(), 23);
+
+        assert_eq!(trail.compact_current_level(), 18);
+        assert_eq!(trail.len(), 5);
+        assert_eq!(trail.compact_current_level(), 0);
+        assert_eq!(trail.changed_since(0), vec![0, 1]);
+
+        trail.pop();
+        assert_eq!(cells.iter().map(|c| c.get()).collect::<Vec<i32>>(), vec![1, 0, 0]);
+        assert_eq!(trail.len(), 1);
+    }
+
+    #[test]
+    fn test_try_pop_until() {
+        let mut trail = Trail::new();
+        let value = Rc::new(Cell::new(0));
+        trail.push();
+        set(&mut trail, &value, 1);
+        trail.push();
+        set(&mut trail, &value, 2);
+
+        let err = trail.try_pop_until(3);
+        assert_eq!(err, Err(PopError { target: 3, level: 2 }));
+        assert_eq!(format!("{}", err.unwrap_err()), "cannot pop up to level 3 from level 2");
+        assert_eq!((trail.level(), value.get()), (2, 2));
+
+        assert_eq!(trail.try_pop_until(2), Ok(()));
+        assert_eq!((trail.level(), value.get()), (2, 2));
+
+        assert_eq!(trail.try_pop_until(1), Ok(()));
+        assert_eq!((trail.level(), value.get()), (1, 1));
+    }
+
+    #[test]
+    #[cfg(feature = "op_log")]
+    fn test_op_log() {
+        let mut trail = Trail::new();
+        trail.push();
+        trail.push();
+        trail.pop();
+        trail.push();
+        trail.push();
+        trail.pop_n(2);
+        let _ = trail.try_pop_until(4);
+        trail.pop_all();
+        assert_eq!(trail.op_log(), &[
+            TrailOp::Push, TrailOp::Push, TrailOp::Pop, TrailOp::Push, TrailOp::Push,
+            TrailOp::PopUntil(1), TrailOp::PopUntil(0)
+        ]);
+    }
+
+    #[test]
+    fn test_post_restore_hooks() {
+        let mut trail = Trail::new();
+        let value = Rc::new(Cell::new(0));
+        let log   = Rc::new(RefCell::new(vec![]));
+        let hook  = |name: &'static str| {
+            let log   = Rc::clone(&log);
+            let value = Rc::clone(&value);
+            Box::new(move || log.borrow_mut().push((name, value.get()))) as Box<dyn FnMut()>
+        };
+
+        trail.push();
+        set(&mut trail, &value, 1);
+        trail.push_post_restore(hook("first"));
+        trail.push();
+        trail.push_post_restore(hook("deep"));
+        set(&mut trail, &value, 2);
+        trail.push_post_restore(hook("second"));
+        set(&mut trail, &value, 3);
+
+        trail.pop();
+        assert_eq!(*log.borrow(), vec![("deep", 1), ("second", 1)]);
+        trail.pop();
+        assert_eq!(log.borrow()[2..], [("first", 0)]);
+        trail.pop();
+        assert_eq!(log.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_current_frame_start() {
+        let mut trail = Trail::new();
+        let value = Rc::new(Cell::new(0));
+        assert_eq!(trail.current_frame_start(), 0);
+        set(&mut trail, &value, 1);
+
+        trail.push();
+        assert_eq!(trail.current_frame_start(), 1);
+        set(&mut trail, &value, 2);
+        set(&mut trail, &value, 3);
+        trail.push();
+        set(&mut trail, &value, 4);
+        assert_eq!(trail.current_frame_start(), 3);
+        assert_eq!(trail.len() - trail.current_frame_start(), 1);
+
+        trail.pop();
+        assert_eq!(trail.current_frame_start(), 1);
+        trail.pop();
+        assert_eq!(trail.current_frame_start(), 0);
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut trail = Trail::with_capacity(100, 10);
+        let value = Rc::new(Cell::new(0));
+        let (entries, levels) = (trail.entries_capacity(), trail.levels_capacity());
+        assert!(entries >= 100 && levels >= 10);
+
+        for level in 0..10 {
+            trail.push();
+            for i in 0..10 {
+                set(&mut trail, &value, level * 10 + i);
+            }
+        }
+        assert_eq!((trail.len(), trail.level()), (100, 10));
+        assert_eq!((trail.entries_capacity(), trail.levels_capacity()), (entries, levels));
+
+        trail.pop_all();
+        assert_eq!(value.get(), 0);
+        trail.reserve_entries(1000);
+        trail.reserve_levels(50);
+        assert!(trail.entries_capacity() >= 1000 && trail.levels_capacity() >= 50);
+    }
+
+    #[test]
+    fn test_level_finalizers() {
+        let mut trail = Trail::new();
+        let log = Rc::new(RefCell::new(vec![]));
+        let l = Rc::clone(&log);
+        trail.register_level_finalizer(Box::new(move |level| l.borrow_mut().push(format!("fin {}", level))));
+
+        trail.push();
+        trail.push();
+        let l = Rc::clone(&log);
+        trail.push_on_trail(Box::new(move || l.borrow_mut().push("entry".to_string())));
+        let l = Rc::clone(&log);
+        trail.push_post_restore(Box::new(move || l.borrow_mut().push("hook".to_string())));
+
+        trail.pop();
+        assert_eq!(*log.borrow(), vec!["entry", "hook", "fin 2"]);
+        trail.pop();
+        assert_eq!(log.borrow()[3..], ["fin 1"]);
+
+        // the finalizer is persistent and fires once per popped level
+        trail.push();
+        trail.push();
+        trail.push();
+        trail.pop_until(1);
+        assert_eq!(log.borrow()[4..], ["fin 3", "fin 2"]);
+        trail.pop_all();
+        assert_eq!(log.borrow()[6..], ["fin 1"]);
+        assert_eq!(log.borrow().len(), 7);
+    }
+
+    #[test]
+    fn test_fork_is_independent() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+        let mut b = Reversible::new(Rc::clone(&trail), 'x');
+        let ha = a.track();
+        let hb = b.track();
+
+        trail.borrow_mut().push();
+        a.set_value(2);
+        b.set_value('y');
+
+        let mut fork = trail.borrow().fork();
+        let mut fa : Reversible<i32>  = fork.take(ha).unwrap();
+        let fb : Reversible<char> = fork.take(hb).unwrap();
+        assert!(fork.take::<i32>(ha).is_none());
+        assert_eq!((fa.get_value(), fb.get_value()), (2, 'y'));
+
+        fork.trail.borrow_mut().push();
+        fa.set_value(42);
+        assert_eq!(a.get_value(), 2);
+        a.set_value(3);
+        assert_eq!(fa.get_value(), 42);
+
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 1);
+        assert_eq!(fa.get_value(), 42);
+
+        fork.trail.borrow_mut().pop();
+        assert_eq!(fa.get_value(), 2);
+        assert_eq!((a.get_value(), b.get_value()), (1, 'x'));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_ambient_trail_nested_scopes() {
+        let outer = Rc::new(RefCell::new(Trail::new()));
+        let inner = Rc::new(RefCell::new(Trail::new()));
+        assert!(Trail::ambient().is_none());
+
+        let (mut a, mut b, mut c) = Trail::install(Rc::clone(&outer), || {
+            let a = Reversible::new_ambient(1);
+            let b = Trail::install(Rc::clone(&inner), || {
+                assert!(Rc::ptr_eq(&Trail::ambient().unwrap(), &inner));
+                Reversible::new_ambient(2)
+            });
+            // the inner install no longer shadows the outer one
+            assert!(Rc::ptr_eq(&Trail::ambient().unwrap(), &outer));
+            (a, b, Reversible::new_ambient(3))
+        });
+        assert!(Trail::ambient().is_none());
+
+        // the reversibles outlive the install and stay on their own trail
+        outer.borrow_mut().push();
+        a.set_value(10);
+        b.set_value(20);
+        c.set_value(30);
+        outer.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value(), c.get_value()), (1, 20, 3));
+
+        inner.borrow_mut().push();
+        b.set_value(21);
+        inner.borrow_mut().pop();
+        assert_eq!(b.get_value(), 20);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_ambient_trail_is_uninstalled_upon_panic() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            Trail::install(Rc::clone(&trail), || panic!("boom"))
+        }));
+        assert!(result.is_err());
+        assert!(Trail::ambient().is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[should_panic(expected = "Reversible::new_ambient requires an ambient trail")]
+    fn test_new_ambient_without_install_panics() {
+        Reversible::new_ambient(0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    #[should_panic(expected = "ReversibleSparseSet::new_ambient requires an ambient trail")]
+    fn test_sparse_set_new_ambient_outside_of_install_panics() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let set = Trail::install(trail, || ::reversible::ReversibleSparseSet::new_ambient(3));
+        assert_eq!(set.size(), 3);
+        ::reversible::ReversibleSparseSet::new_ambient(3);
     }
 }
@@ -1,7 +1,28 @@
 //! This library provides you with a set of types that can be used to
 //! implement a trailing CP-solver similar to minicp.
 //!
+//! # Features
+//!   - `std` (enabled by default): provides the types which need the standard
+//!     library, namely `SyncTrail`, `SyncReversible`, the hash based
+//!     `ReversibleSparseSetMap` and the ambient trail (`Trail::install`).
+//!     Without it, the crate is `no_std` and only requires an allocator
+//!     (through the `alloc` crate).
+//!   - `op_log`: makes the trail record each of its structural operations (see
+//!     `Trail::op_log`), e.g. to compare the search performed by two runs.
+//!   - `serde`: implements the (de)serialization of the current values of the
+//!     reversibles and of the current contents of the reversible collections.
+//!
 //! # Credits
 //! The design of the library whas *heavily* inspired by that of minicp.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "serde")]
+extern crate serde;
+
 pub mod context;
-pub mod reversible;
\ No newline at end of file
+pub mod reversible;
+pub mod search;
+pub mod model;
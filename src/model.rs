@@ -0,0 +1,117 @@
+//! This module provides a thin facade over the trail which spares the client
+//! code from plumbing (and cloning) the shared trail through every function
+//! that builds a part of the model.
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::{Reversible, ReversibleSparseSet, TrailedIntVar};
+
+/// A model owns the shared trail and hands out the reversible objects which
+/// are associated with it. Each of its constructors clones the shared trail
+/// internally, hence the client code never needs to touch the `Rc` (although
+/// it remains available through `trail()` for the types that lack a dedicated
+/// constructor).
+pub struct Model<'a> {
+    trail: Rc<RefCell<Trail<'a>>>
+}
+
+impl<'a> Model<'a> {
+    /// Creates a new model with a fresh trail
+    pub fn new() -> Model<'a> {
+        Model { trail: Rc::new(RefCell::new(Trail::new())) }
+    }
+
+    /// Creates a new model whose reversibles are associated with the given
+    /// (possibly shared) trail.
+    pub fn with_trail(trail: Rc<RefCell<Trail<'a>>>) -> Model<'a> {
+        Model { trail }
+    }
+
+    /// Returns the trail shared by all the reversibles of the model
+    pub fn trail(&self) -> &Rc<RefCell<Trail<'a>>> {
+        &self.trail
+    }
+
+    /// Returns the current level of the trail
+    pub fn level(&self) -> usize {
+        self.trail.borrow().level()
+    }
+
+    /// Pushes a new level on the trail (see `Trail::push`)
+    pub fn push(&self) {
+        self.trail.borrow_mut().push()
+    }
+
+    /// Pops the last level of the trail (see `Trail::pop`)
+    pub fn pop(&self) {
+        self.trail.borrow_mut().pop()
+    }
+
+    /// Creates a reversible holding the given value
+    pub fn reversible<T>(&self, initial: T) -> Reversible<'a, T>
+        where T: Copy + PartialEq + 'a {
+        Reversible::new(Rc::clone(&self.trail), initial)
+    }
+
+    /// Creates a reversible integer holding the given value
+    pub fn int(&self, initial: isize) -> Reversible<'a, isize> {
+        self.reversible(initial)
+    }
+
+    /// Creates a reversible boolean holding the given value
+    pub fn bool(&self, initial: bool) -> Reversible<'a, bool> {
+        self.reversible(initial)
+    }
+
+    /// Creates a reversible sparse set initially containing `0..capacity`
+    pub fn sparse_set(&self, capacity: usize) -> ReversibleSparseSet<'a> {
+        ReversibleSparseSet::new(Rc::clone(&self.trail), capacity)
+    }
+
+    /// Creates an integer variable whose domain initially is `lo..=hi`
+    pub fn int_var(&self, lo: isize, hi: isize) -> TrailedIntVar<'a> {
+        TrailedIntVar::new(Rc::clone(&self.trail), lo, hi)
+    }
+}
+
+impl<'a> Default for Model<'a> {
+    fn default() -> Self {
+        Model::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A model building function which never sees an `Rc`
+    fn loads<'a>(model: &Model<'a>, n: usize) -> Vec<Reversible<'a, isize>> {
+        (0..n).map(|_| model.int(0)).collect()
+    }
+
+    #[test]
+    fn test_small_model() {
+        let model = Model::new();
+        let mut taken  = model.bool(false);
+        let mut weight = model.int(0);
+        let mut items  = model.sparse_set(4);
+        let x = model.int_var(-2, 2);
+        let mut bins = loads(&model, 3);
+
+        model.push();
+        taken.set_value(true);
+        weight += 7;
+        items.remove(2);
+        bins[1].set_value(5);
+        assert_eq!(model.level(), 1);
+        assert_eq!(model.trail().borrow().level(), 1);
+
+        model.pop();
+        assert!(!taken.get_value());
+        assert_eq!(weight, 0);
+        assert_eq!(items.size(), 4);
+        assert!(bins.iter().all(|b| b.get_value() == 0));
+        assert_eq!((x.min(), x.max()), (-2, 2));
+    }
+}
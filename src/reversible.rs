@@ -2,12 +2,115 @@
 //! as basic types to implement the variables of your CP model.
 //! Namely, this submodule provides the following types:
 //!   - Reversible (an object (primitive) whose value can be automagically reset.
+//!   - ReversibleFloat (a reversible f64 whose changes are detected bitwise).
+//!   - ReversibleSegmentTree (range queries over an array of reversible entries).
+//!   - ReversibleTrie (a trie over a small alphabet whose edges can be deactivated).
+//!   - ReversibleMddStore (a layered MDD whose nodes and edges can be deleted).
+//!   - ReversibleBitSet (a set of integers trailed at word granularity).
+//!   - ReversibleAutomaton (the reachable states of each layer of an unfolded DFA).
+//!   - ReversibleSparseSet (a set of integers supporting O(1) removal and restoration).
+//!   - ArenaSparseSet (a sparse set operating on windows of a caller supplied arena).
+//!   - ReversibleSparseSetMap (a sparse set over arbitrary hashable keys).
+//!   - ReversibleSparseMatrix (a matrix whose rows and columns can be removed).
+//!   - SyncReversible (the thread-safe counterpart of Reversible, bound to a SyncTrail).
+//!   - ReversibleWatchList (per-value watch lists with lazy invalidation of moved watchers).
+//!   - ReversibleSortedVec (an ordered set of keys with trailed insertions and removals).
+//!   - ReversibleRangeMap (a map from disjoint integer ranges to values).
+//!   - DomainStore (an arena of sparse-set domains sharing one trail).
+//!   - ReversibleStrTable (the valid tuples of a table constraint, for STR filtering).
+//!   - TrailedIntVar (an integer variable notifying listeners about its domain changes).
+//!   - ReversibleDistanceCache (the nearest still available neighbors of each node).
+//!   - ReversibleHistogram (bucketed counts restored upon backtrack).
+//!   - ReversiblePropagationQueue (the constraints pending propagation, without duplicates).
+//!   - ReversibleView (a read-only value derived from one or more reversibles).
+//!   - ReversibleMinMax (the min and max of a set of candidate values, with holes).
+//!   - ReversibleGroup (a group of reversibles with a local checkpoint/rollback).
+//!   - ReversibleEnum and ReversibleEnumSet (a variable and a set over the variants of an enum).
+//!   - AlwaysTrailed (a reversible cell for the types without a reliable `PartialEq`).
+//!   - Transaction (a batch of writes restored by one single trail entry).
+//!   - TrackedReversible (a reversible cell remembering its value at each level).
+//!   - ReversibleStackOfStacks (one stack per key, all backed by one single vector).
+//!   - ReversibleLazy (a value computed at most once per branch, cleared upon backtrack).
+//!
+//! The `slice` submodule provides aggregate reads (sum, min index, ...) over
+//! slices of reversibles.
 
-use std::rc::Rc;
-use std::cell::{Cell, RefCell};
-use std::fmt;
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::{Rc, Weak};
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::cmp::Ordering;
+use core::any::Any;
+use core::ops::{Add, Sub, Mul, AddAssign, SubAssign, MulAssign};
 
-use ::context::Trail;
+use ::context::{Trail, TrailStatus};
+
+mod float;
+mod segment_tree;
+mod trie;
+mod mdd;
+mod bitset;
+mod automaton;
+mod sparse_set;
+mod arena_sparse_set;
+#[cfg(feature = "std")]
+mod sparse_set_map;
+mod sparse_matrix;
+#[cfg(feature = "std")]
+mod sync;
+mod watch_list;
+mod sorted_vec;
+mod range_map;
+mod domain_store;
+mod str_table;
+mod int_var;
+mod distance_cache;
+mod histogram;
+mod propagation_queue;
+mod view;
+mod min_max;
+mod group;
+mod enumeration;
+mod always_trailed;
+mod transaction;
+mod tracked;
+mod stack_of_stacks;
+mod lazy;
+pub mod slice;
+pub use self::float::ReversibleFloat;
+pub use self::segment_tree::{ReversibleSegmentTree, Monoid, Bounded, Sum, Min, Max};
+pub use self::trie::{ReversibleTrie, Children};
+pub use self::mdd::{ReversibleMddStore, LiveNodes};
+pub use self::bitset::{ReversibleBitSet, BitSetIter};
+pub use self::automaton::ReversibleAutomaton;
+pub use self::sparse_set::{ReversibleSparseSet, SparseSetIter, DomainEvent};
+pub use self::arena_sparse_set::ArenaSparseSet;
+#[cfg(feature = "serde")]
+pub use self::sparse_set::SparseSetSeed;
+#[cfg(feature = "std")]
+pub use self::sparse_set_map::{ReversibleSparseSetMap, SparseSetMapIter};
+pub use self::sparse_matrix::ReversibleSparseMatrix;
+#[cfg(feature = "std")]
+pub use self::sync::SyncReversible;
+pub use self::watch_list::{ReversibleWatchList, WatchEntry};
+pub use self::sorted_vec::{ReversibleSortedVec, SortedVecIter};
+pub use self::range_map::{ReversibleRangeMap, RangeMapIter};
+pub use self::domain_store::{DomainStore, DomainId};
+pub use self::str_table::{ReversibleStrTable, StrTableIter};
+pub use self::int_var::{TrailedIntVar, Wipeout};
+pub use self::distance_cache::ReversibleDistanceCache;
+pub use self::histogram::{ReversibleHistogram, HistogramIter};
+pub use self::propagation_queue::ReversiblePropagationQueue;
+pub use self::view::{ReversibleView, zip_map};
+pub use self::min_max::ReversibleMinMax;
+pub use self::group::ReversibleGroup;
+pub use self::enumeration::{Enumeration, ReversibleEnum, ReversibleEnumSet, EnumSetIter};
+pub use self::always_trailed::AlwaysTrailed;
+pub use self::transaction::{Transaction, transaction};
+pub use self::tracked::TrackedReversible;
+pub use self::stack_of_stacks::{ReversibleStackOfStacks, StackIter};
+pub use self::lazy::ReversibleLazy;
 
 /// This is the reversible object abstraction. It holds a reference to its
 /// parent context. This way, it will be able to post entries on the trail.
@@ -17,7 +120,7 @@ use ::context::Trail;
 /// for the trail and value field might seem somewhat cumbersome. However, these
 /// are actually simpler than meets the eye.
 ///
-///  - The lifetime _<'a>_ is used to tell the compiler that it needs to ensure
+///   - The lifetime _<'a>_ is used to tell the compiler that it needs to ensure
 ///     that whenever we push some restoration closure on the trail, any references
 ///     it holds must live at least as long as <'a> (the scope of the trail).
 ///     Given that the parameter type <T> forces the bound `Copy`, this should
@@ -48,8 +151,87 @@ use ::context::Trail;
 pub struct Reversible<'a, T>
     where T: Copy + PartialEq + 'a {
     trail: Rc<RefCell<Trail<'a>>>,
-    clock: usize,
-    value: Rc<Cell<T>>
+    value: Rc<Cell<T>>,
+    /// The clock and restoration flag of the trail
+    status: Rc<TrailStatus>,
+    /// The (optional) stable id reported by `Trail::changed_since`
+    id: Option<usize>,
+    /// The state shared by all the aliases of this reversible (see `Clone`)
+    shared: Rc<Shared<'a, T>>
+}
+
+/// The identity of the cell of a reversible (see `Reversible::identity`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ReversibleId(usize);
+
+/// The handle of a change listener registered on a reversible
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Subscription(usize);
+
+/// A change listener: it is called with the old and the new value
+type Listener<'a, T> = Box<dyn FnMut(T, T) + 'a>;
+
+/// The invariant of a validated reversible: it tells whether a value is valid
+type Validator<'a, T> = Box<dyn Fn(&T) -> bool + 'a>;
+
+/// The error returned by `Reversible::try_set` when the value it was asked to
+/// store does not satisfy the invariant of the reversible. It holds the
+/// rejected value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidValue<T>(pub T);
+
+impl<T: fmt::Debug> fmt::Display for InvalidValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "the value {:?} violates the invariant of the reversible", self.0)
+    }
+}
+
+/// The state of a reversible which is shared by all its aliases (and by its
+/// restoration closures)
+struct Shared<'a, T> {
+    /// The clock of the trail when an entry was last posted for this reversible
+    clock    : Cell<usize>,
+    /// The clock of the trail when this reversible was created (or frozen)
+    born     : Cell<usize>,
+    /// The clock of the trail when the value of this reversible last changed
+    updated  : Cell<usize>,
+    /// The value saved by the last entry posted for this reversible
+    saved    : Cell<T>,
+    /// The number of times this reversible has been frozen. The restoration
+    /// closures posted before the last freeze are ignored.
+    frozen   : Cell<usize>,
+    /// The identifier of the next subscription
+    next     : Cell<usize>,
+    /// Whether the listeners must also be notified upon restoration
+    on_pop   : Cell<bool>,
+    /// The invariant which the values of a validated reversible must satisfy
+    validator: Option<Validator<'a, T>>,
+    /// The (optional) label which identifies this reversible in diagnostics
+    name     : Option<Rc<str>>,
+    callbacks: RefCell<Vec<(Subscription, Listener<'a, T>)>>
+}
+
+impl<'a, T: Copy> Shared<'a, T> {
+    fn new(clock: usize, initial: T) -> Shared<'a, T> {
+        Shared {
+            clock    : Cell::new(clock),
+            born     : Cell::new(clock),
+            updated  : Cell::new(clock),
+            saved    : Cell::new(initial),
+            frozen   : Cell::new(0),
+            next     : Cell::new(0),
+            on_pop   : Cell::new(false),
+            validator: None,
+            name     : None,
+            callbacks: RefCell::new(vec![])
+        }
+    }
+
+    fn notify(&self, old: T, new: T) {
+        for (_, callback) in self.callbacks.borrow_mut().iter_mut() {
+            callback(old, new);
+        }
+    }
 }
 
 impl<'a, T> Reversible<'a, T>
@@ -59,25 +241,255 @@ impl<'a, T> Reversible<'a, T>
     pub fn new(trail: Rc<RefCell<Trail>>, initial: T) -> Reversible<T> {
         let clock = trail.borrow().clock();
         let value = Rc::new(Cell::new(initial));
+        let status = trail.borrow().status();
         Reversible {
             trail,
-            clock,
-            value
+            value,
+            status,
+            id: None,
+            shared: Rc::new(Shared::new(clock, initial))
+        }
+    }
+
+    /// Creates n reversible objects associated with the given trail, the i-th
+    /// of which is initialized with `init(i)`.
+    pub fn new_array<F>(trail: &Rc<RefCell<Trail<'a>>>, n: usize, init: F) -> Vec<Reversible<'a, T>>
+        where F: Fn(usize) -> T {
+        (0..n).map(|i| Reversible::new(Rc::clone(trail), init(i))).collect()
+    }
+
+    /// Creates one reversible object per item of `inits`, associated with the
+    /// given trail and initialized with that item.
+    pub fn array(trail: &Rc<RefCell<Trail<'a>>>, inits: &[T]) -> Vec<Reversible<'a, T>> {
+        Reversible::new_array(trail, inits.len(), |i| inits[i])
+    }
+
+    /// Creates n reversible objects associated with the given trail, all of
+    /// which are initialized with the given value.
+    pub fn filled(trail: &Rc<RefCell<Trail<'a>>>, n: usize, initial: T) -> Vec<Reversible<'a, T>> {
+        Reversible::new_array(trail, n, |_| initial)
+    }
+
+    /// Same as `new_array`, but the number of reversibles is known at compile
+    /// time and they are returned as an array.
+    pub fn new_array_const<const N: usize, F>(trail: &Rc<RefCell<Trail<'a>>>, init: F) -> [Reversible<'a, T>; N]
+        where F: Fn(usize) -> T {
+        core::array::from_fn(|i| Reversible::new(Rc::clone(trail), init(i)))
+    }
+
+    /// Creates a new reversible object labelled with the given name, which
+    /// shows up in its `Debug` output (see `name`).
+    pub fn new_named(trail: Rc<RefCell<Trail<'a>>>, initial: T, name: &str) -> Reversible<'a, T> {
+        let mut rev = Reversible::new(trail, initial);
+        Rc::get_mut(&mut rev.shared).unwrap().name = Some(Rc::from(name));
+        rev
+    }
+
+    /// Same as `new_array`, except that the i-th reversible is named after the
+    /// given prefix (e.g. `x[17]` for the prefix `x`).
+    pub fn new_array_named<F>(trail: &Rc<RefCell<Trail<'a>>>, prefix: &str, n: usize, init: F) -> Vec<Reversible<'a, T>>
+        where F: Fn(usize) -> T {
+        (0..n).map(|i| Reversible::new_named(Rc::clone(trail), init(i), &format!("{}[{}]", prefix, i))).collect()
+    }
+
+    /// Returns the name of this reversible (None if it was not created with
+    /// `new_named` or `new_array_named`). All the aliases share the same name.
+    pub fn name(&self) -> Option<&str> {
+        self.shared.name.as_deref()
+    }
+
+    /// Creates a new reversible object whose values must satisfy the given
+    /// invariant (e.g. `|&v| v <= capacity`). The writes which violate it are
+    /// rejected by `try_set`, and they make `set_value` (hence also `update`,
+    /// `replace`, ...) panic in debug builds.
+    ///
+    /// # Panics
+    /// When the initial value does not satisfy the invariant.
+    pub fn new_validated<V>(trail: Rc<RefCell<Trail<'a>>>, initial: T, validator: V) -> Reversible<'a, T>
+        where V: Fn(&T) -> bool + 'a {
+        assert!(validator(&initial), "the initial value violates the invariant of the reversible");
+        let mut rev = Reversible::new(trail, initial);
+        Rc::get_mut(&mut rev.shared).unwrap().validator = Some(Box::new(validator));
+        rev
+    }
+
+    /// Returns true iff v satisfies the invariant of this reversible (always
+    /// true when the reversible is not validated)
+    pub fn is_valid(&self, v: &T) -> bool {
+        self.shared.validator.as_ref().is_none_or(|valid| valid(v))
+    }
+
+    /// Changes the current value of the reversible and returns the previous one
+    /// iff v satisfies its invariant. Otherwise, the value and the trail are
+    /// left untouched and v is returned in the error.
+    pub fn try_set(&mut self, v: T) -> Result<T, InvalidValue<T>> {
+        if self.is_valid(&v) {
+            Ok(self.set(v))
+        } else {
+            Err(InvalidValue(v))
+        }
+    }
+
+    /// Checks (in debug builds) that v satisfies the invariant of this reversible
+    fn check(&self, v: &T) {
+        debug_assert!(self.is_valid(v), "the value violates the invariant of the reversible");
+    }
+
+    /// Assigns a stable id to this reversible. From then on, the entries this
+    /// reversible posts on the trail are tagged with that id so that it is
+    /// reported by `Trail::changed_since`.
+    pub fn with_id(mut self, id: usize) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Returns a read-only handle to the value of this reversible. The reader
+    /// shares the cell of the reversible (it observes all the live updates)
+    /// but it does not borrow the reversible itself. Hence, a propagator can
+    /// keep readers of the variables it only reads while mutating the other
+    /// variables of the same collection.
+    pub fn reader(&self) -> ReversibleReader<T> {
+        ReversibleReader { value: Rc::clone(&self.value) }
+    }
+
+    /// Returns a weak handle to the value of this reversible, which does not
+    /// keep the cell alive (see `WeakReversible`)
+    pub fn downgrade(&self) -> WeakReversible<T> {
+        WeakReversible { value: Rc::downgrade(&self.value), identity: self.identity() }
+    }
+
+    /// Returns a read-only view whose value is the current value of this
+    /// reversible mapped through f (e.g. `r.map(|v| v * 2)`).
+    pub fn map<U, F>(&self, f: F) -> ReversibleView<'a, U>
+        where U: 'a, F: Fn(T) -> U + 'a {
+        let reader = self.reader();
+        ReversibleView::from_fn(move || f(reader.get()))
+    }
+
+    /// Returns the clock of the trail when this reversible last posted an entry
+    /// on the trail (or when it was created)
+    pub fn clock(&self) -> usize {
+        self.shared.clock.get()
+    }
+
+    /// Returns the id of this reversible (if it has one)
+    pub fn id(&self) -> Option<usize> {
+        self.id
+    }
+
+    /// Consumes this reversible and returns its current value. The entries
+    /// which this reversible posted on the trail keep their own handle to the
+    /// cell: running them later (upon pop) is harmless, it merely restores a
+    /// value that nobody can observe anymore.
+    pub fn into_inner(self) -> T {
+        self.value.get()
+    }
+
+    /// Consumes this reversible and returns its current value iff nothing else
+    /// references its cell: no alias, no reader, no view and no pending entry
+    /// on the trail (that is, no level in which it was changed remains to be
+    /// popped). Otherwise, the reversible is given back untouched.
+    pub fn try_unwrap(self) -> Result<T, Reversible<'a, T>> {
+        if Rc::strong_count(&self.value) == 1 {
+            Ok(self.value.get())
+        } else {
+            Err(self)
         }
     }
 
+    /// Returns the identity of the cell of this reversible. All the aliases of
+    /// a reversible (see `Clone`) have the same identity, which never changes
+    /// (not even upon backtrack), and two distinct reversibles which are alive
+    /// at the same time have distinct identities. This makes it suitable as the
+    /// key of a map (e.g. from the variables to their subscribed propagators).
+    ///
+    /// # Note
+    /// The identity is derived from the address of the cell. Hence, it may be
+    /// reused by a reversible created after all the aliases of this one have
+    /// been dropped. Use `with_id` if you need ids that are never reused.
+    pub fn identity(&self) -> ReversibleId {
+        ReversibleId(Rc::as_ptr(&self.value) as usize)
+    }
+
+    /// Registers this reversible with its trail so that its current value is
+    /// copied whenever the trail is forked. Returns the handle which identifies
+    /// this reversible in the fork (see `Trail::fork` and `Fork::take`).
+    pub fn track(&self) -> usize
+        where T: 'static {
+        let value = Rc::downgrade(&self.value);
+        self.trail.borrow_mut().track(Box::new(move || {
+            value.upgrade().map(|v| Box::new(v.get()) as Box<dyn Any>)
+        }))
+    }
+
+    /// Returns true iff the trail of this reversible is currently being
+    /// restored (in which case the reversible must not be mutated). Unlike
+    /// `Trail::is_restoring`, this does not borrow the trail and can thus be
+    /// called from a post-restore hook or a level finalizer.
+    pub(crate) fn is_restoring(&self) -> bool {
+        self.status.restoring.get()
+    }
+
     /// This private method takes care of posting an entry on the trail
     /// so as to easily restore the current state.
-    fn trail(&mut self) {
-        let trail_time = self.trail.borrow().clock();
-
-        if trail_time != self.clock {
-            self.clock = trail_time;
+    ///
+    /// # Panics
+    /// When called from a restoration closure (while the trail is being
+    /// restored). Mutating a reversible at that moment is always a bug. Also
+    /// panics when the trail is already borrowed, with a message naming the
+    /// operation (`op`) and the id of this reversible (if it has one).
+    fn trail(&self, op: &str) {
+        if let Some(entry) = self.claim() {
+            let mut trail = match self.trail.try_borrow_mut() {
+                Ok(trail) => trail,
+                Err(_)    => match self.id {
+                    Some(id) => panic!("Reversible::{} (id {}) cannot post its entry: the trail is already borrowed", op, id),
+                    None     => panic!("Reversible::{} cannot post its entry: the trail is already borrowed", op)
+                }
+            };
+            match self.id {
+                Some(id) => trail.push_on_trail_with_id(id, entry),
+                None     => trail.push_on_trail(entry)
+            }
+        }
+    }
 
-            let val = self.value.get();
-            let dst = Rc::clone(&self.value);
-            self.trail.borrow_mut().push_on_trail(Box::new(move || dst.set(val)));
+    /// Returns the closure which restores the current state of this reversible
+    /// iff no entry has been posted for it in the current level yet (in which
+    /// case it is from now on considered to be posted). The caller is
+    /// responsible for pushing the closure on the trail.
+    ///
+    /// # Panics
+    /// When called while the trail is being restored.
+    fn claim(&self) -> Option<Box<dyn FnMut() + 'a>> {
+        if self.status.restoring.get() {
+            panic!("cannot mutate a reversible while the trail is being restored");
         }
+        let trail_time = self.status.clock.get();
+        if trail_time == self.shared.clock.get() {
+            return None;
+        }
+        self.shared.clock.set(trail_time);
+
+        let val = self.value.get();
+        self.shared.saved.set(val);
+        let dst = Rc::clone(&self.value);
+        let shr = Rc::clone(&self.shared);
+        let sts = Rc::clone(&self.status);
+        let gen = shr.frozen.get();
+        Some(Box::new(move || {
+            if shr.frozen.get() != gen {
+                return;
+            }
+            let cur = dst.get();
+            dst.set(val);
+            if cur != val {
+                shr.updated.set(sts.clock.get());
+                if shr.on_pop.get() {
+                    shr.notify(cur, val);
+                }
+            }
+        }))
     }
 
     /// Returns the current value of the reversible object
@@ -86,15 +498,369 @@ impl<'a, T> Reversible<'a, T>
     }
 
     /// Changes the current value of the reversible object.
-    /// returns the current value
+    /// Returns the previous value of the reversible object. Hence, the returned
+    /// value equals `v` iff the call did not change anything (in which case no
+    /// entry is posted on the trail and no listener is notified).
     pub fn set_value(&mut self, v: T) -> T {
-        if v != self.value.get() {
-            self.trail();
+        self.set(v)
+    }
+
+    /// Same as `set_value`, but usable through a shared reference (e.g. from a
+    /// `&self` method of a propagator, or when the reversible lives in an `Rc`).
+    ///
+    /// # Aliasing
+    /// The value, the trail and the bookkeeping of a reversible are interiorly
+    /// mutable, hence this is as safe as `set_value`. It however means that a
+    /// `&Reversible` no longer guarantees that the value does not change while
+    /// you hold it (exactly as with a `&Cell`). Also note that a listener must
+    /// not change the value of the reversible which notifies it.
+    pub fn set(&self, v: T) -> T {
+        self.check(&v);
+        let old = self.value.get();
+        if v != old {
+            self.trail("set_value");
             self.value.set(v);
+            self.changed(old, v);
         }
-        self.value.get()
+        old
+    }
+
+    /// Same as `update`, but usable through a shared reference (see `set`)
+    pub fn set_with<F: FnOnce(T) -> T>(&self, f: F) -> T {
+        let v = f(self.value.get());
+        self.set(v);
+        v
+    }
+
+    /// Replaces the value of this reversible and returns the previous one, just
+    /// like `Cell::replace`. This is the same as `set_value`: nothing is posted
+    /// on the trail when v equals the current value.
+    pub fn replace(&mut self, v: T) -> T {
+        self.set_value(v)
+    }
+
+    /// Replaces the value of this reversible with the default value of T and
+    /// returns the previous one, just like `Cell::take`. Taking from a
+    /// reversible which already holds the default value does not post anything
+    /// on the trail.
+    pub fn take(&mut self) -> T
+        where T: Default {
+        self.set_value(T::default())
+    }
+
+    /// Temporarily sets the value of this reversible to `tmp` while `f` runs,
+    /// and puts the original value back when it returns (or panics). This is
+    /// typically useful to evaluate a heuristic "as if" the reversible held
+    /// another value. Since the original value is back by the time this
+    /// returns, the temporary write posts nothing on the trail and the
+    /// listeners are not notified (neither of the write nor of the restore).
+    ///
+    /// # Note
+    /// The closure can observe the temporary value through the readers, views
+    /// and aliases of this reversible. It must however not change the value of
+    /// the reversible itself (e.g. through an alias): such a change would be
+    /// overwritten when `f` returns, and the entry it would post on the trail
+    /// would save the temporary value. The closure may safely push and pop the
+    /// trail as long as it pops all the levels it pushed.
+    pub fn with_value<R, F: FnOnce() -> R>(&mut self, tmp: T, f: F) -> R {
+        let _restore = RestoreOnDrop { cell: Rc::clone(&self.value), value: self.value.replace(tmp) };
+        f()
+    }
+
+    /// Exchanges the values of this reversible and the other one (which must
+    /// be associated with the same trail). Both reversibles are trailed (at
+    /// most once per level, as usual) before any of them is modified, and the
+    /// listeners are only notified once the exchange is complete: they never
+    /// observe a state where both reversibles hold the same value. Nothing is
+    /// done when both values are equal.
+    ///
+    /// # Note
+    /// Upon pop, the two values are restored one after the other. Hence, the
+    /// listeners which are notified of the restorations (see `notify_on_pop`)
+    /// may observe the intermediate state.
+    pub fn swap(&mut self, other: &mut Reversible<'a, T>) {
+        let mine  = self.value.get();
+        let yours = other.value.get();
+        self.check(&yours);
+        other.check(&mine);
+        if mine != yours {
+            self.trail("swap");
+            other.trail("swap");
+            self.value.set(yours);
+            other.value.set(mine);
+            self.changed(mine, yours);
+            other.changed(yours, mine);
+        }
+    }
+
+    /// Records that the value of this reversible has changed from old to new
+    /// and notifies the listeners
+    fn changed(&self, old: T, new: T) {
+        self.shared.updated.set(self.status.clock.get());
+        self.shared.notify(old, new);
+    }
+
+    /// Registers a listener which is notified (synchronously, with the old and
+    /// the new value) each time the value of this reversible changes. Returns
+    /// the handle to use in order to unsubscribe.
+    ///
+    /// # Note
+    /// By default, the listeners are *not* notified when the value is restored
+    /// upon pop (see `notify_on_pop`). A listener must neither register nor
+    /// unsubscribe any listener on the reversible which notifies it.
+    pub fn on_change<F>(&mut self, callback: F) -> Subscription
+        where F: FnMut(T, T) + 'a {
+        let subscription = Subscription(self.shared.next.get());
+        self.shared.next.set(subscription.0 + 1);
+        self.shared.callbacks.borrow_mut().push((subscription, Box::new(callback)));
+        subscription
+    }
+
+    /// Registers a listener which is notified each time the value of this
+    /// reversible changes *to* the given target (e.g. "wake me up when this
+    /// counter becomes 0"). The other changes are ignored. Just like the other
+    /// listeners, it is notified of the restorations iff `notify_on_pop` says
+    /// so, and it can be unregistered with `unsubscribe`.
+    pub fn on_set_to<F>(&mut self, target: T, mut callback: F) -> Subscription
+        where F: FnMut() + 'a {
+        self.on_change(move |old, new| {
+            if new == target && old != target {
+                callback();
+            }
+        })
+    }
+
+    /// Unregisters the given listener. Returns true iff it was registered.
+    pub fn unsubscribe(&mut self, subscription: Subscription) -> bool {
+        let mut callbacks = self.shared.callbacks.borrow_mut();
+        let len = callbacks.len();
+        callbacks.retain(|&(s, _)| s != subscription);
+        callbacks.len() != len
+    }
+
+    /// Decides whether the listeners are also notified when the value of this
+    /// reversible is restored upon pop. These notifications happen while the
+    /// trail is being restored: the listeners must not mutate any reversible.
+    pub fn notify_on_pop(&mut self, notify: bool) {
+        self.shared.on_pop.set(notify);
+    }
+
+    /// Stores the candidate iff `pred(&current, &candidate)` holds. Returns true
+    /// iff the candidate was stored. Nothing at all happens when the predicate
+    /// rejects the candidate (and, as usual, nothing is trailed when an
+    /// accepted candidate equals the current value).
+    pub fn set_if<P>(&mut self, candidate: T, pred: P) -> bool
+        where P: FnOnce(&T, &T) -> bool {
+        if pred(&self.value.get(), &candidate) {
+            self.set_value(candidate);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Changes the value of the reversible object to `new` iff it currently
+    /// holds the value `expected`. Returns true iff the value was swapped.
+    pub fn compare_and_set(&mut self, expected: T, new: T) -> bool {
+        self.compare_exchange(expected, new).is_ok()
+    }
+
+    /// Changes the value of the reversible object to `new` iff it currently
+    /// holds the value `expected`. On success, the previous value (`expected`)
+    /// is returned. Otherwise, the reversible is left untouched and its actual
+    /// value is returned as an error.
+    pub fn compare_exchange(&mut self, expected: T, new: T) -> Result<T, T> {
+        let current = self.value.get();
+        if current == expected {
+            Ok(self.set_value(new))
+        } else {
+            Err(current)
+        }
+    }
+
+    /// Sets the value of the reversible object and forces an entry on the trail
+    /// (if none was posted for this reversible at the current level yet), even
+    /// when v equals the current value.
+    ///
+    /// # Note
+    /// This is an escape hatch which is only useful when some external side
+    /// effect depends on the reversible being "pinned" at the current level.
+    /// You almost certainly want to use `set_value` instead: `reset_to` posts
+    /// useless entries on the trail whenever the value is unchanged.
+    pub fn reset_to(&mut self, v: T) {
+        self.check(&v);
+        let old = self.value.get();
+        self.trail("reset_to");
+        self.value.set(v);
+        if v != old {
+            self.changed(old, v);
+        }
+    }
+
+    /// Applies f to the current value and stores the result. Just like with
+    /// `set_value`, nothing is trailed when f returns the current value.
+    /// Returns the new value.
+    pub fn update<F: FnOnce(T) -> T>(&mut self, f: F) -> T {
+        self.set_with(f)
+    }
+
+    /// Applies the fallible f to the current value and stores the result. When
+    /// f fails, the reversible is left untouched and the error is returned.
+    /// Otherwise, this behaves just like `update`.
+    pub fn try_update<E, F: FnOnce(T) -> Result<T, E>>(&mut self, f: F) -> Result<T, E> {
+        let v = f(self.value.get())?;
+        self.set_value(v);
+        Ok(v)
+    }
+
+    /// Makes the current value of this reversible permanent: it becomes the new
+    /// baseline of the reversible, as if it had been set at the root. All the
+    /// entries this reversible previously posted on the trail are neutralized,
+    /// hence popping (even with `pop_all`) does not restore any older value.
+    /// The changes made before the next push or pop are permanent too. After
+    /// that, the changes are trailed as usual (relative to the frozen value).
+    ///
+    /// This is typically useful after root-node preprocessing.
+    pub fn freeze(&mut self) {
+        let clock = self.status.clock.get();
+        self.shared.frozen.set(self.shared.frozen.get() + 1);
+        self.shared.clock.set(clock);
+        self.shared.born.set(clock);
+    }
+
+    /// Changes the value of this reversible *without* posting anything on the
+    /// trail: this change survives pops. This is meant for the initialization
+    /// of reversibles which are lazily created after the trail has advanced.
+    ///
+    /// # Note
+    /// The entries this reversible posted at the previous levels still restore
+    /// the values they saved upon pop (use `freeze` if that is not what you
+    /// want). In debug builds, this method panics if an entry has already been
+    /// posted for this reversible in the current level, because popping that
+    /// level would then silently override the value set here.
+    pub fn set_silent(&mut self, v: T) {
+        debug_assert!(
+            !(self.is_dirty() && self.shared.clock.get() != self.shared.born.get()),
+            "set_silent called on a reversible which has already been trailed in this level");
+        self.check(&v);
+        let old = self.value.get();
+        self.value.set(v);
+        if v != old {
+            self.changed(old, v);
+        }
+    }
+
+    /// Returns the clock of the trail when the value of this reversible last
+    /// changed (or when it was created). A restoration performed upon pop is
+    /// a change too: it is recorded at the clock of the trail just before the
+    /// pop (hence, it is seen by whoever looked at the reversible before).
+    pub fn last_update_clock(&self) -> usize {
+        self.shared.updated.get()
+    }
+
+    /// Returns true iff the value of this reversible has changed at the given
+    /// clock or later. A propagator typically records `trail.clock()` when it
+    /// runs and later asks whether its reversibles have changed since then.
+    pub fn changed_since(&self, clock: usize) -> bool {
+        self.last_update_clock() >= clock
+    }
+
+    /// Returns true iff the value of this reversible has changed since the
+    /// clock of the trail last ticked (that is, since the last push or pop).
+    pub fn changed_at_current_level(&self) -> bool {
+        self.changed_since(self.status.clock.get())
+    }
+
+    /// Returns true iff this reversible needs not post any entry on the trail
+    /// if it were modified now. That is, iff its clock matches the current
+    /// clock of the trail.
+    ///
+    /// Since the clock of the trail ticks upon each push and pop, this means
+    /// that the reversible has been modified since the last push (or pop).
+    /// Note that a reversible created in the current level is also considered
+    /// dirty: all its changes will be undone along with its very creation.
+    pub fn is_dirty(&self) -> bool {
+        self.shared.clock.get() == self.status.clock.get()
+    }
+
+    /// Returns the value this reversible had when the clock of the trail last
+    /// ticked (upon the last push or pop), that is, the value saved by the
+    /// entry it has posted since then. When nothing has been trailed for this
+    /// reversible since then (or when it was created since then), this is the
+    /// current value.
+    pub fn saved_value(&self) -> T {
+        let clock = self.shared.clock.get();
+        if clock == self.status.clock.get() && clock != self.shared.born.get() {
+            self.shared.saved.get()
+        } else {
+            self.value.get()
+        }
+    }
+
+    /// Creates a brand new reversible object associated with `other_trail` and
+    /// initialized with the current value of this one. This is a deep detach:
+    /// the snapshot shares nothing with the original object, hence subsequent
+    /// mutations (and restorations) on either side are totally independent.
+    ///
+    /// This comes in handy when a worker hands a subtree off to another worker
+    /// which has its own trail. The snapshot keeps the name of this reversible
+    /// (if any).
+    pub fn snapshot<'b>(&self, other_trail: Rc<RefCell<Trail<'b>>>) -> Reversible<'b, T>
+        where T: 'b {
+        let mut copy = Reversible::new(other_trail, self.get_value());
+        Rc::get_mut(&mut copy.shared).unwrap().name = self.shared.name.clone();
+        copy
+    }
+}
+
+/// This extension trait lets you create reversibles directly from the shared
+/// trail, without the `Rc::clone` boilerplate at model-construction time.
+///
+/// # Construction flow
+/// `Trail::new()` gives you a bare trail. Wrap it in an `Rc<RefCell<..>>` once
+/// (this is how it is meant to be shared anyway), bring this trait in scope and
+/// create your reversibles from it:
+///
+/// ```
+/// # use std::rc::Rc;
+/// # use std::cell::RefCell;
+/// # use trail::context::Trail;
+/// use trail::reversible::MakeReversible;
+///
+/// let trail = Rc::new(RefCell::new(Trail::new()));
+/// let mut x = trail.make_reversible(0);
+/// let flag  = trail.make_reversible(false);
+///
+/// trail.borrow_mut().push();
+/// x.set_value(42);
+/// trail.borrow_mut().pop();
+/// assert_eq!(x.get_value(), 0);
+/// assert!(!flag.get_value());
+/// ```
+pub trait MakeReversible<'a> {
+    /// Creates a new reversible object associated with this trail and
+    /// initialized with the given value.
+    fn make_reversible<T: Copy + PartialEq + 'a>(&self, initial: T) -> Reversible<'a, T>;
+}
+
+impl<'a> MakeReversible<'a> for Rc<RefCell<Trail<'a>>> {
+    fn make_reversible<T: Copy + PartialEq + 'a>(&self, initial: T) -> Reversible<'a, T> {
+        Reversible::new(Rc::clone(self), initial)
     }
+}
 
+#[cfg(feature = "std")]
+impl<T> Reversible<'static, T>
+    where T: Copy + PartialEq + 'static {
+    /// Creates a new reversible object associated with the ambient trail of
+    /// the current thread (see `Trail::install`), initialized with the given
+    /// value.
+    ///
+    /// # Panics
+    /// When no trail is installed on the current thread.
+    pub fn new_ambient(initial: T) -> Reversible<'static, T> {
+        Reversible::new(Trail::expect_ambient("Reversible"), initial)
+    }
 }
 
 impl<'a, T> fmt::Display for Reversible<'a, T>
@@ -104,6 +870,344 @@ impl<'a, T> fmt::Display for Reversible<'a, T>
     }
 }
 
+/// The guard which puts the original value of a reversible back at the end of
+/// `Reversible::with_value`
+struct RestoreOnDrop<T: Copy> {
+    cell : Rc<Cell<T>>,
+    value: T
+}
+
+impl<T: Copy> Drop for RestoreOnDrop<T> {
+    fn drop(&mut self) {
+        self.cell.set(self.value);
+    }
+}
+
+/// A read-only view of the value of a reversible. It observes all the changes
+/// made through the reversible, including the restorations performed upon pop.
+///
+/// # Note
+/// A reader is cheap (it only holds an `Rc` to the value cell) and it does not
+/// keep the trail alive. It stays valid even if the reversible is dropped, in
+/// which case it keeps returning the last value of the reversible.
+#[derive(Clone)]
+pub struct ReversibleReader<T: Copy> {
+    value: Rc<Cell<T>>
+}
+
+impl<T: Copy> ReversibleReader<T> {
+    /// Returns the current value of the observed reversible
+    pub fn get(&self) -> T {
+        self.value.get()
+    }
+}
+
+/// A weak handle to the value of a reversible (see `Reversible::downgrade`).
+/// Unlike a `ReversibleReader`, it does not keep the cell alive: once the
+/// reversible, its aliases, its readers and the trail entries it posted are all
+/// gone, the weak handle reports `None`.
+#[derive(Clone)]
+pub struct WeakReversible<T: Copy> {
+    value   : Weak<Cell<T>>,
+    identity: ReversibleId
+}
+
+impl<T: Copy> WeakReversible<T> {
+    /// Returns a reader of the observed cell, if it is still alive
+    pub fn upgrade(&self) -> Option<ReversibleReader<T>> {
+        self.value.upgrade().map(|value| ReversibleReader { value })
+    }
+
+    /// Returns the current value of the observed cell, if it is still alive
+    pub fn get(&self) -> Option<T> {
+        self.value.upgrade().map(|value| value.get())
+    }
+
+    /// Returns the identity of the observed cell (even if it is gone)
+    pub fn identity(&self) -> ReversibleId {
+        self.identity
+    }
+}
+
+/// Cloning a reversible yields an *alias*: a handle which shares the same cell,
+/// trail, clock and listeners as the original one. Hence, a change made through
+/// any of the aliases is visible through all of them, and all of them together
+/// post at most one entry on the trail per level.
+impl<'a, T> Clone for Reversible<'a, T>
+    where T: Copy + PartialEq + 'a {
+    fn clone(&self) -> Reversible<'a, T> {
+        Reversible {
+            trail    : Rc::clone(&self.trail),
+            value    : Rc::clone(&self.value),
+            status   : Rc::clone(&self.status),
+            id       : self.id,
+            shared   : Rc::clone(&self.shared)
+        }
+    }
+}
+
+/// Shows the value of the reversible along with its clock and identity. The
+/// identity is the id of the reversible when it has one (which makes the output
+/// stable), or the address of its value cell otherwise.
+impl<'a, T> fmt::Debug for Reversible<'a, T>
+    where T: fmt::Debug + Copy + PartialEq + 'a {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut dbg = f.debug_struct("Reversible");
+        if let Some(name) = self.name() {
+            dbg.field("name", &name);
+        }
+        dbg.field("value", &self.value.get());
+        dbg.field("clock", &self.clock());
+        match self.id {
+            Some(id) => dbg.field("id", &id),
+            None     => dbg.field("cell", &Rc::as_ptr(&self.value))
+        };
+        dbg.finish()
+    }
+}
+
+impl<'a, T> Reversible<'a, T>
+    where T: Sub<Output = T> + Copy + PartialEq + 'a {
+    /// Returns how much the value of this reversible has changed since the
+    /// last push or pop: `get_value() - saved_value()`.
+    pub fn delta(&self) -> T {
+        self.get_value() - self.saved_value()
+    }
+}
+
+impl<'a, T> Reversible<'a, T>
+    where T: Copy + Ord + 'a {
+    /// Tightens the lower bound held by this reversible: its value becomes the
+    /// max of the current value and v. Returns true iff the value changed (when
+    /// it did not, nothing is posted on the trail).
+    pub fn set_min(&mut self, v: T) -> bool {
+        self.set_if(v, |current, v| v > current)
+    }
+
+    /// Tightens the upper bound held by this reversible: its value becomes the
+    /// min of the current value and v. Returns true iff the value changed (when
+    /// it did not, nothing is posted on the trail).
+    pub fn set_max(&mut self, v: T) -> bool {
+        self.set_if(v, |current, v| v < current)
+    }
+
+    /// Stores the candidate iff it is smaller than the current value (e.g. to
+    /// keep the smallest completion time seen so far). Returns true iff the
+    /// candidate was stored. This is the same as `set_max`.
+    pub fn set_if_less(&mut self, candidate: T) -> bool {
+        self.set_max(candidate)
+    }
+
+    /// Stores the candidate iff it is greater than the current value. Returns
+    /// true iff the candidate was stored. This is the same as `set_min`.
+    pub fn set_if_greater(&mut self, candidate: T) -> bool {
+        self.set_min(candidate)
+    }
+}
+
+/// This macro implements the compound assignment operators of a numeric
+/// reversible (`r += x`, ...). These go through `set_value`, hence they obey the
+/// usual trailing rules (e.g. `r += 0` does not post anything on the trail).
+macro_rules! op_assign {
+    ($Trait: ident, $method: ident, $Op: ident, $op: ident) => {
+        impl<'a, T> $Trait<T> for Reversible<'a, T>
+            where T: $Op<Output = T> + Copy + PartialEq + 'a {
+            fn $method(&mut self, rhs: T) {
+                let v = self.get_value().$op(rhs);
+                self.set_value(v);
+            }
+        }
+    }
+}
+op_assign!(AddAssign, add_assign, Add, add);
+op_assign!(SubAssign, sub_assign, Sub, sub);
+op_assign!(MulAssign, mul_assign, Mul, mul);
+
+/// This macro implements the binary operators between a numeric reversible and
+/// a scalar. The result is a plain value (`&r + x` is `r.get_value() + x`).
+macro_rules! op_scalar {
+    ($Op: ident, $op: ident) => {
+        impl<'a, 'b, T> $Op<T> for &'b Reversible<'a, T>
+            where T: $Op<Output = T> + Copy + PartialEq + 'a {
+            type Output = T;
+
+            fn $op(self, rhs: T) -> T {
+                self.get_value().$op(rhs)
+            }
+        }
+    }
+}
+op_scalar!(Add, add);
+op_scalar!(Sub, sub);
+op_scalar!(Mul, mul);
+
+/// A reversible compares equal to a plain value iff its current value does
+/// (`counter == 0` is `counter.get_value() == 0`).
+impl<'a, T> PartialEq<T> for Reversible<'a, T>
+    where T: Copy + PartialEq + 'a {
+    fn eq(&self, other: &T) -> bool {
+        self.get_value() == *other
+    }
+}
+
+/// A reversible compares to a plain value as its current value does
+/// (`bound < limit` is `bound.get_value() < limit`).
+impl<'a, T> PartialOrd<T> for Reversible<'a, T>
+    where T: Copy + PartialOrd + 'a {
+    fn partial_cmp(&self, other: &T) -> Option<Ordering> {
+        self.get_value().partial_cmp(other)
+    }
+}
+
+/// Two reversibles are equal iff their *current values* are equal. This is not
+/// an identity comparison: two distinct reversibles holding the same value are
+/// equal, and two aliases of the same reversible are always equal.
+impl<'a, T> PartialEq for Reversible<'a, T>
+    where T: Copy + PartialEq + 'a {
+    fn eq(&self, other: &Reversible<'a, T>) -> bool {
+        self.get_value() == other.get_value()
+    }
+}
+
+/// This macro implements the symmetric comparisons between a plain value and a
+/// reversible (`0 == counter`, `limit > bound`). Coherence forbids a blanket
+/// implementation, hence these are only provided for the primitive types.
+macro_rules! cmp_scalar {
+    ($($t:ty),*) => {
+        $(impl<'a> PartialEq<Reversible<'a, $t>> for $t {
+            fn eq(&self, other: &Reversible<'a, $t>) -> bool {
+                *self == other.get_value()
+            }
+        }
+        impl<'a> PartialOrd<Reversible<'a, $t>> for $t {
+            fn partial_cmp(&self, other: &Reversible<'a, $t>) -> Option<Ordering> {
+                self.partial_cmp(&other.get_value())
+            }
+        })*
+    }
+}
+cmp_scalar!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64, bool, char);
+
+
+/// The error reported when a checked arithmetic operation on a reversible
+/// would produce a value which cannot be represented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticError {
+    /// The result would be larger than the largest value of the type
+    Overflow,
+    /// The result would be smaller than the smallest value of the type
+    Underflow
+}
+
+/// The primitive integer types supporting checked and saturating arithmetic
+pub trait Integer: Copy + PartialOrd + Default {
+    /// Returns self + rhs, or None in case of overflow
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    /// Returns self - rhs, or None in case of overflow
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    /// Returns self + rhs, clamped to the bounds of the type
+    fn saturating_add(self, rhs: Self) -> Self;
+    /// Returns self - rhs, clamped to the bounds of the type
+    fn saturating_sub(self, rhs: Self) -> Self;
+}
+
+macro_rules! integer {
+    ($($t:ty),*) => {
+        $(impl Integer for $t {
+            fn checked_add(self, rhs: $t) -> Option<$t> { <$t>::checked_add(self, rhs) }
+            fn checked_sub(self, rhs: $t) -> Option<$t> { <$t>::checked_sub(self, rhs) }
+            fn saturating_add(self, rhs: $t) -> $t { <$t>::saturating_add(self, rhs) }
+            fn saturating_sub(self, rhs: $t) -> $t { <$t>::saturating_sub(self, rhs) }
+        })*
+    }
+}
+integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl<'a, T> Reversible<'a, T>
+    where T: Integer + PartialEq + 'a {
+    /// Adds delta to the value of this reversible and returns the new value.
+    /// In case of overflow, the reversible is left untouched (nothing is
+    /// trailed) and an error is returned.
+    pub fn checked_add_assign(&mut self, delta: T) -> Result<T, ArithmeticError> {
+        match self.get_value().checked_add(delta) {
+            Some(v) => { self.set_value(v); Ok(v) },
+            None if delta < T::default() => Err(ArithmeticError::Underflow),
+            None => Err(ArithmeticError::Overflow)
+        }
+    }
+
+    /// Subtracts delta from the value of this reversible and returns the new
+    /// value. In case of overflow, the reversible is left untouched (nothing is
+    /// trailed) and an error is returned.
+    pub fn checked_sub_assign(&mut self, delta: T) -> Result<T, ArithmeticError> {
+        match self.get_value().checked_sub(delta) {
+            Some(v) => { self.set_value(v); Ok(v) },
+            None if delta < T::default() => Err(ArithmeticError::Overflow),
+            None => Err(ArithmeticError::Underflow)
+        }
+    }
+
+    /// Adds delta to the value of this reversible (clamping the result to the
+    /// bounds of the type) and returns the new value.
+    pub fn saturating_add_assign(&mut self, delta: T) -> T {
+        self.update(|v| v.saturating_add(delta))
+    }
+
+    /// Subtracts delta from the value of this reversible (clamping the result to
+    /// the bounds of the type) and returns the new value.
+    pub fn saturating_sub_assign(&mut self, delta: T) -> T {
+        self.update(|v| v.saturating_sub(delta))
+    }
+}
+
+/// The (de)serialization of a reversible only deals with its *current* value.
+/// The trail history cannot be serialized, hence it is *not* preserved. A
+/// deserialized reversible is a fresh one which is bound to the trail that is
+/// provided through a `ReversibleSeed`.
+#[cfg(feature = "serde")]
+mod serialization {
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+    use core::marker::PhantomData;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+    use serde::de::DeserializeSeed;
+
+    use ::context::Trail;
+    use super::Reversible;
+
+    impl<'a, T> Serialize for Reversible<'a, T>
+        where T: Serialize + Copy + PartialEq + 'a {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.get_value().serialize(serializer)
+        }
+    }
+
+    /// The seed to deserialize a reversible holding a T against the given trail
+    pub struct ReversibleSeed<'a, T> {
+        trail : Rc<RefCell<Trail<'a>>>,
+        marker: PhantomData<T>
+    }
+
+    impl<'a, T> ReversibleSeed<'a, T> {
+        /// Creates a seed which binds the deserialized reversibles to the
+        /// given trail
+        pub fn new(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleSeed<'a, T> {
+            ReversibleSeed { trail, marker: PhantomData }
+        }
+    }
+
+    impl<'de, 'a, T> DeserializeSeed<'de> for ReversibleSeed<'a, T>
+        where T: Deserialize<'de> + Copy + PartialEq + 'a {
+        type Value = Reversible<'a, T>;
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            let value = T::deserialize(deserializer)?;
+            Ok(Reversible::new(self.trail, value))
+        }
+    }
+}
+#[cfg(feature = "serde")]
+pub use self::serialization::ReversibleSeed;
 
 // TODO: I might want to move unit tests somewhere else (in the tests folder)
 #[cfg(test)]
@@ -123,17 +1227,18 @@ mod test {
         assert_eq!(trail.borrow().level(), 1);
         assert_eq!(a.get_value(), 0);
 
-        a.set_value(1);
+        assert_eq!(a.set_value(1), 0);
         assert_eq!(a.get_value(), 1);
 
         trail.borrow_mut().push();
         assert_eq!(trail.borrow().level(), 2);
         assert_eq!(a.get_value(), 1);
 
-        a.set_value(2);
+        assert_eq!(a.set_value(2), 1);
         assert_eq!(a.get_value(), 2);
 
-        a.set_value(42);
+        assert_eq!(a.set_value(42), 2);
+        assert_eq!(a.set_value(42), 42);
         assert_eq!(a.get_value(), 42);
 
         trail.borrow_mut().pop();
@@ -191,4 +1296,927 @@ mod test {
         trail.borrow_mut().pop_all();
         assert_eq!("Coucou", a.get_value());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_snapshot_is_independent() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let other = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        let mut b = a.snapshot(Rc::clone(&other));
+        assert_eq!(b.get_value(), 1);
+
+        other.borrow_mut().push();
+        b.set_value(2);
+        assert_eq!(a.get_value(), 1);
+
+        a.set_value(3);
+        assert_eq!(b.get_value(), 2);
+
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 0);
+        assert_eq!(b.get_value(), 2);
+
+        other.borrow_mut().pop();
+        assert_eq!(b.get_value(), 1);
+        assert_eq!(a.get_value(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot mutate a reversible while the trail is being restored")]
+    fn test_mutation_during_restoration_panics() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+
+        trail.borrow_mut().push();
+        trail.borrow_mut().push_on_trail(Box::new(move || { a.set_value(1); }));
+        trail.borrow_mut().pop();
+    }
+
+    #[test]
+    fn test_is_dirty() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+        assert!(a.is_dirty());
+
+        trail.borrow_mut().push();
+        assert!(!a.is_dirty());
+        a.set_value(0);
+        assert!(!a.is_dirty());
+        a.set_value(1);
+        assert!(a.is_dirty());
+
+        trail.borrow_mut().push();
+        assert!(!a.is_dirty());
+
+        trail.borrow_mut().pop();
+        assert!(!a.is_dirty());
+        a.set_value(2);
+        assert!(a.is_dirty());
+
+        // is_dirty does not borrow the trail, hence it works from a hook
+        let seen  = Rc::new(Cell::new(None));
+        let alias = a.clone();
+        let s     = Rc::clone(&seen);
+        trail.borrow_mut().push_post_restore(Box::new(move || s.set(Some(alias.is_dirty()))));
+        trail.borrow_mut().pop();
+        assert_eq!(seen.get(), Some(true));
+    }
+
+    #[test]
+    fn test_update() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 10_u32);
+
+        trail.borrow_mut().push();
+        assert_eq!(a.update(|v| v), 10);
+        assert_eq!(trail.borrow().len(), 0);
+        assert_eq!(a.update(|v| v * 2), 20);
+        assert_eq!(trail.borrow().len(), 1);
+
+        trail.borrow_mut().push();
+        assert_eq!(a.try_update(|v| if v > 100 { Ok(v) } else { Err("too small") }), Err("too small"));
+        assert_eq!(a.get_value(), 20);
+        assert_eq!(trail.borrow().len(), 1);
+        assert_eq!(a.try_update(|v| v.checked_sub(1).ok_or("underflow")), Ok(19));
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().pop_all();
+        assert_eq!(a.get_value(), 10);
+        assert!(trail.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_reset_to() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        assert_eq!(trail.borrow().len(), 0);
+        a.reset_to(1);
+        assert_eq!(trail.borrow().len(), 1);
+        a.reset_to(2);
+        assert_eq!(trail.borrow().len(), 1);
+
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 1);
+    }
+
+    #[test]
+    fn test_compare_and_set() {
+        const UNCLAIMED : usize = usize::MAX;
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut slot = Reversible::new(Rc::clone(&trail), UNCLAIMED);
+
+        trail.borrow_mut().push();
+        assert!(slot.compare_and_set(UNCLAIMED, 1));
+        assert!(!slot.compare_and_set(UNCLAIMED, 2));
+        assert_eq!(slot.compare_exchange(UNCLAIMED, 2), Err(1));
+        assert_eq!(slot.compare_exchange(1, 3), Ok(1));
+        assert_eq!(slot.get_value(), 3);
+        assert_eq!(trail.borrow().len(), 1);
+
+        trail.borrow_mut().pop();
+        assert_eq!(slot.get_value(), UNCLAIMED);
+    }
+
+    #[test]
+    fn test_operators() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut budget = Reversible::new(Rc::clone(&trail), 100);
+
+        trail.borrow_mut().push();
+        budget -= 30;
+        assert_eq!(budget.get_value(), 70);
+        assert_eq!(&budget + 5, 75);
+        assert_eq!(&budget - 5, 65);
+        assert_eq!(&budget * 2, 140);
+
+        trail.borrow_mut().push();
+        budget += 0;
+        assert!(!budget.is_dirty());
+        budget *= 2;
+        budget.set_value(&budget + 1);
+        assert_eq!(budget.get_value(), 141);
+
+        trail.borrow_mut().push();
+        {
+            let b = &mut budget;
+            *b -= 41;
+        }
+        assert_eq!(budget.get_value(), 100);
+
+        trail.borrow_mut().pop();
+        assert_eq!(budget.get_value(), 141);
+        trail.borrow_mut().pop();
+        assert_eq!(budget.get_value(), 70);
+        trail.borrow_mut().pop();
+        assert_eq!(budget.get_value(), 100);
+    }
+
+    #[test]
+    fn test_checked_and_saturating() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut capa = Reversible::new(Rc::clone(&trail), 10_u8);
+        let mut temp = Reversible::new(Rc::clone(&trail), -100_i8);
+        let mut heat = Reversible::new(Rc::clone(&trail), 100_i8);
+
+        trail.borrow_mut().push();
+        assert_eq!(capa.checked_sub_assign(11), Err(ArithmeticError::Underflow));
+        assert_eq!(capa.checked_add_assign(250), Err(ArithmeticError::Overflow));
+        assert_eq!(temp.checked_add_assign(-29), Err(ArithmeticError::Underflow));
+        assert_eq!(heat.checked_sub_assign(-28), Err(ArithmeticError::Overflow));
+        assert!(trail.borrow().is_empty());
+
+        assert_eq!(capa.checked_sub_assign(10), Ok(0));
+        assert_eq!(temp.checked_add_assign(-28), Ok(i8::MIN));
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().push();
+        assert_eq!(capa.saturating_sub_assign(1), 0);
+        assert_eq!(temp.saturating_sub_assign(1), i8::MIN);
+        assert!(!capa.is_dirty());
+        assert!(!temp.is_dirty());
+        assert_eq!(capa.saturating_add_assign(255), 255);
+        assert_eq!(capa.saturating_add_assign(1), 255);
+
+        trail.borrow_mut().pop();
+        assert_eq!(capa.get_value(), 0);
+        trail.borrow_mut().pop();
+        assert_eq!(capa.get_value(), 10);
+        assert_eq!(temp.get_value(), -100);
+    }
+
+    #[test]
+    fn test_changed_since() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0).with_id(7);
+        let mut b = Reversible::new(Rc::clone(&trail), 0).with_id(3);
+        let mut c = Reversible::new(Rc::clone(&trail), 0);
+        assert_eq!(a.id(), Some(7));
+        assert_eq!(c.id(), None);
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        c.set_value(1);
+        trail.borrow_mut().push();
+        b.set_value(1);
+        a.set_value(2);
+        trail.borrow_mut().push();
+        a.set_value(3);
+
+        assert_eq!(trail.borrow().changed_since(0), vec![3, 7]);
+        assert_eq!(trail.borrow().changed_since(1), vec![3, 7]);
+        assert_eq!(trail.borrow().changed_since(2), vec![7]);
+        assert_eq!(trail.borrow().changed_since(3), Vec::<usize>::new());
+
+        trail.borrow_mut().pop();
+        trail.borrow_mut().pop();
+        assert_eq!(trail.borrow().changed_since(0), vec![7]);
+    }
+
+    #[test]
+    fn test_set_min_set_max() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut lb = Reversible::new(Rc::clone(&trail), 0);
+        let mut ub = Reversible::new(Rc::clone(&trail), 10);
+
+        trail.borrow_mut().push();
+        assert!(lb.set_min(3));
+        assert!(ub.set_max(7));
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().push();
+        for v in 0..=3 {
+            assert!(!lb.set_min(v));
+            assert!(!ub.set_max(10 - v));
+        }
+        assert_eq!(trail.borrow().len(), 2);
+        assert!(lb.set_min(5));
+        assert!(ub.set_max(5));
+        assert_eq!(trail.borrow().len(), 4);
+
+        trail.borrow_mut().pop();
+        assert_eq!((lb.get_value(), ub.get_value()), (3, 7));
+        trail.borrow_mut().pop();
+        assert_eq!((lb.get_value(), ub.get_value()), (0, 10));
+    }
+
+    #[test]
+    fn test_change_listeners() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let seen  = Rc::new(RefCell::new(vec![]));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+
+        let log = Rc::clone(&seen);
+        let sub = a.on_change(move |old, new| log.borrow_mut().push((old, new)));
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        a.set_value(1);
+        a.update(|v| v + 1);
+        trail.borrow_mut().pop();
+        assert_eq!(*seen.borrow(), vec![(0, 1), (1, 2)]);
+
+        a.notify_on_pop(true);
+        trail.borrow_mut().push();
+        a.set_value(5);
+        trail.borrow_mut().pop();
+        assert_eq!(*seen.borrow(), vec![(0, 1), (1, 2), (0, 5), (5, 0)]);
+
+        assert!(a.unsubscribe(sub));
+        assert!(!a.unsubscribe(sub));
+        a.set_value(7);
+        assert_eq!(seen.borrow().len(), 4);
+    }
+
+    #[test]
+    fn test_on_set_to() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let fired = Rc::new(RefCell::new(vec![]));
+        let mut counter = Reversible::new(Rc::clone(&trail), 2);
+
+        let log = Rc::clone(&fired);
+        let zero = counter.on_set_to(0, move || log.borrow_mut().push("zero"));
+        let log = Rc::clone(&fired);
+        counter.on_set_to(2, move || log.borrow_mut().push("two"));
+
+        trail.borrow_mut().push();
+        counter -= 1;
+        counter -= 1;
+        counter.set_value(0);
+        assert_eq!(*fired.borrow(), vec!["zero"]);
+
+        // by default, restoring the value does not fire
+        trail.borrow_mut().pop();
+        assert_eq!(counter.get_value(), 2);
+        assert_eq!(*fired.borrow(), vec!["zero"]);
+
+        counter.notify_on_pop(true);
+        trail.borrow_mut().push();
+        counter.set_value(0);
+        trail.borrow_mut().pop();
+        assert_eq!(*fired.borrow(), vec!["zero", "zero", "two"]);
+
+        assert!(counter.unsubscribe(zero));
+        trail.borrow_mut().push();
+        counter.set_value(0);
+        assert_eq!(fired.borrow().len(), 3);
+        trail.borrow_mut().pop();
+        assert_eq!(fired.borrow().len(), 4);
+    }
+
+    #[test]
+    fn test_debug() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0).with_id(4);
+        assert_eq!(format!("{:?}", a), "Reversible { value: 0, clock: 0, id: 4 }");
+        assert_eq!(format!("{:?}", trail.borrow()), "Trail { level: 0, clock: 0, entries: 0 }");
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        assert_eq!(format!("{:?}", a), "Reversible { value: 1, clock: 1, id: 4 }");
+        assert_eq!(format!("{:?}", trail.borrow()), "Trail { level: 1, clock: 1, entries: 1 }");
+
+        let b = Reversible::new(Rc::clone(&trail), 'b');
+        assert!(format!("{:?}", b).starts_with("Reversible { value: 'b', clock: 1, cell: 0x"));
+    }
+
+    #[test]
+    fn test_aliases_trail_once() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+        let mut b = a.clone();
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        b.set_value(2);
+        assert_eq!(a.get_value(), 2);
+        assert_eq!(trail.borrow().len(), 1);
+
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 0);
+        assert_eq!(b.get_value(), 0);
+    }
+
+    #[test]
+    fn test_reader_observes_restorations() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+        let reader = a.reader();
+        let other  = reader.clone();
+
+        trail.borrow_mut().push();
+        a.set_value(2);
+        assert_eq!(reader.get(), 2);
+
+        trail.borrow_mut().pop();
+        assert_eq!(reader.get(), 1);
+        assert_eq!(other.get(), 1);
+    }
+
+    #[test]
+    fn test_reader_does_not_borrow() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut vars : Vec<Reversible<i32>> = (0..3).map(|i| Reversible::new(Rc::clone(&trail), i)).collect();
+        let first = vars[0].reader();
+
+        trail.borrow_mut().push();
+        for var in vars.iter_mut().skip(1) {
+            var.set_value(first.get() + 10);
+        }
+        vars[0].set_value(5);
+        assert_eq!(first.get(), 5);
+        assert_eq!(vars.iter().map(|v| v.get_value()).collect::<Vec<i32>>(), vec![5, 10, 10]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(first.get(), 0);
+    }
+
+    #[test]
+    fn test_freeze() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        trail.borrow_mut().push();
+        a.set_value(2);
+        a.freeze();
+        a.set_value(3);
+
+        trail.borrow_mut().push();
+        a.set_value(4);
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 3);
+
+        trail.borrow_mut().pop_all();
+        assert_eq!(trail.borrow().level(), 0);
+        assert_eq!(a.get_value(), 3);
+
+        a.freeze();
+        a.set_value(5);
+        assert!(trail.borrow().is_empty());
+
+        // freeze does not borrow the trail, hence it works from a hook: the
+        // value restored by the pop becomes permanent
+        trail.borrow_mut().push();
+        a.set_value(6);
+        trail.borrow_mut().push();
+        a.set_value(7);
+        let mut alias = a.clone();
+        trail.borrow_mut().push_post_restore(Box::new(move || alias.freeze()));
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 6);
+        trail.borrow_mut().pop_all();
+        assert_eq!(a.get_value(), 6);
+    }
+
+    #[test]
+    fn test_set_silent() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        a.set_silent(5);
+        let mut b = Reversible::new(Rc::clone(&trail), 0);
+        b.set_silent(6);
+        assert!(trail.borrow().is_empty());
+
+        trail.borrow_mut().pop_all();
+        assert_eq!(a.get_value(), 5);
+        assert_eq!(b.get_value(), 6);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "already been trailed in this level")]
+    fn test_set_silent_after_trailing_panics() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        a.set_silent(2);
+    }
+
+    #[test]
+    fn test_update_clock() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 0);
+        assert_eq!(a.last_update_clock(), 0);
+
+        trail.borrow_mut().push();
+        assert!(!a.changed_at_current_level());
+        a.set_value(0);
+        assert!(!a.changed_at_current_level());
+        a.set_value(1);
+        assert!(a.changed_at_current_level());
+        assert_eq!(a.last_update_clock(), 1);
+
+        trail.borrow_mut().push();
+        let seen = trail.borrow().clock();
+        assert!(!a.changed_since(seen));
+
+        // a restoration is a change, recorded just before the pop ticks
+        trail.borrow_mut().pop();
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 0);
+        assert!(a.changed_since(seen));
+        assert_eq!(a.last_update_clock(), 3);
+        assert!(!a.changed_at_current_level());
+    }
+
+    #[test]
+    fn test_swap() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+        let mut b = Reversible::new(Rc::clone(&trail), 2);
+        let seen = Rc::new(Cell::new((0, 0)));
+        let copy = Rc::clone(&seen);
+        let other= b.reader();
+        a.on_change(move |_, new| copy.set((new, other.get())));
+
+        trail.borrow_mut().push();
+        a.set_value(10);
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        a.swap(&mut b);
+        assert_eq!((a.get_value(), b.get_value()), (2, 10));
+        assert_eq!(seen.get(), (2, 10));
+        assert_eq!(trail.borrow().len(), 3);
+
+        // swapping back at the same level does not trail again
+        b.swap(&mut a);
+        assert_eq!((a.get_value(), b.get_value()), (10, 2));
+        assert_eq!(trail.borrow().len(), 3);
+        b.set_value(10);
+        a.swap(&mut b);
+        assert_eq!(trail.borrow().len(), 3);
+
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (10, 2));
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (10, 2));
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (1, 2));
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut counter = Reversible::new(Rc::clone(&trail), 0);
+        let other = Reversible::new(Rc::clone(&trail), 5);
+        assert!(counter == 0);
+        assert!(0 == counter);
+        assert!(counter < 3);
+        assert!(3 > counter);
+        assert!(counter != other);
+
+        trail.borrow_mut().push();
+        counter.set_value(5);
+        assert!(counter == other);
+        assert!(counter == counter.clone());
+        assert!(counter >= 5);
+        assert!(4 < counter);
+
+        trail.borrow_mut().pop();
+        assert!(counter == 0);
+        assert!(counter != other);
+        assert!(counter <= 0);
+    }
+
+    #[test]
+    fn test_take_and_replace() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 5);
+
+        trail.borrow_mut().push();
+        assert_eq!(a.replace(5), 5);
+        assert!(trail.borrow().is_empty());
+        assert_eq!(a.replace(7), 5);
+        assert_eq!(a.take(), 7);
+        assert_eq!(a.get_value(), 0);
+        assert_eq!(trail.borrow().len(), 1);
+
+        trail.borrow_mut().push();
+        assert_eq!(a.take(), 0);
+        assert_eq!(trail.borrow().len(), 1);
+        assert_eq!(a.replace(3), 0);
+
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 0);
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 5);
+    }
+
+    /// A propagator shared through an Rc, mutating its state from `&self`
+    struct Counters<'a> {
+        fired : Reversible<'a, usize>,
+        latest: Reversible<'a, isize>
+    }
+
+    impl<'a> Counters<'a> {
+        fn fire(&self, value: isize) {
+            self.fired.set_with(|n| n + 1);
+            self.latest.set(value);
+        }
+    }
+
+    #[test]
+    fn test_shared_setters() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let counters = Rc::new(Counters {
+            fired : Reversible::new(Rc::clone(&trail), 0),
+            latest: Reversible::new(Rc::clone(&trail), 0)
+        });
+        let other = Rc::clone(&counters);
+
+        trail.borrow_mut().push();
+        counters.fire(3);
+        other.fire(-2);
+        assert_eq!((counters.fired.get_value(), counters.latest.get_value()), (2, -2));
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().pop();
+        assert_eq!((other.fired.get_value(), other.latest.get_value()), (0, 0));
+    }
+
+    #[test]
+    fn test_validated() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut load = Reversible::new_validated(Rc::clone(&trail), 0, |&v| (0..=10).contains(&v));
+        let plain = Reversible::new(Rc::clone(&trail), 0);
+        assert!(plain.is_valid(&-1));
+
+        trail.borrow_mut().push();
+        assert_eq!(load.try_set(11), Err(InvalidValue(11)));
+        assert_eq!(load.get_value(), 0);
+        assert!(trail.borrow().is_empty());
+        assert_eq!(load.try_set(7), Ok(0));
+        assert_eq!(load.try_set(-1), Err(InvalidValue(-1)));
+        assert_eq!(load.get_value(), 7);
+        assert_eq!(trail.borrow().len(), 1);
+
+        // the aliases share the invariant
+        let mut alias = load.clone();
+        assert!(alias.try_set(12).is_err());
+        alias.update(|v| v + 3);
+
+        trail.borrow_mut().pop();
+        assert_eq!(load.get_value(), 0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "the value violates the invariant of the reversible")]
+    fn test_validated_update_panics() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut load = Reversible::new_validated(Rc::clone(&trail), 5, |&v| v <= 10);
+        load.update(|v| v * 3);
+    }
+
+    #[test]
+    fn test_identity() {
+        use std::collections::HashMap;
+
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = Reversible::new(Rc::clone(&trail), 0);
+        let y = Reversible::new(Rc::clone(&trail), 0);
+        let alias = x.clone();
+        assert_eq!(x.identity(), alias.identity());
+        assert_ne!(x.identity(), y.identity());
+
+        let mut subscribed : HashMap<ReversibleId, Vec<usize>> = HashMap::new();
+        subscribed.entry(x.identity()).or_default().push(0);
+        subscribed.entry(y.identity()).or_default().push(1);
+        subscribed.entry(alias.identity()).or_default().push(2);
+
+        trail.borrow_mut().push();
+        x.set_value(4);
+        trail.borrow_mut().pop();
+        assert_eq!(subscribed[&x.identity()], vec![0, 2]);
+        assert_eq!(subscribed[&y.identity()], vec![1]);
+    }
+
+    #[test]
+    fn test_into_inner_and_try_unwrap() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+        let reader = a.reader();
+
+        trail.borrow_mut().push();
+        a.set_value(2);
+        assert_eq!(a.into_inner(), 2);
+        // the pending entry still restores the (now unreachable) cell
+        trail.borrow_mut().pop();
+        assert_eq!(reader.get(), 1);
+
+        let b = Reversible::new(Rc::clone(&trail), 10);
+        let alias = b.clone();
+        let b = b.try_unwrap().unwrap_err();
+        drop(alias);
+
+        trail.borrow_mut().push();
+        let mut b = b;
+        b.set_value(20);
+        let b = b.try_unwrap().unwrap_err();
+        assert_eq!(b.get_value(), 20);
+        trail.borrow_mut().pop();
+        assert_eq!(b.try_unwrap().ok(), Some(10));
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+        let weak = a.downgrade();
+        let id = a.identity();
+        assert_eq!(weak.get(), Some(1));
+        assert_eq!(weak.identity(), id);
+
+        trail.borrow_mut().push();
+        a.set_value(2);
+        let reader = weak.upgrade().unwrap();
+        drop(a);
+        drop(reader);
+        // the pending trail entry still references the cell
+        assert_eq!(weak.get(), Some(2));
+
+        trail.borrow_mut().pop();
+        assert_eq!(weak.get(), None);
+        assert!(weak.upgrade().is_none());
+        assert_eq!(weak.identity(), id);
+    }
+
+    #[test]
+    fn test_set_if() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut best = Reversible::new(Rc::clone(&trail), 100);
+        let mut parity = Reversible::new(Rc::clone(&trail), 0);
+
+        trail.borrow_mut().push();
+        assert!(!best.set_if_less(120));
+        assert!(!parity.set_if(3, |_, c| c % 2 == 0));
+        assert!(trail.borrow().is_empty());
+
+        assert!(best.set_if_less(80));
+        assert!(!best.set_if_less(90));
+        assert!(best.set_if_less(70));
+        assert!(!best.set_if_greater(65));
+        assert!(parity.set_if(4, |cur, c| c > cur));
+        assert_eq!((best.get_value(), parity.get_value()), (70, 4));
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().pop();
+        assert_eq!((best.get_value(), parity.get_value()), (100, 0));
+    }
+
+    #[test]
+    fn test_saved_value() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 10);
+        assert_eq!((a.saved_value(), a.delta()), (10, 0));
+
+        trail.borrow_mut().push();
+        a.set_value(12);
+        a.set_value(15);
+        assert_eq!((a.saved_value(), a.delta()), (10, 5));
+
+        trail.borrow_mut().push();
+        assert_eq!((a.saved_value(), a.delta()), (15, 0));
+        a.set_value(11);
+        assert_eq!((a.saved_value(), a.delta()), (15, -4));
+
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), a.saved_value()), (15, 15));
+        a.set_value(20);
+        assert_eq!(a.saved_value(), 15);
+
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), a.saved_value()), (10, 10));
+    }
+
+    #[test]
+    fn test_new_array() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut cells = Reversible::new_array(&trail, 1000, |i| i * 2);
+        assert_eq!(cells.len(), 1000);
+        assert!(cells.iter().enumerate().all(|(i, c)| c.get_value() == i * 2));
+
+        trail.borrow_mut().push();
+        for i in (0..1000).step_by(37) {
+            cells[i].set_value(0);
+        }
+        assert_eq!(cells[37].get_value(), 0);
+        assert_eq!(cells[38].get_value(), 76);
+        trail.borrow_mut().pop();
+        assert!(cells.iter().enumerate().all(|(i, c)| c.get_value() == i * 2));
+
+        let flags = Reversible::filled(&trail, 3, true);
+        assert!(flags.iter().all(|f| f.get_value()));
+
+        let [mut a, b, c] = Reversible::new_array_const(&trail, |i| i as isize - 1);
+        assert_eq!((a.get_value(), b.get_value(), c.get_value()), (-1, 0, 1));
+        trail.borrow_mut().push();
+        a.set_value(5);
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), -1);
+    }
+
+    #[test]
+    fn test_borrowed_trail_panics_with_context() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut plain = Reversible::new(Rc::clone(&trail), 0);
+        let mut named = Reversible::new(Rc::clone(&trail), 0).with_id(7);
+        trail.borrow_mut().push();
+
+        let guard = trail.borrow();
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| plain.set_value(1))).unwrap_err();
+        assert_eq!(err.downcast_ref::<String>().unwrap(),
+            "Reversible::set_value cannot post its entry: the trail is already borrowed");
+        let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| named.reset_to(1))).unwrap_err();
+        assert_eq!(err.downcast_ref::<String>().unwrap(),
+            "Reversible::reset_to (id 7) cannot post its entry: the trail is already borrowed");
+        drop(guard);
+    }
+
+    #[test]
+    fn test_with_value() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = Reversible::new(Rc::clone(&trail), 3);
+        let reader = x.reader();
+        trail.borrow_mut().push();
+        let len = trail.borrow().len();
+
+        let seen = x.with_value(10, || reader.get() * 2);
+        assert_eq!(seen, 20);
+        assert_eq!(x.get_value(), 3);
+        assert_eq!(trail.borrow().len(), len);
+
+        // balanced pushes and pops within the closure are fine
+        let seen = x.with_value(5, || {
+            trail.borrow_mut().push();
+            trail.borrow_mut().pop();
+            reader.get()
+        });
+        assert_eq!(seen, 5);
+        assert_eq!(x.get_value(), 3);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            x.with_value(42, || -> () { panic!("boom") })
+        }));
+        assert!(result.is_err());
+        assert_eq!(x.get_value(), 3);
+
+        trail.borrow_mut().pop();
+        assert_eq!(x.get_value(), 3);
+    }
+
+    #[test]
+    fn test_named_reversibles() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let lb = Reversible::new_named(Rc::clone(&trail), 3, "makespan_lb").with_id(1);
+        assert_eq!(lb.name(), Some("makespan_lb"));
+        assert_eq!(lb.clone().name(), Some("makespan_lb"));
+        assert_eq!(format!("{:?}", lb), r#"Reversible { name: "makespan_lb", value: 3, clock: 0, id: 1 }"#);
+        assert_eq!(format!("{:?}", Reversible::new(Rc::clone(&trail), 3).with_id(2)), "Reversible { value: 3, clock: 0, id: 2 }");
+
+        let xs = Reversible::new_array_named(&trail, "x", 20, |i| i);
+        assert_eq!(xs[17].name(), Some("x[17]"));
+        assert_eq!(xs[17].get_value(), 17);
+
+        let other = Rc::new(RefCell::new(Trail::new()));
+        assert_eq!(lb.snapshot(other).name(), Some("makespan_lb"));
+        assert_eq!(Reversible::new(trail, 0).name(), None);
+        assert_eq!(std::mem::size_of::<Option<Rc<str>>>(), std::mem::size_of::<Rc<str>>());
+    }
+
+    #[test]
+    fn test_array_and_filled() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut xs = Reversible::array(&trail, &['a', 'b', 'c']);
+        let mut ys = Reversible::filled(&trail, 3, 0_u8);
+
+        trail.borrow_mut().push();
+        xs[1].set_value('z');
+        ys[2].set_value(9);
+        assert_eq!(xs.iter().map(|x| x.get_value()).collect::<String>(), "azc");
+        trail.borrow_mut().pop();
+        assert_eq!(xs.iter().map(|x| x.get_value()).collect::<String>(), "abc");
+        assert!(ys.iter().all(|y| y.get_value() == 0));
+        assert!(Reversible::<i32>::array(&trail, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_make_reversible() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = trail.make_reversible(0);
+        let mut b = trail.make_reversible("zero");
+
+        trail.borrow_mut().push();
+        a.set_value(1);
+        b.set_value("one");
+        assert_eq!(Rc::strong_count(&trail), 3);
+
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 0);
+        assert_eq!(b.get_value(), "zero");
+    }
+}
+#[cfg(all(test, feature = "serde"))]
+mod test_serde {
+    extern crate serde_json;
+    use serde::{Serialize, Deserialize};
+    use serde::de::DeserializeSeed;
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32
+    }
+
+    /// Serializes the given reversible, then deserializes it against a fresh
+    /// trail and checks that the copy trails normally
+    fn round_trip<T>(value: T, other: T, json: &str)
+        where T: Copy + PartialEq + fmt::Debug + Serialize + for<'de> Deserialize<'de> {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut rev = Reversible::new(Rc::clone(&trail), other);
+        trail.borrow_mut().push();
+        rev.set_value(value);
+        assert_eq!(serde_json::to_string(&rev).unwrap(), json);
+
+        let fresh = Rc::new(RefCell::new(Trail::new()));
+        let seed  = ReversibleSeed::new(Rc::clone(&fresh));
+        let mut copy : Reversible<T> = seed.deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+        assert_eq!(copy.get_value(), value);
+
+        fresh.borrow_mut().push();
+        copy.set_value(other);
+        fresh.borrow_mut().pop();
+        assert_eq!(copy.get_value(), value);
+
+        // the original is unaffected
+        trail.borrow_mut().pop();
+        assert_eq!(rev.get_value(), other);
+    }
+
+    #[test]
+    fn test_round_trips() {
+        round_trip(-42_i64, 7, "-42");
+        round_trip(true, false, "true");
+        round_trip(Point { x: 1, y: -2 }, Point { x: 0, y: 0 }, r#"{"x":1,"y":-2}"#);
+    }
+
+    #[test]
+    fn test_rejects_ill_typed_values() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let seed  = ReversibleSeed::<bool>::new(trail);
+        assert!(seed.deserialize(&mut serde_json::Deserializer::from_str("12")).is_err());
+    }
+}
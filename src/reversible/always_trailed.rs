@@ -0,0 +1,105 @@
+//! This module provides a reversible cell which does not compare values.
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Cell, RefCell};
+
+use ::context::{Trail, TrailStatus};
+
+/// This structure implements a reversible cell for the types which have no
+/// (reliable) notion of equality. Unlike a `Reversible`, it never compares the
+/// new value with the current one: the first set of each level unconditionally
+/// posts an entry on the trail, and the subsequent sets of the same level post
+/// nothing.
+///
+/// # Trade-off
+/// A `Reversible` skips the trail when a set does not change anything, which
+/// an `AlwaysTrailed` cannot detect. Hence, it may post entries that were not
+/// strictly needed (at most one per level). It also offers none of the services
+/// that rely on change detection (listeners, last update clock, ...). In
+/// exchange, T only has to be `Copy`, and values such as `NaN` (which is not
+/// equal to itself) are handled just like any other.
+pub struct AlwaysTrailed<'a, T: Copy + 'a> {
+    trail : Rc<RefCell<Trail<'a>>>,
+    value : Rc<Cell<T>>,
+    status: Rc<TrailStatus>,
+    /// The clock of the trail when an entry was last posted for this cell
+    clock : Cell<usize>
+}
+
+impl<'a, T: Copy + 'a> AlwaysTrailed<'a, T> {
+    /// Creates a new cell associated with the given trail and initialized with
+    /// the given value.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, initial: T) -> AlwaysTrailed<'a, T> {
+        let clock  = trail.borrow().clock();
+        let status = trail.borrow().status();
+        AlwaysTrailed { trail, value: Rc::new(Cell::new(initial)), status, clock: Cell::new(clock) }
+    }
+
+    /// Returns the current value of the cell
+    pub fn get_value(&self) -> T {
+        self.value.get()
+    }
+
+    /// Changes the current value of the cell and returns the previous one.
+    /// The first call of each level posts an entry on the trail, whatever the
+    /// value.
+    ///
+    /// # Panics
+    /// When called while the trail is being restored.
+    pub fn set_value(&mut self, v: T) -> T {
+        if self.status.restoring.get() {
+            panic!("cannot mutate a reversible while the trail is being restored");
+        }
+        let now = self.status.clock.get();
+        if now != self.clock.get() {
+            self.clock.set(now);
+            let val = self.value.get();
+            let dst = Rc::clone(&self.value);
+            self.trail.borrow_mut().push_on_trail(Box::new(move || dst.set(val)));
+        }
+        self.value.replace(v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::f64;
+
+    #[test]
+    fn test_nan_trails_once_per_level() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = AlwaysTrailed::new(Rc::clone(&trail), 1.0);
+
+        trail.borrow_mut().push();
+        x.set_value(f64::NAN);
+        x.set_value(f64::NAN);
+        assert!(x.get_value().is_nan());
+        assert_eq!(trail.borrow().len(), 1);
+
+        trail.borrow_mut().push();
+        assert!(x.set_value(f64::NAN).is_nan());
+        assert_eq!(trail.borrow().len(), 2);
+        x.set_value(2.0);
+
+        trail.borrow_mut().pop();
+        assert!(x.get_value().is_nan());
+        trail.borrow_mut().pop();
+        assert_eq!(x.get_value(), 1.0);
+    }
+
+    #[test]
+    fn test_no_partial_eq_needed() {
+        #[derive(Clone, Copy)]
+        struct Opaque(u8);
+
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = AlwaysTrailed::new(Rc::clone(&trail), Opaque(1));
+        trail.borrow_mut().push();
+        x.set_value(Opaque(1));
+        assert_eq!(trail.borrow().len(), 1);
+        x.set_value(Opaque(2));
+        trail.borrow_mut().pop();
+        assert_eq!(x.get_value().0, 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! This module provides a reversible sparse set operating on a window of an
+//! arena supplied by the caller.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::mem;
+
+use ::context::Trail;
+use ::reversible::{Reversible, DomainEvent, SparseSetIter};
+
+/// This structure implements the same set of integers in `0..capacity` as a
+/// `ReversibleSparseSet`, except that it does not allocate its `values` and
+/// `indices` arrays: these are windows borrowed from an arena which is owned by
+/// the caller. Carving the sets of thousands of variables out of one arena (see
+/// `from_arena`) thus replaces two allocations per variable by a single one,
+/// and keeps the domains next to each other in memory.
+///
+/// # Contract
+/// The arena must outlive all the sets which operate on its windows: this is
+/// what the `'arena` lifetime expresses, and it is enforced by the compiler.
+/// Since each set holds a `&mut` to its windows, nothing else can read or
+/// write them while the set is alive. The trail, on the other hand, may outlive
+/// the sets and the arena: its entries only restore the (reversible) size of
+/// the sets and never touch the arena. Once the sets are dropped, each window
+/// holds some permutation of `0..capacity` (its contents are unspecified).
+///
+/// # Note
+/// Unlike `ReversibleSparseSet`, this set does not cache its bounds: `min()`
+/// and `max()` scan the present values.
+pub struct ArenaSparseSet<'a, 'arena> {
+    values : &'arena mut [usize],
+    indices: &'arena mut [usize],
+    size   : Reversible<'a, usize>
+}
+
+impl<'a, 'arena> ArenaSparseSet<'a, 'arena> {
+    /// Creates a new sparse set associated with the given trail, which
+    /// operates on the given windows and initially contains all the values
+    /// `0..capacity` (where capacity is the length of the windows). The
+    /// previous contents of the windows are overwritten.
+    ///
+    /// # Panics
+    /// When the two windows do not have the same length.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, values: &'arena mut [usize], indices: &'arena mut [usize]) -> ArenaSparseSet<'a, 'arena> {
+        assert_eq!(values.len(), indices.len(), "the values and indices windows must have the same length");
+        for (i, (v, x)) in values.iter_mut().zip(indices.iter_mut()).enumerate() {
+            *v = i;
+            *x = i;
+        }
+        let capacity = values.len();
+        ArenaSparseSet { values, indices, size: Reversible::new(trail, capacity) }
+    }
+
+    /// Carves one set per item of `capacities` out of the given arena. Each
+    /// set takes two consecutive windows of its capacity (for its values and
+    /// its indices) and the sets are laid out in the order of `capacities`.
+    /// The part of the arena which is left over (if any) is not used.
+    ///
+    /// # Panics
+    /// When the arena is shorter than twice the sum of the capacities.
+    pub fn from_arena(trail: &Rc<RefCell<Trail<'a>>>, arena: &'arena mut [usize], capacities: &[usize]) -> Vec<ArenaSparseSet<'a, 'arena>> {
+        let needed = capacities.iter().sum::<usize>() * 2;
+        assert!(arena.len() >= needed, "the arena holds {} items but the sets need {}", arena.len(), needed);
+        let mut rest = arena;
+        let mut sets = Vec::with_capacity(capacities.len());
+        for &capacity in capacities {
+            let (values, tail)   = mem::take(&mut rest).split_at_mut(capacity);
+            let (indices, tail)  = tail.split_at_mut(capacity);
+            rest = tail;
+            sets.push(ArenaSparseSet::new(Rc::clone(trail), values, indices));
+        }
+        sets
+    }
+
+    /// Returns the number of values this set can hold (`0..capacity`)
+    pub fn capacity(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the number of values currently present in the set
+    pub fn size(&self) -> usize {
+        self.size.get_value()
+    }
+
+    /// Returns true iff there is no value in the set
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Returns true iff v is present in the set
+    pub fn contains(&self, v: usize) -> bool {
+        v < self.capacity() && self.indices[v] < self.size()
+    }
+
+    /// Returns the smallest value present in the set (None when it is empty).
+    /// This costs O(size).
+    pub fn min(&self) -> Option<usize> {
+        self.iter().min()
+    }
+
+    /// Returns the largest value present in the set (None when it is empty).
+    /// This costs O(size).
+    pub fn max(&self) -> Option<usize> {
+        self.iter().max()
+    }
+
+    /// Removes v from the set and returns the resulting event (see
+    /// `ReversibleSparseSet::remove`).
+    pub fn remove(&mut self, v: usize) -> DomainEvent {
+        if !self.contains(v) {
+            return DomainEvent::NoChange;
+        }
+        let last = self.size() - 1;
+        self.swap(self.indices[v], last);
+        self.size.set_value(last);
+        DomainEvent::change(last)
+    }
+
+    /// Removes all values from the set
+    pub fn remove_all(&mut self) {
+        self.size.set_value(0);
+    }
+
+    /// Removes all values but v from the set. If v was not present in the set,
+    /// the set becomes empty.
+    pub fn remove_all_but(&mut self, v: usize) {
+        if self.contains(v) {
+            self.swap(self.indices[v], 0);
+            self.size.set_value(1);
+        } else {
+            self.remove_all();
+        }
+    }
+
+    /// Returns the present elements of the set (in no particular order)
+    pub fn as_slice(&self) -> &[usize] {
+        &self.values[..self.size()]
+    }
+
+    /// Iterates over the present elements of the set (in no particular order)
+    pub fn iter(&self) -> SparseSetIter<'_> {
+        SparseSetIter { inner: self.as_slice().iter() }
+    }
+
+    /// Swaps the items at positions i and j of the values window
+    fn swap(&mut self, i: usize, j: usize) {
+        let vi = self.values[i];
+        let vj = self.values[j];
+        self.values[i]  = vj;
+        self.values[j]  = vi;
+        self.indices[vi]= j;
+        self.indices[vj]= i;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sorted(set: &ArenaSparseSet) -> Vec<usize> {
+        let mut values : Vec<usize> = set.iter().collect();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn test_sets_share_the_arena() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut arena = vec![42; 20];
+        {
+            let mut sets = ArenaSparseSet::from_arena(&trail, &mut arena, &[3, 0, 5]);
+            assert_eq!(sets.iter().map(|s| s.capacity()).collect::<Vec<_>>(), vec![3, 0, 5]);
+            assert!(sets[1].is_empty());
+
+            trail.borrow_mut().push();
+            assert_eq!(sets[0].remove(1), DomainEvent::Changed);
+            assert_eq!(sets[0].remove(1), DomainEvent::NoChange);
+            sets[2].remove_all_but(3);
+            assert_eq!(sorted(&sets[0]), vec![0, 2]);
+            assert_eq!((sets[2].min(), sets[2].max(), sets[2].size()), (Some(3), Some(3), 1));
+
+            trail.borrow_mut().push();
+            assert_eq!(sets[0].remove(0), DomainEvent::Fixed);
+            assert_eq!(sets[0].remove(2), DomainEvent::Empty);
+            sets[2].remove_all();
+            assert_eq!((sets[2].min(), sets[2].max()), (None, None));
+
+            trail.borrow_mut().pop();
+            assert_eq!(sorted(&sets[0]), vec![0, 2]);
+            assert!(sets[2].contains(3) && !sets[2].contains(4));
+
+            trail.borrow_mut().pop();
+            assert_eq!(sorted(&sets[0]), vec![0, 1, 2]);
+            assert_eq!(sorted(&sets[2]), vec![0, 1, 2, 3, 4]);
+            assert!(!sets[2].contains(5));
+        }
+        // the trail outlives the sets; the left over part of the arena is untouched
+        trail.borrow_mut().pop_all();
+        assert_eq!(&arena[16..], &[42; 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the arena holds 5 items but the sets need 6")]
+    fn test_arena_too_small() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut arena = [0; 5];
+        ArenaSparseSet::from_arena(&trail, &mut arena, &[1, 2]);
+    }
+}
@@ -0,0 +1,123 @@
+//! This module provides the reversible layered state sets that lie at the heart
+//! of the propagator for the regular constraint.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::ReversibleBitSet;
+
+/// This structure stores, for each layer (position) of an unfolded automaton,
+/// the set of states that are still reachable at that position. Each of these
+/// sets is a `ReversibleBitSet`, hence the removal of states is trailed at word
+/// granularity and is automagically undone upon backtrack.
+///
+/// The transitions of the automaton are not stored in this structure. Instead,
+/// they are given as a function `delta(q, v) -> Option<q'>` to the methods that
+/// need them. This way, the same structure can be used with any kind of DFA.
+pub struct ReversibleAutomaton<'a> {
+    nb_states: usize,
+    layers   : Vec<ReversibleBitSet<'a>>
+}
+
+impl<'a> ReversibleAutomaton<'a> {
+    /// Creates a new structure associated with the given trail, comprising
+    /// `nb_layers` layers in which all of the `nb_states` states are present.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, nb_layers: usize, nb_states: usize) -> ReversibleAutomaton<'a> {
+        let layers = (0..nb_layers)
+            .map(|_| ReversibleBitSet::new(Rc::clone(&trail), nb_states, true))
+            .collect();
+
+        ReversibleAutomaton { nb_states, layers }
+    }
+
+    /// Returns the number of layers
+    pub fn nb_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns the number of states of the automaton
+    pub fn nb_states(&self) -> usize {
+        self.nb_states
+    }
+
+    /// Removes the state q from the given layer
+    pub fn remove_state(&mut self, layer: usize, q: usize) {
+        self.layers[layer].remove(q)
+    }
+
+    /// Returns true iff the state q is still present in the given layer
+    pub fn contains(&self, layer: usize, q: usize) -> bool {
+        self.layers[layer].contains(q)
+    }
+
+    /// Returns the set of states that are still present in the given layer
+    pub fn reachable(&self, layer: usize) -> &ReversibleBitSet<'a> {
+        &self.layers[layer]
+    }
+
+    /// Returns true iff the value `v` is supported at the given layer. That is,
+    /// iff there exists a state q present in `layer` such that `delta(q, v)` is
+    /// a state present in `layer + 1`.
+    pub fn supports<F>(&self, layer: usize, value: usize, delta: F) -> bool
+        where F: Fn(usize, usize) -> Option<usize> {
+        let next = &self.layers[layer + 1];
+        self.layers[layer].iter()
+            .filter_map(|q| delta(q, value))
+            .any(|dst| next.contains(dst))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A DFA over {0, 1} accepting the words that contain no two consecutive 1s.
+    /// State 0: last symbol was 0 (or start), state 1: last symbol was a 1.
+    fn delta(q: usize, v: usize) -> Option<usize> {
+        match (q, v) {
+            (_, 0) => Some(0),
+            (0, 1) => Some(1),
+            _      => None
+        }
+    }
+
+    #[test]
+    fn test_supports_reappear_after_backtrack() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        // three variables, hence four layers of states
+        let mut dfa = ReversibleAutomaton::new(Rc::clone(&trail), 4, 2);
+        // only the initial state is reachable at layer 0
+        dfa.remove_state(0, 1);
+
+        assert!(dfa.supports(0, 0, delta));
+        assert!(dfa.supports(0, 1, delta));
+        assert!(dfa.supports(1, 1, delta));
+
+        // x1 = 1 : state 0 is no longer reachable at layer 1
+        trail.borrow_mut().push();
+        dfa.remove_state(1, 0);
+        assert_eq!(dfa.reachable(1).iter().collect::<Vec<usize>>(), vec![1]);
+        assert!(!dfa.supports(1, 1, delta));
+        assert!(dfa.supports(1, 0, delta));
+
+        // state 0 is no longer reachable at layer 2 either
+        trail.borrow_mut().push();
+        dfa.remove_state(2, 0);
+        assert!(!dfa.supports(1, 0, delta));
+        assert!(!dfa.supports(1, 1, delta));
+        assert!(dfa.reachable(2).iter().all(|q| q == 1));
+
+        trail.borrow_mut().pop();
+        assert!(dfa.contains(2, 0));
+        assert!(dfa.supports(1, 0, delta));
+        assert!(!dfa.contains(1, 0));
+        assert!(!dfa.supports(1, 1, delta));
+
+        trail.borrow_mut().pop();
+        assert!(dfa.contains(1, 0));
+        assert!(dfa.supports(1, 1, delta));
+        assert_eq!(dfa.reachable(1).iter().collect::<Vec<usize>>(), vec![0, 1]);
+        assert!(!dfa.contains(0, 1));
+    }
+}
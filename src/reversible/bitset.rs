@@ -0,0 +1,208 @@
+//! This module provides a bitset whose membership is reversible. The bitset is
+//! trailed at word granularity: each 64 bits word is a `Reversible<u64>`.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// The number of bits in one word of the bitset
+const WORD_SIZE : usize = 64;
+
+/// This structure implements a set of integers in the range `0..capacity`
+/// backed by reversible words. Changing any number of bits of the same word
+/// posts at most one entry on the trail per level.
+pub struct ReversibleBitSet<'a> {
+    capacity: usize,
+    words   : Vec<Reversible<'a, u64>>
+}
+
+impl<'a> ReversibleBitSet<'a> {
+    /// Creates a new bitset associated with the given trail, able to hold the
+    /// values `0..capacity`. When `full` is true, all these values are initially
+    /// present in the set; otherwise the set is initially empty.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, capacity: usize, full: bool) -> ReversibleBitSet<'a> {
+        let nb_words = capacity.div_ceil(WORD_SIZE);
+        let words    = (0..nb_words)
+            .map(|w| {
+                let init = if full { Self::mask(capacity, w) } else { 0 };
+                Reversible::new(Rc::clone(&trail), init)
+            })
+            .collect();
+
+        ReversibleBitSet { capacity, words }
+    }
+
+    /// Returns the mask of the bits of the w-th word that lie in 0..capacity
+    fn mask(capacity: usize, w: usize) -> u64 {
+        let remaining = capacity - w * WORD_SIZE;
+        if remaining >= WORD_SIZE { !0 } else { (1 << remaining) - 1 }
+    }
+
+    /// Returns the number of values this bitset can hold (`0..capacity`)
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns true iff i is present in the set
+    pub fn contains(&self, i: usize) -> bool {
+        i < self.capacity && self.words[i / WORD_SIZE].get_value() & (1 << (i % WORD_SIZE)) != 0
+    }
+
+    /// Adds i to the set
+    pub fn insert(&mut self, i: usize) {
+        assert!(i < self.capacity, "value out of bounds");
+        let word = &mut self.words[i / WORD_SIZE];
+        let v = word.get_value() | (1 << (i % WORD_SIZE));
+        word.set_value(v);
+    }
+
+    /// Removes i from the set
+    pub fn remove(&mut self, i: usize) {
+        assert!(i < self.capacity, "value out of bounds");
+        let word = &mut self.words[i / WORD_SIZE];
+        let v = word.get_value() & !(1 << (i % WORD_SIZE));
+        word.set_value(v);
+    }
+
+    /// Removes all values from the set
+    pub fn clear(&mut self) {
+        for word in self.words.iter_mut() {
+            word.set_value(0);
+        }
+    }
+
+    /// Returns true iff there is no value in the set
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| w.get_value() == 0)
+    }
+
+    /// Returns the number of values present in the set, in O(nb_words)
+    pub fn count_ones(&self) -> u32 {
+        self.words.iter().map(|w| w.get_value().count_ones()).sum()
+    }
+
+    /// Returns the smallest value present in the set (None when it is empty),
+    /// in O(nb_words)
+    pub fn first_set(&self) -> Option<usize> {
+        self.words.iter().enumerate()
+            .find(|(_, w)| w.get_value() != 0)
+            .map(|(i, w)| i * WORD_SIZE + w.get_value().trailing_zeros() as usize)
+    }
+
+    /// Returns the current value of the w-th word of the set
+    pub fn word(&self, w: usize) -> u64 {
+        self.words[w].get_value()
+    }
+
+    /// Returns the number of words used to represent the set
+    pub fn nb_words(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Iterates over the values present in the set, in increasing order
+    pub fn iter<'b>(&'b self) -> BitSetIter<'a, 'b> {
+        BitSetIter { set: self, next: 0 }
+    }
+}
+
+/// The iterator over the values present in a reversible bitset
+pub struct BitSetIter<'a: 'b, 'b> {
+    set : &'b ReversibleBitSet<'a>,
+    next: usize
+}
+
+impl<'a, 'b> Iterator for BitSetIter<'a, 'b> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.next < self.set.capacity {
+            let w    = self.next / WORD_SIZE;
+            let bits = self.set.word(w) >> (self.next % WORD_SIZE);
+            if bits == 0 {
+                self.next = (w + 1) * WORD_SIZE;
+            } else {
+                let found = self.next + bits.trailing_zeros() as usize;
+                self.next = found + 1;
+                return Some(found);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ok() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleBitSet::new(Rc::clone(&trail), 130, false);
+        assert!(set.is_empty());
+        assert_eq!(set.nb_words(), 3);
+
+        trail.borrow_mut().push();
+        set.insert(0);
+        set.insert(64);
+        set.insert(129);
+        assert_eq!(set.iter().collect::<Vec<usize>>(), vec![0, 64, 129]);
+
+        trail.borrow_mut().push();
+        set.remove(64);
+        set.insert(3);
+        assert!(!set.contains(64));
+        assert!(!set.contains(1000));
+        assert_eq!(set.iter().collect::<Vec<usize>>(), vec![0, 3, 129]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.iter().collect::<Vec<usize>>(), vec![0, 64, 129]);
+
+        trail.borrow_mut().pop();
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_full() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleBitSet::new(Rc::clone(&trail), 70, true);
+        assert_eq!(set.iter().count(), 70);
+        assert!(set.contains(69));
+        assert!(!set.contains(70));
+
+        trail.borrow_mut().push();
+        set.clear();
+        assert!(set.is_empty());
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.iter().count(), 70);
+    }
+
+    #[test]
+    fn test_count_ones_and_first_set() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleBitSet::new(Rc::clone(&trail), 200, false);
+        assert_eq!((set.count_ones(), set.first_set()), (0, None));
+        for &i in &[5, 63, 64, 130, 199] {
+            set.insert(i);
+        }
+        assert_eq!((set.count_ones(), set.first_set()), (5, Some(5)));
+
+        trail.borrow_mut().push();
+        set.remove(5);
+        set.remove(63);
+        assert_eq!((set.count_ones(), set.first_set()), (3, Some(64)));
+        set.remove(64);
+        set.remove(130);
+        assert_eq!((set.count_ones(), set.first_set()), (1, Some(199)));
+        set.clear();
+        assert_eq!((set.count_ones(), set.first_set()), (0, None));
+
+        trail.borrow_mut().pop();
+        assert_eq!((set.count_ones(), set.first_set()), (5, Some(5)));
+
+        let full = ReversibleBitSet::new(Rc::clone(&trail), 70, true);
+        assert_eq!((full.count_ones(), full.first_set()), (70, Some(0)));
+    }
+}
@@ -0,0 +1,131 @@
+//! This module provides a reversible cache of the nearest available neighbors
+//! of each node, as used by the insertion heuristics of routing propagators.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::{Reversible, ReversibleBitSet};
+
+/// This structure caches, for each node, its neighbors sorted by increasing
+/// distance along with the set of nodes which are still available (not yet
+/// inserted in a route). Using a node is trailed, hence it becomes available
+/// again upon backtrack.
+///
+/// # Implementation Notes
+/// Each node owns a reversible cursor in its sorted list of neighbors. All the
+/// neighbors before the cursor are known to be used. Queries move the cursor
+/// forward past the used neighbors, so that each neighbor is skipped at most
+/// once per level (amortized O(1) per query). Because the cursors are trailed
+/// along with the availability of the nodes, popping a level rewinds both of
+/// them consistently.
+pub struct ReversibleDistanceCache<'a> {
+    /// The neighbors of each node, sorted by increasing distance
+    sorted   : Vec<Vec<usize>>,
+    /// The position of the first possibly available neighbor of each node
+    cursors  : Vec<Reversible<'a, usize>>,
+    /// The nodes which have not been used yet
+    available: ReversibleBitSet<'a>
+}
+
+impl<'a> ReversibleDistanceCache<'a> {
+    /// Creates a new cache associated with the given trail from a (square)
+    /// distance matrix. All nodes are initially available.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, distances: &[Vec<usize>]) -> ReversibleDistanceCache<'a> {
+        let n = distances.len();
+        assert!(distances.iter().all(|row| row.len() == n), "the distance matrix must be square");
+
+        let sorted = (0..n)
+            .map(|i| {
+                let mut neighbors : Vec<usize> = (0..n).filter(|&j| j != i).collect();
+                neighbors.sort_by_key(|&j| distances[i][j]);
+                neighbors
+            })
+            .collect();
+        let cursors = (0..n).map(|_| Reversible::new(Rc::clone(&trail), 0)).collect();
+
+        ReversibleDistanceCache { sorted, cursors, available: ReversibleBitSet::new(trail, n, true) }
+    }
+
+    /// Returns the number of nodes in the cache
+    pub fn nb_nodes(&self) -> usize {
+        self.sorted.len()
+    }
+
+    /// Returns true iff the given node has not been used yet
+    pub fn is_available(&self, node: usize) -> bool {
+        self.available.contains(node)
+    }
+
+    /// Marks the given node as used. Returns true iff it was available.
+    pub fn mark_used(&mut self, node: usize) -> bool {
+        if self.is_available(node) {
+            self.available.remove(node);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns (at most) the k available neighbors of the given node which are
+    /// the closest to it, sorted by increasing distance.
+    pub fn nearest_available(&mut self, node: usize, k: usize) -> Vec<usize> {
+        let neighbors = &self.sorted[node];
+        let available = &self.available;
+
+        let cursor = &mut self.cursors[node];
+        let start  = neighbors[cursor.get_value()..].iter()
+            .position(|&j| available.contains(j))
+            .map_or(neighbors.len(), |p| cursor.get_value() + p);
+        cursor.set_value(start);
+
+        neighbors[start..].iter()
+            .cloned()
+            .filter(|&j| available.contains(j))
+            .take(k)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_two_levels_of_insertions() {
+        // five points on a line, at positions 0, 1, 3, 6 and 10
+        let pos = [0, 1, 3, 6, 10];
+        let distances : Vec<Vec<usize>> = pos.iter()
+            .map(|&a: &isize| pos.iter().map(|&b| (a - b).unsigned_abs()).collect())
+            .collect();
+
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut cache = ReversibleDistanceCache::new(Rc::clone(&trail), &distances);
+        assert_eq!(cache.nb_nodes(), 5);
+        assert_eq!(cache.nearest_available(0, 2), vec![1, 2]);
+
+        trail.borrow_mut().push();
+        assert!(cache.mark_used(1));
+        assert!(!cache.mark_used(1));
+        assert_eq!(cache.nearest_available(0, 2), vec![2, 3]);
+        assert_eq!(cache.nearest_available(2, 3), vec![0, 3, 4]);
+
+        trail.borrow_mut().push();
+        cache.mark_used(2);
+        cache.mark_used(3);
+        assert!(!cache.is_available(3));
+        assert_eq!(cache.nearest_available(0, 2), vec![4]);
+        cache.mark_used(4);
+        assert!(cache.nearest_available(0, 2).is_empty());
+
+        trail.borrow_mut().pop();
+        assert!(cache.is_available(2));
+        assert_eq!(cache.nearest_available(0, 2), vec![2, 3]);
+        assert_eq!(cache.nearest_available(4, 4), vec![3, 2, 0]);
+
+        trail.borrow_mut().pop();
+        assert!(cache.is_available(1));
+        assert_eq!(cache.nearest_available(0, 1), vec![1]);
+        assert_eq!(cache.nearest_available(2, 2), vec![1, 0]);
+    }
+}
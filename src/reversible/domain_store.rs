@@ -0,0 +1,336 @@
+//! This module provides an arena of sparse-set domains which all share one trail.
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+
+/// A lightweight handle to one of the domains of a `DomainStore`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DomainId(usize);
+
+/// This structure stores a large number of integer domains (sparse sets) whose
+/// removals are automagically undone upon backtrack.
+///
+/// # Implementation Notes
+/// Creating one `ReversibleSparseSet` per variable means that each domain holds
+/// its own clone of the `Rc<RefCell<Trail>>` and its own vectors. Instead, the
+/// store owns the trail along with flat vectors holding the data of all the
+/// domains contiguously (for the sake of cache locality). Each domain occupies
+/// a window of the `values` and `indices` vectors, and its size is stored in
+/// a shared `sizes` vector. Just like a `Reversible`, each domain remembers the
+/// clock of the trail when its size was last trailed, so that at most one entry
+/// is posted on the trail for each domain per level.
+pub struct DomainStore<'a> {
+    trail  : Rc<RefCell<Trail<'a>>>,
+    /// The position of the first item of each domain in `values` and `indices`
+    offsets: Vec<usize>,
+    /// The smallest value of the initial domain (values are stored relative)
+    lows   : Vec<isize>,
+    /// The initial number of values of each domain
+    caps   : Vec<usize>,
+    /// The clock at which the size of each domain was last trailed
+    clocks : Vec<usize>,
+    /// The current size of each domain (shared with the restoration closures)
+    sizes  : Rc<RefCell<Vec<usize>>>,
+    values : Vec<usize>,
+    indices: Vec<usize>
+}
+
+impl<'a> DomainStore<'a> {
+    /// Creates a new empty store with its own trail.
+    pub fn new() -> DomainStore<'a> {
+        DomainStore::with_trail(Rc::new(RefCell::new(Trail::new())))
+    }
+
+    /// Creates a new empty store whose domains are trailed on the given trail.
+    pub fn with_trail(trail: Rc<RefCell<Trail<'a>>>) -> DomainStore<'a> {
+        DomainStore {
+            trail,
+            offsets: vec![],
+            lows   : vec![],
+            caps   : vec![],
+            clocks : vec![],
+            sizes  : Rc::new(RefCell::new(vec![])),
+            values : vec![],
+            indices: vec![]
+        }
+    }
+
+    /// Returns the trail shared by all the domains of the store
+    pub fn trail(&self) -> &Rc<RefCell<Trail<'a>>> {
+        &self.trail
+    }
+
+    /// Adds a new domain comprising all the values of lo..=hi to the store
+    ///
+    /// # Panics
+    /// When hi < lo, or when lo..=hi spans the whole range of isize (its size
+    /// would not fit in a usize).
+    pub fn add_domain(&mut self, lo: isize, hi: isize) -> DomainId {
+        assert!(lo <= hi, "a domain cannot be created empty");
+        let id  = DomainId(self.offsets.len());
+        let cap = (hi.wrapping_sub(lo) as usize).checked_add(1)
+            .expect("a domain cannot span the whole range of isize");
+
+        self.offsets.push(self.values.len());
+        self.lows   .push(lo);
+        self.caps   .push(cap);
+        self.clocks .push(self.trail.borrow().clock());
+        self.sizes  .borrow_mut().push(cap);
+        self.values .extend(0..cap);
+        self.indices.extend(0..cap);
+        id
+    }
+
+    /// Returns the number of domains in the store
+    pub fn nb_domains(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns the total number of values held by the backing arrays
+    pub fn nb_values(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the number of values currently present in the given domain
+    pub fn size(&self, id: DomainId) -> usize {
+        self.sizes.borrow()[id.0]
+    }
+
+    /// Returns true iff the given domain is empty
+    pub fn is_empty(&self, id: DomainId) -> bool {
+        self.size(id) == 0
+    }
+
+    /// Returns true iff the given domain is reduced to a single value
+    pub fn is_fixed(&self, id: DomainId) -> bool {
+        self.size(id) == 1
+    }
+
+    /// Returns true iff v is present in the given domain
+    pub fn contains(&self, id: DomainId, v: isize) -> bool {
+        match self.relative(id, v) {
+            Some(r) => self.indices[self.offsets[id.0] + r] < self.size(id),
+            None    => false
+        }
+    }
+
+    /// Returns the smallest value present in the given domain (if any)
+    pub fn min(&self, id: DomainId) -> Option<isize> {
+        self.present(id).iter().min().map(|&r| self.lows[id.0].wrapping_add(r as isize))
+    }
+
+    /// Returns the largest value present in the given domain (if any)
+    pub fn max(&self, id: DomainId) -> Option<isize> {
+        self.present(id).iter().max().map(|&r| self.lows[id.0].wrapping_add(r as isize))
+    }
+
+    /// Returns the values present in the given domain (in no particular order)
+    pub fn values(&self, id: DomainId) -> Vec<isize> {
+        let lo = self.lows[id.0];
+        self.present(id).iter().map(|&r| lo.wrapping_add(r as isize)).collect()
+    }
+
+    /// Removes v from the given domain. Returns true iff v was present.
+    pub fn remove(&mut self, id: DomainId, v: isize) -> bool {
+        if !self.contains(id, v) {
+            return false;
+        }
+        let off  = self.offsets[id.0];
+        let r    = self.relative(id, v).unwrap();
+        let last = self.size(id) - 1;
+        self.swap(off, self.indices[off + r], last);
+        self.set_size(id, last);
+        true
+    }
+
+    /// Removes all values but v from the given domain. If v was not present, the
+    /// domain becomes empty.
+    pub fn fix(&mut self, id: DomainId, v: isize) {
+        if self.contains(id, v) {
+            let off = self.offsets[id.0];
+            let r   = self.relative(id, v).unwrap();
+            self.swap(off, self.indices[off + r], 0);
+            self.set_size(id, 1);
+        } else {
+            self.set_size(id, 0);
+        }
+    }
+
+    /// Same as `fix`, but returns the number of entries this operation posted
+    /// on the trail (which lets you attribute the growth of the trail to the
+    /// propagation steps). A no-op assignment posts no entry at all.
+    pub fn assign(&mut self, id: DomainId, v: isize) -> usize {
+        let before = self.trail.borrow().len();
+        self.fix(id, v);
+        self.trail.borrow().len() - before
+    }
+
+    /// Returns the position of v relative to the lower bound of the domain
+    /// (None if v is out of the initial bounds of the domain)
+    fn relative(&self, id: DomainId, v: isize) -> Option<usize> {
+        let lo = self.lows[id.0];
+        if v < lo || v.wrapping_sub(lo) as usize >= self.caps[id.0] {
+            None
+        } else {
+            Some(v.wrapping_sub(lo) as usize)
+        }
+    }
+
+    /// Returns the relative values of the present items of the given domain
+    fn present(&self, id: DomainId) -> &[usize] {
+        let off = self.offsets[id.0];
+        &self.values[off..off + self.size(id)]
+    }
+
+    /// Swaps the items at positions i and j of the window starting at `off`
+    fn swap(&mut self, off: usize, i: usize, j: usize) {
+        let vi = self.values[off + i];
+        let vj = self.values[off + j];
+        self.values [off + i ] = vj;
+        self.values [off + j ] = vi;
+        self.indices[off + vi] = j;
+        self.indices[off + vj] = i;
+    }
+
+    /// Changes the size of a domain, trailing its previous value if needed
+    fn set_size(&mut self, id: DomainId, size: usize) {
+        let old = self.size(id);
+        if old == size {
+            return;
+        }
+
+        let mut trail = self.trail.borrow_mut();
+        let trail_time= trail.clock();
+        if self.clocks[id.0] != trail_time {
+            self.clocks[id.0] = trail_time;
+            let dst = Rc::clone(&self.sizes);
+            trail.push_on_trail(Box::new(move || dst.borrow_mut()[id.0] = old));
+        }
+        self.sizes.borrow_mut()[id.0] = size;
+    }
+}
+
+impl<'a> Default for DomainStore<'a> {
+    fn default() -> DomainStore<'a> {
+        DomainStore::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ok() {
+        let mut store = DomainStore::new();
+        let x = store.add_domain(-2, 2);
+        let y = store.add_domain(10, 12);
+        let trail = Rc::clone(store.trail());
+
+        assert_eq!(store.size(x), 5);
+        assert_eq!(store.min(x), Some(-2));
+        assert_eq!(store.max(y), Some(12));
+        assert!(!store.contains(y, 13));
+
+        trail.borrow_mut().push();
+        assert!(store.remove(x, -2));
+        assert!(!store.remove(x, -2));
+        assert!(!store.remove(x, 42));
+        store.remove(x, 2);
+        assert_eq!(store.min(x), Some(-1));
+        assert_eq!(store.max(x), Some(1));
+
+        trail.borrow_mut().push();
+        store.fix(y, 11);
+        store.fix(x, 7);
+        assert!(store.is_fixed(y));
+        assert!(store.is_empty(x));
+        assert_eq!(store.values(y), vec![11]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(store.size(x), 3);
+        assert_eq!(store.size(y), 3);
+
+        trail.borrow_mut().pop();
+        assert_eq!(store.size(x), 5);
+        assert_eq!(store.min(x), Some(-2));
+    }
+
+    #[test]
+    fn test_assign_counts_entries() {
+        let mut store = DomainStore::new();
+        let x = store.add_domain(0, 9);
+        let y = store.add_domain(0, 9);
+        let trail = Rc::clone(store.trail());
+        // changes at the root are not trailed
+        assert_eq!(store.assign(y, 5), 0);
+
+        trail.borrow_mut().push();
+        assert_eq!(store.assign(x, 3), 1);
+        assert_eq!(store.assign(x, 3), 0);
+        assert_eq!(store.assign(y, 5), 0);
+
+        trail.borrow_mut().push();
+        store.remove(x, 4);
+        assert_eq!(store.assign(x, 3), 0);
+        assert_eq!(store.assign(x, 4), 1);
+        assert!(store.is_empty(x));
+
+        trail.borrow_mut().pop_all();
+        assert_eq!(store.size(x), 10);
+        assert_eq!(store.size(y), 1);
+    }
+
+    #[test]
+    fn test_many_domains() {
+        let mut store = DomainStore::new();
+        let ids : Vec<DomainId> = (0..10_000).map(|_| store.add_domain(0, 9)).collect();
+        let trail = Rc::clone(store.trail());
+        assert_eq!(store.nb_domains(), 10_000);
+        assert_eq!(store.nb_values(), 100_000);
+
+        trail.borrow_mut().push();
+        for id in ids.iter().step_by(7) {
+            store.remove(*id, 3);
+            store.remove(*id, 4);
+        }
+        trail.borrow_mut().push();
+        for id in ids.iter().step_by(3) {
+            store.fix(*id, 9);
+        }
+        assert_eq!(store.size(ids[21]), 1);
+        assert_eq!(store.size(ids[7]), 8);
+        assert_eq!(store.size(ids[1]), 10);
+
+        trail.borrow_mut().pop();
+        assert_eq!(store.size(ids[21]), 8);
+        assert_eq!(store.size(ids[3]), 10);
+
+        trail.borrow_mut().pop();
+        assert!(ids.iter().all(|&id| store.size(id) == 10));
+        assert_eq!(store.nb_values(), 100_000);
+    }
+
+    #[test]
+    fn test_extreme_values() {
+        let mut store = DomainStore::new();
+        let x = store.add_domain(-3, 3);
+        let y = store.add_domain(isize::MAX - 1, isize::MAX);
+        assert!(!store.contains(x, isize::MAX));
+        assert!(!store.contains(x, isize::MIN));
+        assert!(!store.remove(x, isize::MAX));
+        assert!(store.contains(y, isize::MAX));
+        assert_eq!((store.min(y), store.max(y)), (Some(isize::MAX - 1), Some(isize::MAX)));
+        store.fix(x, isize::MIN);
+        assert!(store.is_empty(x));
+    }
+
+    #[test]
+    #[should_panic(expected = "a domain cannot span the whole range of isize")]
+    fn test_full_range_domain_is_rejected() {
+        DomainStore::new().add_domain(isize::MIN, isize::MAX);
+    }
+}
@@ -0,0 +1,232 @@
+//! This module provides reversible variables and sets over the variants of a
+//! (small, field-less) user enum.
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+use ::context::Trail;
+use ::reversible::{Reversible, ReversibleBitSet, BitSetIter};
+
+/// The field-less enums whose variants can be stored in a `ReversibleEnumSet`.
+/// The variants must be numbered `0..COUNT` by `index()`, and `from_index()`
+/// must be the inverse of `index()`.
+///
+/// # Example
+/// ```
+/// use trail::reversible::Enumeration;
+///
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum Light { Red, Orange, Green }
+///
+/// impl Enumeration for Light {
+///     const COUNT: usize = 3;
+///     fn index(self) -> usize { self as usize }
+///     fn from_index(i: usize) -> Light { [Light::Red, Light::Orange, Light::Green][i] }
+/// }
+/// assert_eq!(Light::from_index(Light::Green.index()), Light::Green);
+/// ```
+pub trait Enumeration: Copy {
+    /// The number of variants of the enum
+    const COUNT: usize;
+    /// Returns the index of the variant, in `0..COUNT`
+    fn index(self) -> usize;
+    /// Returns the variant having the given index
+    fn from_index(i: usize) -> Self;
+}
+
+/// A reversible variable whose value is either unset or one of the variants of
+/// an enum (any `Copy + PartialEq` type actually). This is a thin wrapper over
+/// a `Reversible<Option<E>>`, hence it obeys the usual trailing rules.
+///
+/// # Example
+/// ```
+/// use std::rc::Rc;
+/// use std::cell::RefCell;
+/// use trail::context::Trail;
+/// use trail::reversible::ReversibleEnum;
+///
+/// #[derive(Clone, Copy, PartialEq)]
+/// enum Phase { Idle, Running }
+///
+/// let trail = Rc::new(RefCell::new(Trail::new()));
+/// let mut phase = ReversibleEnum::new(Rc::clone(&trail));
+///
+/// trail.borrow_mut().push();
+/// assert!(phase.set_if_unset(Phase::Idle));
+/// assert!(!phase.set_if_unset(Phase::Running));
+/// assert!(phase.is(Phase::Idle));
+/// trail.borrow_mut().pop();
+/// assert!(phase.get().is_none());
+/// ```
+pub struct ReversibleEnum<'a, E>
+    where E: Copy + PartialEq + 'a {
+    value: Reversible<'a, Option<E>>
+}
+
+impl<'a, E> ReversibleEnum<'a, E>
+    where E: Copy + PartialEq + 'a {
+    /// Creates a new unset variable associated with the given trail
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleEnum<'a, E> {
+        ReversibleEnum { value: Reversible::new(trail, None) }
+    }
+
+    /// Creates a new variable associated with the given trail and initially
+    /// set to the given variant
+    pub fn with_value(trail: Rc<RefCell<Trail<'a>>>, e: E) -> ReversibleEnum<'a, E> {
+        ReversibleEnum { value: Reversible::new(trail, Some(e)) }
+    }
+
+    /// Returns the current variant (None when the variable is unset)
+    pub fn get(&self) -> Option<E> {
+        self.value.get_value()
+    }
+
+    /// Returns true iff the variable is currently set to e
+    pub fn is(&self, e: E) -> bool {
+        self.get() == Some(e)
+    }
+
+    /// Returns true iff the variable is currently set
+    pub fn is_set(&self) -> bool {
+        self.get().is_some()
+    }
+
+    /// Sets the variable to e
+    pub fn set(&mut self, e: E) {
+        self.value.set_value(Some(e));
+    }
+
+    /// Unsets the variable
+    pub fn unset(&mut self) {
+        self.value.set_value(None);
+    }
+
+    /// Sets the variable to e iff it is currently unset. Returns true iff the
+    /// variable has been set by this call.
+    pub fn set_if_unset(&mut self, e: E) -> bool {
+        if self.is_set() {
+            false
+        } else {
+            self.set(e);
+            true
+        }
+    }
+}
+
+/// A set of enum variants whose membership is reversible. It is backed by a
+/// `ReversibleBitSet` indexed by `Enumeration::index`.
+pub struct ReversibleEnumSet<'a, E: Enumeration> {
+    bits   : ReversibleBitSet<'a>,
+    phantom: PhantomData<E>
+}
+
+impl<'a, E: Enumeration> ReversibleEnumSet<'a, E> {
+    /// Creates a new set associated with the given trail. When `full` is true,
+    /// all the variants are initially present; otherwise the set is empty.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, full: bool) -> ReversibleEnumSet<'a, E> {
+        ReversibleEnumSet { bits: ReversibleBitSet::new(trail, E::COUNT, full), phantom: PhantomData }
+    }
+
+    /// Returns true iff e is present in the set
+    pub fn contains(&self, e: E) -> bool {
+        self.bits.contains(e.index())
+    }
+
+    /// Adds e to the set
+    pub fn insert(&mut self, e: E) {
+        self.bits.insert(e.index())
+    }
+
+    /// Removes e from the set
+    pub fn remove(&mut self, e: E) {
+        self.bits.remove(e.index())
+    }
+
+    /// Removes all the variants from the set
+    pub fn clear(&mut self) {
+        self.bits.clear()
+    }
+
+    /// Returns true iff there is no variant in the set
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// Iterates over the variants present in the set, in increasing index order
+    pub fn iter<'b>(&'b self) -> EnumSetIter<'a, 'b, E> {
+        EnumSetIter { inner: self.bits.iter(), phantom: PhantomData }
+    }
+}
+
+/// The iterator over the variants present in a reversible enum set
+pub struct EnumSetIter<'a: 'b, 'b, E> {
+    inner  : BitSetIter<'a, 'b>,
+    phantom: PhantomData<E>
+}
+
+impl<'a, 'b, E: Enumeration> Iterator for EnumSetIter<'a, 'b, E> {
+    type Item = E;
+
+    fn next(&mut self) -> Option<E> {
+        self.inner.next().map(E::from_index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Task { Todo, Doing, Blocked, Done }
+
+    impl Enumeration for Task {
+        const COUNT: usize = 4;
+        fn index(self) -> usize { self as usize }
+        fn from_index(i: usize) -> Task { [Task::Todo, Task::Doing, Task::Blocked, Task::Done][i] }
+    }
+
+    #[test]
+    fn test_enum_variable() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut task = ReversibleEnum::with_value(Rc::clone(&trail), Task::Todo);
+        assert!(task.is(Task::Todo));
+        assert!(!task.set_if_unset(Task::Done));
+
+        trail.borrow_mut().push();
+        task.set(Task::Doing);
+        trail.borrow_mut().push();
+        task.unset();
+        assert!(task.set_if_unset(Task::Blocked));
+        assert_eq!(task.get(), Some(Task::Blocked));
+
+        trail.borrow_mut().pop();
+        assert!(task.is(Task::Doing));
+        trail.borrow_mut().pop();
+        assert!(task.is(Task::Todo));
+    }
+
+    #[test]
+    fn test_enum_set() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut allowed = ReversibleEnumSet::new(Rc::clone(&trail), true);
+        assert_eq!(allowed.iter().collect::<Vec<Task>>(), vec![Task::Todo, Task::Doing, Task::Blocked, Task::Done]);
+
+        trail.borrow_mut().push();
+        allowed.remove(Task::Blocked);
+        allowed.remove(Task::Todo);
+        assert!(!allowed.contains(Task::Blocked));
+        assert_eq!(allowed.iter().collect::<Vec<Task>>(), vec![Task::Doing, Task::Done]);
+
+        trail.borrow_mut().push();
+        allowed.clear();
+        allowed.insert(Task::Todo);
+        assert_eq!(allowed.iter().collect::<Vec<Task>>(), vec![Task::Todo]);
+        allowed.clear();
+        assert!(allowed.is_empty());
+
+        trail.borrow_mut().pop();
+        assert_eq!(allowed.iter().collect::<Vec<Task>>(), vec![Task::Doing, Task::Done]);
+        trail.borrow_mut().pop();
+        assert!(allowed.contains(Task::Blocked));
+    }
+}
@@ -0,0 +1,86 @@
+//! This module provides a reversible floating point number.
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// This structure implements a reversible `f64` (e.g. the bound of an LP
+/// relaxation). Using a plain `Reversible<f64>` is a bad idea since `NaN` is
+/// not equal to itself: every set would be seen as a change.
+///
+/// # Equality Semantics
+/// A `ReversibleFloat` decides whether a set changes its value by comparing
+/// the bit patterns of the old and new values (`f64::to_bits`). Hence:
+///   - setting `NaN` over the very same `NaN` is no change (nothing is trailed);
+///   - `-0.0` and `+0.0` are two distinct values (setting one over the other is
+///     a change, and restoring it gives back the exact sign of the zero).
+pub struct ReversibleFloat<'a> {
+    bits: Reversible<'a, u64>
+}
+
+impl<'a> ReversibleFloat<'a> {
+    /// Creates a new reversible float associated with the given trail and
+    /// initialized with the given value.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, initial: f64) -> ReversibleFloat<'a> {
+        ReversibleFloat { bits: Reversible::new(trail, initial.to_bits()) }
+    }
+
+    /// Returns the current value of the reversible float
+    pub fn get_value(&self) -> f64 {
+        f64::from_bits(self.bits.get_value())
+    }
+
+    /// Changes the current value of the reversible float and returns its
+    /// previous value. Nothing is trailed when the bits of v are those of the
+    /// current value.
+    pub fn set_value(&mut self, v: f64) -> f64 {
+        f64::from_bits(self.bits.set_value(v.to_bits()))
+    }
+}
+
+impl<'a> fmt::Display for ReversibleFloat<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Reversible({})", self.get_value())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_nan_and_signed_zeros() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = ReversibleFloat::new(Rc::clone(&trail), 0.0);
+
+        trail.borrow_mut().push();
+        x.set_value(-0.0);
+        assert!(x.get_value().is_sign_negative());
+        assert_eq!(trail.borrow().len(), 1);
+
+        trail.borrow_mut().push();
+        x.set_value(f64::NAN);
+        x.set_value(f64::NAN);
+        assert!(x.get_value().is_nan());
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().push();
+        x.set_value(1.5);
+        assert_eq!(x.get_value(), 1.5);
+
+        trail.borrow_mut().pop();
+        assert!(x.get_value().is_nan());
+        x.set_value(f64::NAN);
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().pop();
+        assert_eq!(x.get_value(), 0.0);
+        assert!(x.get_value().is_sign_negative());
+
+        trail.borrow_mut().pop();
+        assert!(x.get_value().is_sign_positive());
+        assert_eq!(format!("{}", x), "Reversible(0)");
+    }
+}
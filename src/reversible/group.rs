@@ -0,0 +1,195 @@
+//! This module provides a group of reversibles which can be checkpointed and
+//! rolled back independently of the trail levels.
+use alloc::vec::Vec;
+use ::reversible::Reversible;
+
+/// A group of reversibles supporting a local checkpoint/rollback mechanism.
+/// `save()` records the current value of each member of the group and
+/// `rollback()` writes the most recently saved values back. This lets a
+/// propagator probe a localized hypothesis without pushing a level of the
+/// trail (and thus without touching the reversibles outside of the group).
+///
+/// # Interaction with the trail
+/// The group holds aliases of its members, hence the rollback changes their
+/// values through the regular `set_value`: the rollback itself is trailed at
+/// the current level and it is undone when that level is popped, just like any
+/// other change. Conversely, the checkpoints are *not* trailed: popping the
+/// trail neither creates nor discards them. Hence, a checkpoint saved below a
+/// level that is popped afterwards still holds the values that were current
+/// when `save()` was called.
+///
+/// # Initial values
+/// On top of its checkpoints, the group remembers the value each member had
+/// when it joined the group, which `reset_all()` writes back (e.g. to restart
+/// the search with a fresh heuristic state without popping the trail). Since
+/// the group only holds aliases, a reversible may belong to several groups.
+pub struct ReversibleGroup<'a, T>
+    where T: Copy + PartialEq + 'a {
+    members: Vec<Reversible<'a, T>>,
+    initial: Vec<T>,
+    frames : Vec<Vec<T>>
+}
+
+impl<'a, T> ReversibleGroup<'a, T>
+    where T: Copy + PartialEq + 'a {
+    /// Creates a new group containing (aliases of) the given reversibles. Their
+    /// current values are their initial values (see `reset_all`).
+    pub fn new(members: &[Reversible<'a, T>]) -> ReversibleGroup<'a, T> {
+        let initial = members.iter().map(|m| m.get_value()).collect();
+        ReversibleGroup { members: members.to_vec(), initial, frames: vec![] }
+    }
+
+    /// Adds (an alias of) the given reversible to the group, with its current
+    /// value as initial value. The checkpoints which have already been saved
+    /// do not cover it.
+    pub fn add(&mut self, member: &Reversible<'a, T>) {
+        self.members.push(member.clone());
+        self.initial.push(member.get_value());
+    }
+
+    /// Returns the number of reversibles in the group
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns true iff the group has no member
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Returns the number of checkpoints which have been saved and not rolled
+    /// back yet
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Records the current value of all the members of the group
+    pub fn save(&mut self) {
+        self.frames.push(self.members.iter().map(|m| m.get_value()).collect());
+    }
+
+    /// Writes the values recorded by the most recent `save()` back to the
+    /// members of the group and discards that checkpoint. Returns false (and
+    /// does nothing) when there is no checkpoint.
+    pub fn rollback(&mut self) -> bool {
+        match self.frames.pop() {
+            Some(frame) => {
+                for (member, &value) in self.members.iter_mut().zip(frame.iter()) {
+                    member.set_value(value);
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Writes the initial value of each member back. The reset goes through
+    /// the regular `set_value`: it is trailed at the current level and undone
+    /// when that level is popped. The checkpoints are left untouched.
+    pub fn reset_all(&mut self) {
+        for (member, &value) in self.members.iter_mut().zip(self.initial.iter()) {
+            member.set_value(value);
+        }
+    }
+
+    /// Same as `reset_all`, except that the reset is permanent: each member is
+    /// frozen at its initial value (see `Reversible::freeze`), hence no pop
+    /// undoes the reset. Note that this also makes the changes performed on the
+    /// members before the next push or pop permanent.
+    pub fn reset_all_silent(&mut self) {
+        for (member, &value) in self.members.iter_mut().zip(self.initial.iter()) {
+            member.set_value(value);
+            member.freeze();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::context::Trail;
+
+    #[test]
+    fn test_save_mutate_rollback() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+        let mut b = Reversible::new(Rc::clone(&trail), 2);
+        let mut other = Reversible::new(Rc::clone(&trail), 0);
+        let mut group = ReversibleGroup::new(&[a.clone(), b.clone()]);
+        assert!(!group.rollback());
+
+        trail.borrow_mut().push();
+        a.set_value(10);
+        group.save();
+        b.set_value(20);
+        other.set_value(42);
+
+        trail.borrow_mut().push();
+        a.set_value(100);
+        group.save();
+        b.set_value(200);
+        assert!(group.rollback());
+        assert_eq!((a.get_value(), b.get_value()), (100, 20));
+
+        // the checkpoints survive the pop of the level they were saved at
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (10, 20));
+        assert_eq!(group.depth(), 1);
+
+        trail.borrow_mut().push();
+        assert!(group.rollback());
+        assert_eq!((a.get_value(), b.get_value(), other.get_value()), (10, 2, 42));
+
+        // the rollback itself is trailed
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (10, 20));
+
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value(), other.get_value()), (1, 2, 0));
+    }
+
+    #[test]
+    fn test_reset_all() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = Reversible::new(Rc::clone(&trail), 1);
+        let mut b = Reversible::new(Rc::clone(&trail), 2);
+        let mut heuristic = ReversibleGroup::new(&[a.clone()]);
+        let mut both = ReversibleGroup::new(&[]);
+        both.add(&a);
+        trail.borrow_mut().push();
+        b.set_value(5);
+        both.add(&b);
+
+        a.set_value(10);
+        b.set_value(20);
+        trail.borrow_mut().push();
+        a.set_value(100);
+        heuristic.reset_all();
+        assert_eq!((a.get_value(), b.get_value()), (1, 20));
+        both.reset_all();
+        assert_eq!((a.get_value(), b.get_value()), (1, 5));
+
+        // the reset is undone by the pop
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (10, 20));
+
+        trail.borrow_mut().push();
+        b.set_value(30);
+        both.reset_all_silent();
+        assert_eq!((a.get_value(), b.get_value()), (1, 5));
+
+        // the silent reset survives the pops
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (1, 5));
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value()), (1, 5));
+
+        // and the reversibles are trailed as usual afterwards
+        trail.borrow_mut().push();
+        a.set_value(7);
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 1);
+    }
+}
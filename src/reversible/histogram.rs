@@ -0,0 +1,133 @@
+//! This module provides a histogram of bucketed counts which are restored upon
+//! backtrack (e.g. to gather per-branch activity statistics).
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::slice::Iter;
+use core::iter::Enumerate;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// This structure implements a histogram whose buckets are reversible counts.
+/// Each bucket is trailed at most once per level, no matter how many times it
+/// is incremented in that level.
+pub struct ReversibleHistogram<'a> {
+    buckets: Vec<Reversible<'a, usize>>,
+    total  : Reversible<'a, usize>
+}
+
+impl<'a> ReversibleHistogram<'a> {
+    /// Creates a new histogram associated with the given trail, with n_buckets
+    /// buckets which are all initially empty.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, n_buckets: usize) -> ReversibleHistogram<'a> {
+        let buckets = (0..n_buckets).map(|_| Reversible::new(Rc::clone(&trail), 0)).collect();
+        ReversibleHistogram { buckets, total: Reversible::new(trail, 0) }
+    }
+
+    /// Returns the number of buckets of the histogram
+    pub fn nb_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Increments the count of the given bucket by one
+    pub fn incr(&mut self, bucket: usize) {
+        self.add(bucket, 1)
+    }
+
+    /// Increments the count of the given bucket by w
+    pub fn add(&mut self, bucket: usize, w: usize) {
+        let count = self.buckets[bucket].get_value();
+        self.buckets[bucket].set_value(count + w);
+        let total = self.total.get_value();
+        self.total.set_value(total + w);
+    }
+
+    /// Returns the count of the given bucket
+    pub fn count(&self, bucket: usize) -> usize {
+        self.buckets[bucket].get_value()
+    }
+
+    /// Returns the sum of the counts of all buckets
+    pub fn total(&self) -> usize {
+        self.total.get_value()
+    }
+
+    /// Returns the bucket having the largest count (the smallest such bucket
+    /// in case of ties), or None if all buckets are empty.
+    pub fn argmax(&self) -> Option<usize> {
+        let mut best = None;
+        let mut max  = 0;
+        for (i, b) in self.buckets.iter().enumerate() {
+            if b.get_value() > max {
+                max  = b.get_value();
+                best = Some(i);
+            }
+        }
+        best
+    }
+
+    /// Iterates (in increasing order) over the buckets having a non zero count.
+    /// Each item is a pair (bucket, count).
+    pub fn iter_nonzero(&self) -> HistogramIter<'a, '_> {
+        HistogramIter { inner: self.buckets.iter().enumerate() }
+    }
+}
+
+/// The iterator over the non empty buckets of a histogram
+pub struct HistogramIter<'a: 'b, 'b> {
+    inner: Enumerate<Iter<'b, Reversible<'a, usize>>>
+}
+
+impl<'a, 'b> Iterator for HistogramIter<'a, 'b> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        self.inner.by_ref()
+            .map(|(i, b)| (i, b.get_value()))
+            .find(|&(_, count)| count > 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_argmax_after_partial_pops() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut histo = ReversibleHistogram::new(Rc::clone(&trail), 5);
+        assert_eq!(histo.argmax(), None);
+        assert_eq!(histo.iter_nonzero().count(), 0);
+
+        trail.borrow_mut().push();
+        histo.incr(1);
+        histo.incr(1);
+        histo.incr(3);
+        assert_eq!(histo.argmax(), Some(1));
+
+        trail.borrow_mut().push();
+        histo.add(3, 4);
+        assert_eq!(histo.argmax(), Some(3));
+
+        trail.borrow_mut().push();
+        histo.add(4, 10);
+        histo.incr(0);
+        assert_eq!(histo.argmax(), Some(4));
+        assert_eq!(histo.total(), 18);
+        assert_eq!(histo.iter_nonzero().collect::<Vec<_>>(), vec![(0, 1), (1, 2), (3, 5), (4, 10)]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(histo.argmax(), Some(3));
+        assert_eq!(histo.count(4), 0);
+
+        trail.borrow_mut().pop();
+        assert_eq!(histo.argmax(), Some(1));
+        assert_eq!(histo.total(), 3);
+        assert_eq!(histo.iter_nonzero().collect::<Vec<_>>(), vec![(1, 2), (3, 1)]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(histo.argmax(), None);
+        assert_eq!(histo.total(), 0);
+    }
+}
@@ -0,0 +1,377 @@
+//! This module provides a small integer variable abstraction built on top of
+//! the reversible types. The variable owns a reversible domain and notifies the
+//! listeners which have been registered on it whenever its domain changes.
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::{Reversible, ReversibleSparseSet, DomainEvent};
+
+/// The error which is reported when an operation removes the last value of the
+/// domain of a variable (which means that the search must backtrack). See
+/// `DomainEvent::into_result`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wipeout;
+
+/// A list of listeners whose registrations are reversible: the length of the
+/// list is trailed, hence the listeners registered at a deeper level disappear
+/// upon backtrack.
+struct ListenerList<'a> {
+    listeners: Vec<Box<dyn FnMut() + 'a>>,
+    len      : Reversible<'a, usize>
+}
+
+impl<'a> ListenerList<'a> {
+    fn new(trail: Rc<RefCell<Trail<'a>>>) -> ListenerList<'a> {
+        ListenerList { listeners: vec![], len: Reversible::new(trail, 0) }
+    }
+
+    fn register(&mut self, listener: Box<dyn FnMut() + 'a>) {
+        let len = self.len.get_value();
+        self.listeners.truncate(len);
+        self.listeners.push(listener);
+        self.len.set_value(len + 1);
+    }
+
+    fn notify(&mut self) {
+        let len = self.len.get_value();
+        for listener in self.listeners[..len].iter_mut() {
+            listener();
+        }
+    }
+}
+
+/// This structure implements an integer variable whose domain is reversible
+/// and which notifies its listeners about the changes of its domain.
+///
+/// # Listeners
+/// Three kinds of listeners can be registered:
+///   - `on_fix` listeners are notified when the domain becomes a singleton.
+///   - `on_bound_change` listeners are notified when the min or max changes.
+///   - `on_domain_change` listeners are notified when any value is removed.
+///
+/// Each operation returns the `DomainEvent` which describes its effect on the
+/// domain. A wipeout is reported as `DomainEvent::Empty`, which
+/// `DomainEvent::into_result` turns into an `Err(Wipeout)` for the propagators
+/// that chain the operations with `?`. When an operation succeeds, the
+/// listeners are notified in that order (fix, bound change, domain change).
+/// No listener is notified when an operation leaves the domain unchanged or
+/// when it wipes the domain out. Registering a listener is itself reversible:
+/// a listener registered at some level stops being notified once that level
+/// has been popped.
+pub struct TrailedIntVar<'a> {
+    offset   : isize,
+    domain   : ReversibleSparseSet<'a>,
+    min      : Reversible<'a, isize>,
+    max      : Reversible<'a, isize>,
+    on_fix   : ListenerList<'a>,
+    on_bound : ListenerList<'a>,
+    on_domain: ListenerList<'a>
+}
+
+impl<'a> TrailedIntVar<'a> {
+    /// Creates a new variable associated with the given trail, whose domain
+    /// initially comprises all the values of lo..=hi.
+    ///
+    /// # Panics
+    /// When hi < lo, or when lo..=hi spans the whole range of isize (its size
+    /// would not fit in a usize).
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, lo: isize, hi: isize) -> TrailedIntVar<'a> {
+        assert!(lo <= hi, "a variable cannot be created with an empty domain");
+        let size = (hi.wrapping_sub(lo) as usize).checked_add(1)
+            .expect("the domain of a variable cannot span the whole range of isize");
+        TrailedIntVar {
+            offset   : lo,
+            domain   : ReversibleSparseSet::new(Rc::clone(&trail), size),
+            min      : Reversible::new(Rc::clone(&trail), lo),
+            max      : Reversible::new(Rc::clone(&trail), hi),
+            on_fix   : ListenerList::new(Rc::clone(&trail)),
+            on_bound : ListenerList::new(Rc::clone(&trail)),
+            on_domain: ListenerList::new(trail)
+        }
+    }
+
+    /// Returns the smallest value of the domain
+    pub fn min(&self) -> isize {
+        self.min.get_value()
+    }
+
+    /// Returns the largest value of the domain
+    pub fn max(&self) -> isize {
+        self.max.get_value()
+    }
+
+    /// Returns the number of values in the domain
+    pub fn size(&self) -> usize {
+        self.domain.size()
+    }
+
+    /// Returns true iff the domain is reduced to a single value
+    pub fn is_fixed(&self) -> bool {
+        self.size() == 1
+    }
+
+    /// Returns true iff v belongs to the domain
+    pub fn contains(&self, v: isize) -> bool {
+        self.index(v).is_some_and(|i| self.domain.contains(i))
+    }
+
+    /// Registers a listener which is notified when the variable gets fixed
+    pub fn on_fix(&mut self, listener: Box<dyn FnMut() + 'a>) {
+        self.on_fix.register(listener)
+    }
+
+    /// Registers a listener which is notified when the min or the max changes
+    pub fn on_bound_change(&mut self, listener: Box<dyn FnMut() + 'a>) {
+        self.on_bound.register(listener)
+    }
+
+    /// Registers a listener which is notified when the domain changes
+    pub fn on_domain_change(&mut self, listener: Box<dyn FnMut() + 'a>) {
+        self.on_domain.register(listener)
+    }
+
+    /// Removes v from the domain and returns the resulting event
+    pub fn remove(&mut self, v: isize) -> DomainEvent {
+        self.update(|var| {
+            if let Some(i) = var.index(v) {
+                var.domain.remove(i);
+            }
+        })
+    }
+
+    /// Removes all values but v from the domain and returns the resulting
+    /// event (`Empty` when v was not in the domain).
+    pub fn fix(&mut self, v: isize) -> DomainEvent {
+        self.update(|var| {
+            match var.index(v) {
+                Some(i) => var.domain.remove_all_but(i),
+                None    => var.domain.remove_all()
+            }
+        })
+    }
+
+    /// Removes all values strictly smaller than v from the domain and returns
+    /// the resulting event. Any v is accepted: when v is at most the min,
+    /// nothing changes and when it exceeds the max, the domain is wiped out.
+    ///
+    /// # Cost
+    /// O(1) in these two cases, O(size) otherwise (the values to remove are
+    /// found by scanning the present values, whatever the range of the domain).
+    pub fn remove_below(&mut self, v: isize) -> DomainEvent {
+        self.update(|var| {
+            if v > var.max() {
+                var.domain.remove_all();
+            } else if v > var.min() {
+                let gone : Vec<usize> = var.domain.iter().filter(|&i| var.value(i) < v).collect();
+                var.domain.remove_values(&gone);
+            }
+        })
+    }
+
+    /// Removes all values strictly greater than v from the domain and returns
+    /// the resulting event. Any v is accepted: when v is at least the max,
+    /// nothing changes and when it is below the min, the domain is wiped out.
+    /// The cost is the same as that of `remove_below`.
+    pub fn remove_above(&mut self, v: isize) -> DomainEvent {
+        self.update(|var| {
+            if v < var.min() {
+                var.domain.remove_all();
+            } else if v < var.max() {
+                let gone : Vec<usize> = var.domain.iter().filter(|&i| var.value(i) > v).collect();
+                var.domain.remove_values(&gone);
+            }
+        })
+    }
+
+    /// Returns the position of v in the sparse set of the domain (None when v
+    /// lies below the initial min). Since v is at least the offset, the
+    /// difference always fits in a usize even when it overflows an isize.
+    fn index(&self, v: isize) -> Option<usize> {
+        if v < self.offset {
+            None
+        } else {
+            Some(v.wrapping_sub(self.offset) as usize)
+        }
+    }
+
+    /// Returns the value stored at position i of the sparse set of the domain
+    fn value(&self, i: usize) -> isize {
+        self.offset.wrapping_add(i as isize)
+    }
+
+    /// Applies the given modification to the domain, then updates the bounds
+    /// and notifies the listeners.
+    fn update<F: FnOnce(&mut Self)>(&mut self, modification: F) -> DomainEvent {
+        let size = self.size();
+        modification(self);
+        let new_size = self.size();
+
+        if new_size == 0 {
+            return DomainEvent::Empty;
+        }
+        if new_size == size {
+            return DomainEvent::NoChange;
+        }
+
+        let (lo, hi) = (self.min(), self.max());
+        let new_lo = self.value(self.domain.min().unwrap());
+        let new_hi = self.value(self.domain.max().unwrap());
+        self.min.set_value(new_lo);
+        self.max.set_value(new_hi);
+
+        if new_size == 1 {
+            self.on_fix.notify();
+        }
+        if new_lo != lo || new_hi != hi {
+            self.on_bound.notify();
+        }
+        self.on_domain.notify();
+        DomainEvent::change(new_size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use super::*;
+
+    #[test]
+    fn test_domain_operations() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = TrailedIntVar::new(Rc::clone(&trail), 0, 9);
+
+        trail.borrow_mut().push();
+        assert_eq!(x.remove(0), DomainEvent::Changed);
+        assert_eq!(x.remove(0), DomainEvent::NoChange);
+        assert_eq!(x.min(), 1);
+        assert_eq!(x.remove_above(6), DomainEvent::Changed);
+        assert_eq!(x.remove_below(3), DomainEvent::Changed);
+        assert_eq!((x.min(), x.max(), x.size()), (3, 6, 4));
+
+        trail.borrow_mut().push();
+        assert_eq!(x.fix(5), DomainEvent::Fixed);
+        assert!(x.is_fixed());
+        assert_eq!(x.remove(5), DomainEvent::Empty);
+
+        trail.borrow_mut().pop();
+        assert_eq!((x.min(), x.max(), x.size()), (3, 6, 4));
+        assert_eq!(x.remove_below(100), DomainEvent::Empty);
+
+        trail.borrow_mut().pop();
+        assert_eq!((x.min(), x.max(), x.size()), (0, 9, 10));
+        assert_eq!(x.fix(42), DomainEvent::Empty);
+    }
+
+    #[test]
+    fn test_bounds_removals_out_of_range() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = TrailedIntVar::new(Rc::clone(&trail), -3, 3);
+
+        trail.borrow_mut().push();
+        assert_eq!(x.remove_below(-3), DomainEvent::NoChange);
+        assert_eq!(x.remove_below(-100), DomainEvent::NoChange);
+        assert_eq!(x.remove_below(isize::MIN), DomainEvent::NoChange);
+        assert_eq!(x.remove_above(3), DomainEvent::NoChange);
+        assert_eq!(x.remove_above(100), DomainEvent::NoChange);
+        assert_eq!(x.remove_above(isize::MAX), DomainEvent::NoChange);
+        assert_eq!((x.min(), x.max(), x.size()), (-3, 3, 7));
+
+        assert_eq!(x.remove_below(4), DomainEvent::Empty);
+        trail.borrow_mut().pop();
+
+        trail.borrow_mut().push();
+        assert_eq!(x.remove_below(isize::MAX), DomainEvent::Empty);
+        trail.borrow_mut().pop();
+
+        trail.borrow_mut().push();
+        assert_eq!(x.remove_above(-4), DomainEvent::Empty);
+        trail.borrow_mut().pop();
+
+        trail.borrow_mut().push();
+        assert_eq!(x.remove_above(isize::MIN), DomainEvent::Empty);
+        trail.borrow_mut().pop();
+
+        // the bounds themselves are the last values kept
+        trail.borrow_mut().push();
+        assert_eq!(x.remove_below(3), DomainEvent::Fixed);
+        assert!(x.is_fixed());
+        trail.borrow_mut().pop();
+        assert_eq!(x.remove_above(-3), DomainEvent::Fixed);
+        assert_eq!((x.min(), x.max()), (-3, -3));
+    }
+
+    #[test]
+    fn test_extreme_values() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = TrailedIntVar::new(Rc::clone(&trail), -3, 3);
+        assert!(!x.contains(isize::MIN));
+        assert!(!x.contains(isize::MAX));
+
+        trail.borrow_mut().push();
+        assert_eq!(x.remove(isize::MIN), DomainEvent::NoChange);
+        assert_eq!(x.remove(isize::MAX), DomainEvent::NoChange);
+        assert_eq!(x.size(), 7);
+        assert_eq!(x.fix(isize::MAX), DomainEvent::Empty);
+        trail.borrow_mut().pop();
+
+        trail.borrow_mut().push();
+        assert_eq!(x.fix(isize::MIN), DomainEvent::Empty);
+        trail.borrow_mut().pop();
+        assert_eq!((x.min(), x.max(), x.size()), (-3, 3, 7));
+
+        let mut y = TrailedIntVar::new(Rc::clone(&trail), isize::MAX - 2, isize::MAX);
+        assert!(y.contains(isize::MAX));
+        assert!(!y.contains(isize::MIN));
+        assert_eq!(y.remove(isize::MAX), DomainEvent::Changed);
+        assert_eq!(y.max(), isize::MAX - 1);
+        assert_eq!(y.fix(isize::MIN), DomainEvent::Empty);
+    }
+
+    #[test]
+    #[should_panic(expected = "the domain of a variable cannot span the whole range of isize")]
+    fn test_full_range_domain_is_rejected() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        TrailedIntVar::new(trail, isize::MIN, isize::MAX);
+    }
+
+    #[test]
+    fn test_listeners_are_reversible() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let fixes   = Rc::new(Cell::new(0));
+        let bounds  = Rc::new(Cell::new(0));
+        let changes = Rc::new(Cell::new(0));
+        let mut x = TrailedIntVar::new(Rc::clone(&trail), 0, 5);
+
+        let c = Rc::clone(&changes);
+        x.on_domain_change(Box::new(move || c.set(c.get() + 1)));
+
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        let f = Rc::clone(&fixes);
+        x.on_fix(Box::new(move || f.set(f.get() + 1)));
+        let b = Rc::clone(&bounds);
+        x.on_bound_change(Box::new(move || b.set(b.get() + 1)));
+
+        assert_eq!(x.remove(2), DomainEvent::Changed); // interior value
+        assert_eq!((fixes.get(), bounds.get(), changes.get()), (0, 0, 1));
+        assert_eq!(x.remove(0), DomainEvent::Changed); // bound
+        assert_eq!((fixes.get(), bounds.get(), changes.get()), (0, 1, 2));
+        assert_eq!(x.fix(3), DomainEvent::Fixed);
+        assert_eq!((fixes.get(), bounds.get(), changes.get()), (1, 2, 3));
+        assert_eq!(x.fix(3), DomainEvent::NoChange); // no notification
+        assert_eq!((fixes.get(), bounds.get(), changes.get()), (1, 2, 3));
+
+        trail.borrow_mut().pop();
+        assert_eq!((x.min(), x.max(), x.size()), (0, 5, 6));
+        assert_eq!(x.remove(0), DomainEvent::Changed);
+        assert_eq!(x.fix(4), DomainEvent::Fixed);
+        // the level 2 listeners are gone, the root one is still there
+        assert_eq!((fixes.get(), bounds.get(), changes.get()), (1, 2, 5));
+
+        trail.borrow_mut().pop();
+        assert_eq!((x.min(), x.max(), x.size()), (0, 5, 6));
+    }
+}
@@ -0,0 +1,96 @@
+//! This module provides a lazily initialized cell which is cleared upon backtrack.
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::AlwaysTrailed;
+
+/// This structure implements a single slot holding a value which is computed
+/// at most once per branch of the search (think of a `OnceCell` which is reset
+/// upon backtrack). The initialization performed at some level is undone when
+/// that level is popped: the cell is then uninitialized again, and the next
+/// branch recomputes the value. An initialization performed at level 0 is
+/// permanent.
+///
+/// # Note
+/// The slot is an `AlwaysTrailed`, hence T only has to be `Copy` and the
+/// initialization posts (at most) one entry on the trail.
+pub struct ReversibleLazy<'a, T: Copy + 'a> {
+    slot: AlwaysTrailed<'a, Option<T>>
+}
+
+impl<'a, T: Copy + 'a> ReversibleLazy<'a, T> {
+    /// Creates a new uninitialized cell associated with the given trail
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleLazy<'a, T> {
+        ReversibleLazy { slot: AlwaysTrailed::new(trail, None) }
+    }
+
+    /// Returns the value of the cell (None when it is not initialized)
+    pub fn get(&self) -> Option<T> {
+        self.slot.get_value()
+    }
+
+    /// Returns true iff the cell holds a value
+    pub fn is_initialized(&self) -> bool {
+        self.get().is_some()
+    }
+
+    /// Returns the value of the cell, which is first computed with f when the
+    /// cell is not initialized.
+    pub fn get_or_init<F: FnOnce() -> T>(&mut self, f: F) -> T {
+        match self.get() {
+            Some(v) => v,
+            None    => {
+                let v = f();
+                self.slot.set_value(Some(v));
+                v
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_initialization_is_branch_local() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let calls = Cell::new(0);
+        let mut lazy = ReversibleLazy::new(Rc::clone(&trail));
+        let compute = |v: i32| { calls.set(calls.get() + 1); v };
+        assert!(!lazy.is_initialized());
+
+        trail.borrow_mut().push();
+        assert_eq!(lazy.get_or_init(|| compute(1)), 1);
+        assert_eq!(lazy.get_or_init(|| compute(2)), 1);
+        assert_eq!(calls.get(), 1);
+
+        // the inner level sees the outer initialization and shadows nothing
+        trail.borrow_mut().push();
+        assert_eq!(lazy.get_or_init(|| compute(3)), 1);
+        trail.borrow_mut().pop();
+        assert_eq!(lazy.get(), Some(1));
+        assert_eq!(calls.get(), 1);
+
+        trail.borrow_mut().pop();
+        assert_eq!(lazy.get(), None);
+
+        // the next branch recomputes the value
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        assert_eq!(lazy.get_or_init(|| compute(4)), 4);
+        trail.borrow_mut().pop();
+        assert!(!lazy.is_initialized());
+        trail.borrow_mut().pop();
+        assert_eq!(calls.get(), 2);
+
+        // an initialization at the root is permanent
+        assert_eq!(lazy.get_or_init(|| compute(5)), 5);
+        trail.borrow_mut().push();
+        trail.borrow_mut().pop();
+        trail.borrow_mut().pop_all();
+        assert_eq!(lazy.get(), Some(5));
+    }
+}
@@ -0,0 +1,323 @@
+//! This module provides a store for layered multi-valued decision diagrams
+//! (MDDs) whose nodes and edges can be reversibly deleted during filtering.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// An edge of the MDD, going from a node of layer `i` to a node of layer `i+1`
+/// and labeled with some value.
+struct Edge<'a> {
+    src  : usize,
+    dst  : usize,
+    value: usize,
+    alive: Reversible<'a, bool>
+}
+
+/// A node of the MDD, along with its (reversible) in and out degrees.
+struct Node<'a> {
+    layer  : usize,
+    inputs : Vec<usize>,
+    outputs: Vec<usize>,
+    alive  : Reversible<'a, bool>,
+    in_deg : Reversible<'a, usize>,
+    out_deg: Reversible<'a, usize>
+}
+
+/// This structure stores a layered MDD whose nodes and edges can be deleted
+/// during the search. All deletions (as well as the degree and support counters
+/// they affect) are automagically restored upon backtrack.
+///
+/// The diagram is meant to be built at the root (using `add_node` and
+/// `add_edge`) before any node or edge gets deleted. Deletion cascades: a node
+/// that loses all its outgoing edges (resp. incoming edges) gets deleted, and
+/// deleting a node deletes all the edges attached to it.
+pub struct ReversibleMddStore<'a> {
+    trail  : Rc<RefCell<Trail<'a>>>,
+    nodes  : Vec<Node<'a>>,
+    edges  : Vec<Edge<'a>>,
+    /// layers[i] lists the ids of the nodes of the i-th layer
+    layers : Vec<Vec<usize>>,
+    /// support[i][v] counts the live edges labeled v leaving layer i
+    support: Vec<Vec<Reversible<'a, usize>>>
+}
+
+impl<'a> ReversibleMddStore<'a> {
+    /// Creates a new empty store associated with the given trail.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleMddStore<'a> {
+        ReversibleMddStore {
+            trail,
+            nodes  : vec![],
+            edges  : vec![],
+            layers : vec![],
+            support: vec![]
+        }
+    }
+
+    /// Adds a new node to the given layer and returns its identifier.
+    pub fn add_node(&mut self, layer: usize) -> usize {
+        while self.layers.len() <= layer {
+            self.layers.push(vec![]);
+            self.support.push(vec![]);
+        }
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            layer,
+            inputs : vec![],
+            outputs: vec![],
+            alive  : Reversible::new(Rc::clone(&self.trail), true),
+            in_deg : Reversible::new(Rc::clone(&self.trail), 0),
+            out_deg: Reversible::new(Rc::clone(&self.trail), 0)
+        });
+        self.layers[layer].push(id);
+        id
+    }
+
+    /// Adds a new edge labeled `value` between `src` and `dst` and returns its
+    /// identifier. `dst` must belong to the layer that follows the one of `src`.
+    pub fn add_edge(&mut self, src: usize, dst: usize, value: usize) -> usize {
+        let layer = self.nodes[src].layer;
+        assert_eq!(layer + 1, self.nodes[dst].layer, "edges must connect successive layers");
+
+        let id = self.edges.len();
+        self.edges.push(Edge {
+            src,
+            dst,
+            value,
+            alive: Reversible::new(Rc::clone(&self.trail), true)
+        });
+        self.nodes[src].outputs.push(id);
+        self.nodes[dst].inputs .push(id);
+        Self::increment(&mut self.nodes[src].out_deg);
+        Self::increment(&mut self.nodes[dst].in_deg);
+
+        let support = &mut self.support[layer];
+        while support.len() <= value {
+            support.push(Reversible::new(Rc::clone(&self.trail), 0));
+        }
+        Self::increment(&mut support[value]);
+        id
+    }
+
+    /// Returns the number of layers in the diagram
+    pub fn nb_layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Iterates over the live nodes of the i-th layer
+    pub fn layer<'b>(&'b self, i: usize) -> LiveNodes<'a, 'b> {
+        LiveNodes { mdd: self, layer: &self.layers[i], pos: 0 }
+    }
+
+    /// Returns true iff the given node has not been deleted
+    pub fn is_node_alive(&self, node: usize) -> bool {
+        self.nodes[node].alive.get_value()
+    }
+
+    /// Returns true iff the given edge has not been deleted
+    pub fn is_edge_alive(&self, edge: usize) -> bool {
+        self.edges[edge].alive.get_value()
+    }
+
+    /// Returns the number of live edges entering the given node
+    pub fn in_degree(&self, node: usize) -> usize {
+        self.nodes[node].in_deg.get_value()
+    }
+
+    /// Returns the number of live edges leaving the given node
+    pub fn out_degree(&self, node: usize) -> usize {
+        self.nodes[node].out_deg.get_value()
+    }
+
+    /// Returns true iff some live edge labeled `value` leaves the given layer
+    pub fn is_value_supported(&self, layer: usize, value: usize) -> bool {
+        self.support[layer].get(value).is_some_and(|s| s.get_value() > 0)
+    }
+
+    /// Deletes the given edge (and cascades the deletion to the nodes that
+    /// lose all their inputs or outputs).
+    pub fn delete_edge(&mut self, edge: usize) {
+        let mut pending = vec![];
+        self.delete_edge_only(edge, &mut pending);
+        self.cascade(pending);
+    }
+
+    /// Deletes the given node along with all the edges attached to it (and
+    /// cascades the deletion to the nodes that lose all their inputs or outputs).
+    pub fn delete_node(&mut self, node: usize) {
+        self.cascade(vec![node]);
+    }
+
+    /// Deletes all the pending nodes, along with all the nodes that become
+    /// disconnected because of these deletions.
+    fn cascade(&mut self, mut pending: Vec<usize>) {
+        while let Some(node) = pending.pop() {
+            if !self.nodes[node].alive.get_value() {
+                continue;
+            }
+            self.nodes[node].alive.set_value(false);
+
+            for i in 0..self.nodes[node].inputs.len() {
+                let edge = self.nodes[node].inputs[i];
+                self.delete_edge_only(edge, &mut pending);
+            }
+            for i in 0..self.nodes[node].outputs.len() {
+                let edge = self.nodes[node].outputs[i];
+                self.delete_edge_only(edge, &mut pending);
+            }
+        }
+    }
+
+    /// Deletes one edge, updates the counters it affects and records the
+    /// nodes that must be deleted as a consequence.
+    fn delete_edge_only(&mut self, edge: usize, pending: &mut Vec<usize>) {
+        if !self.edges[edge].alive.get_value() {
+            return;
+        }
+        self.edges[edge].alive.set_value(false);
+
+        let (src, dst, value) = {
+            let e = &self.edges[edge];
+            (e.src, e.dst, e.value)
+        };
+        let layer = self.nodes[src].layer;
+        Self::decrement(&mut self.support[layer][value]);
+
+        // The source of an edge never belongs to the last layer and its target
+        // never belongs to the first one. Hence, a node that loses all its
+        // outputs (or inputs) is necessarily disconnected.
+        if Self::decrement(&mut self.nodes[src].out_deg) == 0 {
+            pending.push(src);
+        }
+        if Self::decrement(&mut self.nodes[dst].in_deg) == 0 {
+            pending.push(dst);
+        }
+    }
+
+    fn increment(counter: &mut Reversible<'a, usize>) -> usize {
+        let v = counter.get_value() + 1;
+        counter.set_value(v);
+        v
+    }
+
+    fn decrement(counter: &mut Reversible<'a, usize>) -> usize {
+        let v = counter.get_value() - 1;
+        counter.set_value(v);
+        v
+    }
+}
+
+/// The iterator over the live nodes of some layer of an MDD
+pub struct LiveNodes<'a: 'b, 'b> {
+    mdd  : &'b ReversibleMddStore<'a>,
+    layer: &'b [usize],
+    pos  : usize
+}
+
+impl<'a, 'b> Iterator for LiveNodes<'a, 'b> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.pos < self.layer.len() {
+            let node = self.layer[self.pos];
+            self.pos += 1;
+            if self.mdd.is_node_alive(node) {
+                return Some(node);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Builds the following diagram (values in parentheses):
+    ///
+    ///   layer 0:        r
+    ///               (0)/ \(1)
+    ///   layer 1:      a   b
+    ///             (2) |   | (2),(3)
+    ///   layer 2:      t===t
+    ///
+    /// where b has two edges (labeled 2 and 3) going to t.
+    fn mdd(trail: &Rc<RefCell<Trail<'static>>>) -> ReversibleMddStore<'static> {
+        let mut mdd = ReversibleMddStore::new(Rc::clone(trail));
+        let r = mdd.add_node(0);
+        let a = mdd.add_node(1);
+        let b = mdd.add_node(1);
+        let t = mdd.add_node(2);
+        mdd.add_edge(r, a, 0); // 0
+        mdd.add_edge(r, b, 1); // 1
+        mdd.add_edge(a, t, 2); // 2
+        mdd.add_edge(b, t, 2); // 3
+        mdd.add_edge(b, t, 3); // 4
+        mdd
+    }
+
+    #[test]
+    fn test_construction() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mdd   = mdd(&trail);
+
+        assert_eq!(mdd.nb_layers(), 3);
+        assert_eq!(mdd.layer(1).collect::<Vec<usize>>(), vec![1, 2]);
+        assert_eq!(mdd.out_degree(2), 2);
+        assert_eq!(mdd.in_degree(3), 3);
+        assert!(mdd.is_value_supported(0, 0));
+        assert!(mdd.is_value_supported(1, 3));
+        assert!(!mdd.is_value_supported(1, 0));
+        assert!(!mdd.is_value_supported(1, 42));
+    }
+
+    #[test]
+    fn test_cascade_is_reversible() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut mdd = mdd(&trail);
+
+        trail.borrow_mut().push();
+        mdd.delete_edge(3);
+        assert!(mdd.is_node_alive(2));
+        assert_eq!(mdd.out_degree(2), 1);
+        assert!(mdd.is_value_supported(1, 2));
+
+        trail.borrow_mut().push();
+        // b loses its last output: it is deleted, and so is the edge r -> b
+        mdd.delete_edge(4);
+        assert!(!mdd.is_node_alive(2));
+        assert!(!mdd.is_edge_alive(1));
+        assert!(!mdd.is_value_supported(0, 1));
+        assert!(!mdd.is_value_supported(1, 3));
+        assert_eq!(mdd.layer(1).collect::<Vec<usize>>(), vec![1]);
+        assert_eq!(mdd.out_degree(0), 1);
+        assert_eq!(mdd.in_degree(3), 1);
+
+        trail.borrow_mut().push();
+        // deleting a wipes out the whole diagram
+        mdd.delete_node(1);
+        assert!(!mdd.is_value_supported(1, 2));
+        assert!(!mdd.is_node_alive(0));
+        assert!(!mdd.is_node_alive(3));
+        assert_eq!(mdd.layer(2).count(), 0);
+
+        trail.borrow_mut().pop();
+        assert!(mdd.is_value_supported(1, 2));
+        assert!(mdd.is_node_alive(0));
+        assert_eq!(mdd.in_degree(3), 1);
+
+        trail.borrow_mut().pop();
+        assert!(mdd.is_node_alive(2));
+        assert!(mdd.is_value_supported(0, 1));
+        assert!(mdd.is_value_supported(1, 3));
+        assert_eq!(mdd.out_degree(2), 1);
+        assert_eq!(mdd.in_degree(3), 2);
+
+        trail.borrow_mut().pop();
+        assert_eq!(mdd.out_degree(2), 2);
+        assert_eq!(mdd.in_degree(3), 3);
+        assert!(mdd.is_edge_alive(3));
+    }
+}
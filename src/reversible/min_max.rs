@@ -0,0 +1,140 @@
+//! This module provides a reversible structure tracking the minimum and the
+//! maximum of a set of candidate values from which values are removed.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::{Reversible, ReversibleBitSet};
+
+/// This structure tracks the smallest and the largest of the values which are
+/// still present among a fixed set of candidates. Values are removed through
+/// `remove()` and restored upon backtrack. Both `min()` and `max()` are O(1).
+///
+/// # Holes
+/// Unlike an interval (which is fully described by its bounds), the set of
+/// present values may have holes: removing a value which lies strictly between
+/// the min and the max leaves both bounds unchanged, and the value stays
+/// removed when it later becomes a bound. E.g. removing 3 then 1 from `{1,3,5}`
+/// yields a min of 5.
+///
+/// # Implementation Notes
+/// The candidates are sorted and deduplicated upon creation. The presence of
+/// each of them is kept in a reversible bitset, and the positions of the min
+/// and one past the max in the sorted candidates are reversible pointers. When
+/// a bound is removed, its pointer skips the removed candidates. A candidate is
+/// skipped at most once per pointer along a branch of the search, hence the
+/// cost of the removals is amortized O(1) (plus the binary search of `v`).
+pub struct ReversibleMinMax<'a, T> {
+    sorted : Vec<T>,
+    present: ReversibleBitSet<'a>,
+    lo     : Reversible<'a, usize>,
+    hi     : Reversible<'a, usize>
+}
+
+impl<'a, T: Copy + Ord> ReversibleMinMax<'a, T> {
+    /// Creates a new structure associated with the given trail in which all the
+    /// given candidates are present (duplicate candidates are ignored).
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, candidates: &[T]) -> ReversibleMinMax<'a, T> {
+        let mut sorted = candidates.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let n = sorted.len();
+        ReversibleMinMax {
+            sorted,
+            present: ReversibleBitSet::new(Rc::clone(&trail), n, true),
+            lo     : Reversible::new(Rc::clone(&trail), 0),
+            hi     : Reversible::new(trail, n)
+        }
+    }
+
+    /// Returns true iff all the candidates have been removed
+    pub fn is_empty(&self) -> bool {
+        self.lo.get_value() >= self.hi.get_value()
+    }
+
+    /// Returns the smallest present value (None when the set is empty)
+    pub fn min(&self) -> Option<T> {
+        if self.is_empty() { None } else { Some(self.sorted[self.lo.get_value()]) }
+    }
+
+    /// Returns the largest present value (None when the set is empty)
+    pub fn max(&self) -> Option<T> {
+        if self.is_empty() { None } else { Some(self.sorted[self.hi.get_value() - 1]) }
+    }
+
+    /// Returns true iff v is a present value
+    pub fn contains(&self, v: T) -> bool {
+        self.position(v).is_some()
+    }
+
+    /// Removes v from the set. Returns true iff v was present.
+    pub fn remove(&mut self, v: T) -> bool {
+        let p = match self.position(v) {
+            Some(p) => p,
+            None    => return false
+        };
+        self.present.remove(p);
+
+        let (mut lo, mut hi) = (self.lo.get_value(), self.hi.get_value());
+        if p == lo {
+            while lo < hi && !self.present.contains(lo) {
+                lo += 1;
+            }
+            self.lo.set_value(lo);
+        }
+        if p + 1 == hi {
+            while hi > lo && !self.present.contains(hi - 1) {
+                hi -= 1;
+            }
+            self.hi.set_value(hi);
+        }
+        true
+    }
+
+    /// Returns the position of v among the sorted candidates iff it is present
+    fn position(&self, v: T) -> Option<usize> {
+        self.sorted.binary_search(&v).ok()
+            .filter(|&p| p >= self.lo.get_value() && p < self.hi.get_value() && self.present.contains(p))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bounds_and_interior() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut mm = ReversibleMinMax::new(Rc::clone(&trail), &[7, 1, 5, 3, 9, 5]);
+        assert_eq!((mm.min(), mm.max()), (Some(1), Some(9)));
+
+        trail.borrow_mut().push();
+        // removing interior values leaves the bounds untouched
+        assert!(mm.remove(3));
+        assert!(mm.remove(7));
+        assert!(!mm.remove(7));
+        assert!(!mm.remove(4));
+        assert_eq!((mm.min(), mm.max()), (Some(1), Some(9)));
+
+        trail.borrow_mut().push();
+        // removing the bounds skips the holes
+        assert!(mm.remove(1));
+        assert_eq!(mm.min(), Some(5));
+        assert!(mm.remove(9));
+        assert_eq!(mm.max(), Some(5));
+        assert!(mm.remove(5));
+        assert!(mm.is_empty());
+        assert_eq!((mm.min(), mm.max()), (None, None));
+
+        trail.borrow_mut().pop();
+        assert_eq!((mm.min(), mm.max()), (Some(1), Some(9)));
+        assert!(!mm.contains(3));
+        assert!(mm.remove(9));
+        assert_eq!(mm.max(), Some(5));
+
+        trail.borrow_mut().pop();
+        assert_eq!((mm.min(), mm.max()), (Some(1), Some(9)));
+        assert!(mm.contains(3) && mm.contains(7));
+    }
+}
@@ -0,0 +1,173 @@
+//! This module provides a reversible work queue of constraint ids, as used by
+//! the fix point loop of a propagation engine.
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+
+/// The state of the queue: a sparse set whose first `size` values are pending
+struct QueueState {
+    values : Vec<usize>,
+    indices: Vec<usize>,
+    size   : usize
+}
+
+impl QueueState {
+    /// Swaps the items at positions i and j of the values array
+    fn swap(&mut self, i: usize, j: usize) {
+        let vi = self.values[i];
+        let vj = self.values[j];
+        self.values[i]  = vj;
+        self.values[j]  = vi;
+        self.indices[vi]= j;
+        self.indices[vj]= i;
+    }
+}
+
+/// This structure implements the queue of the constraints which are pending
+/// propagation. It is a sparse set over the constraint ids `0..capacity`, hence
+/// enqueuing an id which is already pending is a no-op (and costs O(1)). The
+/// membership of the queue is restored upon backtrack: the constraints which
+/// were pending when a level was pushed are pending again once it is popped.
+///
+/// # Order
+/// The pending constraints are dequeued in no particular order.
+///
+/// # Implementation Notes
+/// Unlike a `ReversibleSparseSet` which only supports removals (and thus only
+/// needs to trail its size), the queue supports both insertions and removals.
+/// Mixing these reorders the values across the size boundary, which is why
+/// each operation performed below level zero posts one entry on the trail that
+/// swaps the values back and restores the size. Since the trail is unwound in
+/// LIFO order, these entries exactly revert the operations. Changes performed
+/// at level zero are permanent.
+pub struct ReversiblePropagationQueue<'a> {
+    trail: Rc<RefCell<Trail<'a>>>,
+    state: Rc<RefCell<QueueState>>
+}
+
+impl<'a> ReversiblePropagationQueue<'a> {
+    /// Creates a new empty queue for the constraint ids `0..capacity`,
+    /// associated with the given trail.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, capacity: usize) -> ReversiblePropagationQueue<'a> {
+        let state = QueueState { values: (0..capacity).collect(), indices: (0..capacity).collect(), size: 0 };
+        ReversiblePropagationQueue { trail, state: Rc::new(RefCell::new(state)) }
+    }
+
+    /// Returns the largest number of constraints the queue can hold
+    pub fn capacity(&self) -> usize {
+        self.state.borrow().values.len()
+    }
+
+    /// Returns the number of pending constraints
+    pub fn len(&self) -> usize {
+        self.state.borrow().size
+    }
+
+    /// Returns true iff no constraint is pending
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true iff the given constraint is pending
+    pub fn contains(&self, id: usize) -> bool {
+        let state = self.state.borrow();
+        id < state.values.len() && state.indices[id] < state.size
+    }
+
+    /// Marks the given constraint as pending. Returns false (and does nothing)
+    /// if it already was.
+    pub fn enqueue(&mut self, id: usize) -> bool {
+        assert!(id < self.capacity(), "constraint id out of bounds");
+        if self.contains(id) {
+            return false;
+        }
+        let (i, j) = {
+            let mut state = self.state.borrow_mut();
+            let (i, j) = (state.indices[id], state.size);
+            state.swap(i, j);
+            state.size += 1;
+            (i, j)
+        };
+        self.post(i, j, j);
+        true
+    }
+
+    /// Removes one of the pending constraints from the queue and returns it
+    /// (None when the queue is empty).
+    pub fn dequeue(&mut self) -> Option<usize> {
+        let id = {
+            let mut state = self.state.borrow_mut();
+            if state.size == 0 {
+                return None;
+            }
+            state.size -= 1;
+            state.values[state.size]
+        };
+        let size = self.len();
+        self.post(size, size, size + 1);
+        Some(id)
+    }
+
+    /// Posts the entry which undoes the last operation: it swaps the values at
+    /// positions i and j back, and restores the size that was `old`.
+    fn post(&self, i: usize, j: usize, old: usize) {
+        let mut trail = self.trail.borrow_mut();
+        if trail.level() > 0 {
+            let dst = Rc::clone(&self.state);
+            trail.push_on_trail(Box::new(move || {
+                let mut state = dst.borrow_mut();
+                state.size = old;
+                state.swap(i, j);
+            }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn drain(queue: &mut ReversiblePropagationQueue) -> Vec<usize> {
+        let mut processed = vec![];
+        while let Some(id) = queue.dequeue() {
+            processed.push(id);
+        }
+        processed.sort();
+        processed
+    }
+
+    #[test]
+    fn test_no_double_processing() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut queue = ReversiblePropagationQueue::new(Rc::clone(&trail), 6);
+
+        trail.borrow_mut().push();
+        assert!(queue.enqueue(3));
+        assert!(queue.enqueue(1));
+        assert!(!queue.enqueue(3));
+        assert!(queue.enqueue(5));
+        assert!(!queue.enqueue(1));
+        assert_eq!(queue.len(), 3);
+
+        trail.borrow_mut().push();
+        queue.dequeue();
+        queue.enqueue(0);
+        assert!(queue.contains(0));
+        assert_eq!(queue.len(), 3);
+        assert_eq!(drain(&mut queue).len(), 3);
+        assert!(queue.is_empty());
+
+        trail.borrow_mut().pop();
+        assert_eq!(drain(&mut queue), vec![1, 3, 5]);
+        queue.enqueue(4);
+        queue.enqueue(4);
+        assert_eq!(drain(&mut queue), vec![4]);
+
+        trail.borrow_mut().pop();
+        assert!(queue.is_empty());
+        assert!(!queue.contains(3));
+    }
+}
@@ -0,0 +1,235 @@
+//! This module provides a reversible map from disjoint integer ranges to values.
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Ref, RefCell};
+use core::ops::Range;
+
+use ::context::Trail;
+
+/// One segment of the map: the half open range start..end mapped to value
+type Segment<V> = (isize, isize, V);
+
+/// This structure maps disjoint (half open) ranges of integers to values. The
+/// inserts and removals may split the existing ranges in up to three pieces, or
+/// merge adjacent ranges mapped to the same value. All of these are undone upon
+/// backtrack so that the map recovers its exact previous segmentation.
+///
+/// # Implementation Notes
+/// The segments are stored sorted in a vector. Each update replaces a window
+/// of contiguous segments by a (possibly different) number of new segments.
+/// When this happens below level zero, an entry is posted on the trail which
+/// puts the original segments back in place of the new ones. Since the trail
+/// is unwound in LIFO order, the window is always valid when the entry is run.
+/// Changes performed at level zero are permanent.
+pub struct ReversibleRangeMap<'a, V>
+    where V: Copy + PartialEq + 'a {
+    trail   : Rc<RefCell<Trail<'a>>>,
+    segments: Rc<RefCell<Vec<Segment<V>>>>
+}
+
+impl<'a, V> ReversibleRangeMap<'a, V>
+    where V: Copy + PartialEq + 'a {
+    /// Creates a new empty map associated with the given trail.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleRangeMap<'a, V> {
+        ReversibleRangeMap { trail, segments: Rc::new(RefCell::new(vec![])) }
+    }
+
+    /// Returns the number of disjoint segments in the map
+    pub fn nb_segments(&self) -> usize {
+        self.segments.borrow().len()
+    }
+
+    /// Returns true iff no point is mapped to any value
+    pub fn is_empty(&self) -> bool {
+        self.segments.borrow().is_empty()
+    }
+
+    /// Returns the value associated with the given point (if any)
+    pub fn get(&self, point: isize) -> Option<V> {
+        let segments = self.segments.borrow();
+        let pos = segments.partition_point(|s| s.1 <= point);
+        segments.get(pos)
+            .filter(|s| s.0 <= point)
+            .map(|s| s.2)
+    }
+
+    /// Maps all the points of the range a..b to v (overwriting whatever they
+    /// were mapped to before).
+    pub fn insert(&mut self, range: Range<isize>, v: V) {
+        if range.start < range.end {
+            self.update(range, Some(v));
+        }
+    }
+
+    /// Unmaps all the points of the range a..b
+    pub fn remove(&mut self, range: Range<isize>) {
+        if range.start < range.end {
+            self.update(range, None);
+        }
+    }
+
+    /// Iterates (in increasing order) over the segments which overlap the
+    /// range a..b. Each item is the complete segment and its value.
+    pub fn overlapping(&self, range: Range<isize>) -> RangeMapIter<'_, V> {
+        let segments = self.segments.borrow();
+        let pos = segments.partition_point(|s| s.1 <= range.start);
+        let end = segments.partition_point(|s| s.0 <  range.end).max(pos);
+        RangeMapIter { segments, pos, end }
+    }
+
+    /// Replaces the content of a..b with the given value (or makes it unmapped)
+    fn update(&mut self, range: Range<isize>, v: Option<V>) {
+        let (a, b) = (range.start, range.end);
+        let (from, to, replacement) = {
+            let segments = self.segments.borrow();
+            // the window comprises all segments that overlap or touch a..b
+            let from = segments.partition_point(|s| s.1 <  a);
+            let to   = segments.partition_point(|s| s.0 <= b);
+
+            let mut pieces = vec![];
+            if let Some(first) = segments[from..to].first() {
+                if first.0 < a {
+                    pieces.push((first.0, a.min(first.1), first.2));
+                }
+            }
+            if let Some(v) = v {
+                pieces.push((a, b, v));
+            }
+            if let Some(last) = segments[from..to].last() {
+                if last.1 > b {
+                    pieces.push((b.max(last.0), last.1, last.2));
+                }
+            }
+
+            let mut replacement : Vec<Segment<V>> = vec![];
+            for piece in pieces {
+                match replacement.last_mut() {
+                    Some(prev) if prev.1 == piece.0 && prev.2 == piece.2 => prev.1 = piece.1,
+                    _ => replacement.push(piece)
+                }
+            }
+
+            if segments[from..to] == replacement[..] {
+                return;
+            }
+            (from, to, replacement)
+        };
+
+        let added    = replacement.len();
+        let original : Vec<Segment<V>> = self.segments.borrow_mut()
+            .splice(from..to, replacement)
+            .collect();
+
+        let mut trail = self.trail.borrow_mut();
+        if trail.level() > 0 {
+            let dst = Rc::clone(&self.segments);
+            trail.push_on_trail(Box::new(move || {
+                dst.borrow_mut().splice(from..from + added, original.iter().cloned());
+            }));
+        }
+    }
+}
+
+/// The iterator over the segments of a reversible range map.
+///
+/// # Note
+/// The iterator borrows the content of the map: the map cannot be modified
+/// (nor the trail popped) as long as the iterator is alive.
+pub struct RangeMapIter<'b, V: 'b> {
+    segments: Ref<'b, Vec<Segment<V>>>,
+    pos     : usize,
+    end     : usize
+}
+
+impl<'b, V> Iterator for RangeMapIter<'b, V>
+    where V: Copy + 'b {
+    type Item = (Range<isize>, V);
+
+    fn next(&mut self) -> Option<(Range<isize>, V)> {
+        if self.pos < self.end {
+            let (start, end, v) = self.segments[self.pos];
+            self.pos += 1;
+            Some((start..end, v))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn content<'a>(map: &ReversibleRangeMap<'a, char>) -> Vec<(Range<isize>, char)> {
+        map.overlapping(isize::MIN..isize::MAX).collect()
+    }
+
+    #[test]
+    fn test_split_in_three_and_restore() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut map = ReversibleRangeMap::new(Rc::clone(&trail));
+        map.insert(0..10, 'a');
+
+        trail.borrow_mut().push();
+        map.insert(3..5, 'b');
+        assert_eq!(content(&map), vec![(0..3, 'a'), (3..5, 'b'), (5..10, 'a')]);
+        assert_eq!(map.get(4), Some('b'));
+        assert_eq!(map.get(5), Some('a'));
+        assert_eq!(map.get(10), None);
+
+        trail.borrow_mut().push();
+        map.remove(4..7);
+        assert_eq!(content(&map), vec![(0..3, 'a'), (3..4, 'b'), (7..10, 'a')]);
+        assert_eq!(map.get(5), None);
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&map), vec![(0..3, 'a'), (3..5, 'b'), (5..10, 'a')]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&map), vec![(0..10, 'a')]);
+    }
+
+    #[test]
+    fn test_merge_and_restore() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut map = ReversibleRangeMap::new(Rc::clone(&trail));
+        map.insert(0..3, 'a');
+        map.insert(5..8, 'a');
+        map.insert(10..12, 'b');
+
+        trail.borrow_mut().push();
+        // fills the hole and overwrites part of the b segment
+        map.insert(2..11, 'a');
+        assert_eq!(content(&map), vec![(0..11, 'a'), (11..12, 'b')]);
+        assert_eq!(map.nb_segments(), 2);
+
+        trail.borrow_mut().push();
+        map.remove(-5..20);
+        assert!(map.is_empty());
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&map), vec![(0..11, 'a'), (11..12, 'b')]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&map), vec![(0..3, 'a'), (5..8, 'a'), (10..12, 'b')]);
+        assert_eq!(map.overlapping(4..6).collect::<Vec<_>>(), vec![(5..8, 'a')]);
+        assert_eq!(map.overlapping(3..5).count(), 0);
+    }
+
+    #[test]
+    fn test_noop_update_does_not_trail() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut map = ReversibleRangeMap::new(Rc::clone(&trail));
+        map.insert(0..10, 'a');
+
+        trail.borrow_mut().push();
+        map.insert(2..4, 'a');
+        map.remove(20..30);
+        map.insert(5..5, 'z');
+        assert_eq!(content(&map), vec![(0..10, 'a')]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&map), vec![(0..10, 'a')]);
+    }
+}
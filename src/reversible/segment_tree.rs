@@ -0,0 +1,271 @@
+//! This module provides a reversible segment tree which can be used to answer
+//! range queries (sum, min, max, ...) over an array whose entries are updated
+//! during a branch and automagically restored upon backtrack.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use core::ops::{Add, Range};
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// A monoid defines how the values of two adjacent segments are combined.
+/// The `identity()` is the value of an empty segment, that is to say the value
+/// `e` such that `combine(e, x) == combine(x, e) == x` for all x.
+pub trait Monoid<T> {
+    /// Returns the neutral element of the monoid
+    fn identity() -> T;
+    /// Combines the values of two adjacent segments
+    fn combine(a: T, b: T) -> T;
+}
+
+/// This trait is implemented by the types that have a smallest and a largest
+/// value. It is used to provide the identity of the `Min` and `Max` monoids.
+pub trait Bounded {
+    /// Returns the smallest value of the type
+    fn min_value() -> Self;
+    /// Returns the largest value of the type
+    fn max_value() -> Self;
+}
+
+macro_rules! bounded {
+    ($($t:ty),*) => {
+        $(impl Bounded for $t {
+            fn min_value() -> $t { <$t>::MIN }
+            fn max_value() -> $t { <$t>::MAX }
+        })*
+    }
+}
+bounded!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// The sum monoid (identity is `T::default()`, that is zero for the numbers)
+pub struct Sum;
+/// The min monoid (identity is the largest value of T)
+pub struct Min;
+/// The max monoid (identity is the smallest value of T)
+pub struct Max;
+
+impl<T> Monoid<T> for Sum where T: Copy + Default + Add<Output=T> {
+    fn identity() -> T { T::default() }
+    fn combine(a: T, b: T) -> T { a + b }
+}
+impl<T> Monoid<T> for Min where T: Copy + Ord + Bounded {
+    fn identity() -> T { T::max_value() }
+    fn combine(a: T, b: T) -> T { a.min(b) }
+}
+impl<T> Monoid<T> for Max where T: Copy + Ord + Bounded {
+    fn identity() -> T { T::min_value() }
+    fn combine(a: T, b: T) -> T { a.max(b) }
+}
+
+/// This structure implements a (bottom-up) segment tree whose nodes are all
+/// reversible. Updating an entry only modifies the O(log n) nodes that lie on
+/// the path between that leaf and the root. Each of these is a `Reversible`,
+/// which means that it posts at most one entry on the trail per level.
+pub struct ReversibleSegmentTree<'a, T, M>
+    where T: Copy + PartialEq + 'a,
+          M: Monoid<T> {
+    /// The number of leaves
+    len  : usize,
+    /// The nodes of the tree: the leaves are stored at positions len..2*len
+    /// and the parent of node i is node i/2.
+    nodes: Vec<Reversible<'a, T>>,
+    monoid: PhantomData<M>
+}
+
+impl<'a, T, M> ReversibleSegmentTree<'a, T, M>
+    where T: Copy + PartialEq + 'a,
+          M: Monoid<T> {
+    /// Creates a new segment tree associated with the given trail and
+    /// whose leaves are initialized with the values of the given slice.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, initial: &[T]) -> ReversibleSegmentTree<'a, T, M> {
+        let len = initial.len();
+
+        let mut values = vec![M::identity(); 2 * len];
+        values[len..].copy_from_slice(initial);
+        for i in (1..len).rev() {
+            values[i] = M::combine(values[2 * i], values[2 * i + 1]);
+        }
+
+        let nodes = values.into_iter()
+            .map(|v| Reversible::new(Rc::clone(&trail), v))
+            .collect();
+
+        ReversibleSegmentTree { len, nodes, monoid: PhantomData }
+    }
+
+    /// Returns the number of entries in the underlying array
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true iff the underlying array is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the current value of the i-th entry of the array
+    pub fn get(&self, i: usize) -> T {
+        assert!(i < self.len, "index out of bounds");
+        self.nodes[self.len + i].get_value()
+    }
+
+    /// Changes the value of the i-th entry of the array to v, and updates all
+    /// the nodes between that leaf and the root.
+    pub fn update(&mut self, i: usize, v: T) {
+        assert!(i < self.len, "index out of bounds");
+        let mut pos = self.len + i;
+        self.nodes[pos].set_value(v);
+
+        while pos > 1 {
+            pos /= 2;
+            let combined = M::combine(
+                self.nodes[2 * pos].get_value(),
+                self.nodes[2 * pos + 1].get_value());
+            self.nodes[pos].set_value(combined);
+        }
+    }
+
+    /// Returns the combination of all the entries in the range a..b
+    /// (the identity of the monoid if the range is empty).
+    pub fn query(&self, range: Range<usize>) -> T {
+        assert!(range.end <= self.len, "range out of bounds");
+        let mut left  = M::identity();
+        let mut right = M::identity();
+
+        let mut lo = range.start + self.len;
+        let mut hi = range.end   + self.len;
+        while lo < hi {
+            if lo % 2 == 1 {
+                left = M::combine(left, self.nodes[lo].get_value());
+                lo  += 1;
+            }
+            if hi % 2 == 1 {
+                hi   -= 1;
+                right = M::combine(self.nodes[hi].get_value(), right);
+            }
+            lo /= 2;
+            hi /= 2;
+        }
+        M::combine(left, right)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate rand;
+    use self::rand::Rng;
+    use super::*;
+
+    fn naive_sum(data: &[isize], range: Range<usize>) -> isize {
+        data[range].iter().sum()
+    }
+    fn naive_min(data: &[isize], range: Range<usize>) -> isize {
+        data[range].iter().cloned().min().unwrap_or(isize::MAX)
+    }
+
+    #[test]
+    fn test_ok() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut tree : ReversibleSegmentTree<isize, Sum> =
+            ReversibleSegmentTree::new(Rc::clone(&trail), &[1, 2, 3, 4, 5]);
+
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.query(0..5), 15);
+        assert_eq!(tree.query(1..3), 5);
+        assert_eq!(tree.query(2..2), 0);
+
+        trail.borrow_mut().push();
+        tree.update(0, 10);
+        assert_eq!(tree.query(0..5), 24);
+
+        trail.borrow_mut().push();
+        tree.update(4, 0);
+        tree.update(4, -5);
+        assert_eq!(tree.query(0..5), 14);
+        assert_eq!(tree.get(4), -5);
+
+        trail.borrow_mut().pop();
+        assert_eq!(tree.query(0..5), 24);
+        assert_eq!(tree.get(4), 5);
+
+        trail.borrow_mut().pop();
+        assert_eq!(tree.query(0..5), 15);
+        assert_eq!(tree.get(0), 1);
+    }
+
+    #[test]
+    fn test_min_max() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut min : ReversibleSegmentTree<isize, Min> =
+            ReversibleSegmentTree::new(Rc::clone(&trail), &[5, 3, 8, 6]);
+        let mut max : ReversibleSegmentTree<isize, Max> =
+            ReversibleSegmentTree::new(Rc::clone(&trail), &[5, 3, 8, 6]);
+
+        trail.borrow_mut().push();
+        min.update(1, 9);
+        max.update(2, 1);
+        assert_eq!(min.query(0..4), 5);
+        assert_eq!(max.query(0..4), 6);
+
+        trail.borrow_mut().pop();
+        assert_eq!(min.query(0..4), 3);
+        assert_eq!(max.query(0..4), 8);
+    }
+
+    #[test]
+    fn test_empty() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let tree : ReversibleSegmentTree<isize, Min> =
+            ReversibleSegmentTree::new(Rc::clone(&trail), &[]);
+
+        assert!(tree.is_empty());
+        assert_eq!(tree.query(0..0), isize::MAX);
+    }
+
+    #[test]
+    fn test_random_against_naive() {
+        let mut rng = rand::thread_rng();
+        let n = 37;
+        let initial : Vec<isize> = (0..n).map(|_| rng.gen_range(-100, 100)).collect();
+
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut sum : ReversibleSegmentTree<isize, Sum> =
+            ReversibleSegmentTree::new(Rc::clone(&trail), &initial);
+        let mut min : ReversibleSegmentTree<isize, Min> =
+            ReversibleSegmentTree::new(Rc::clone(&trail), &initial);
+
+        let mut saved = vec![];
+        let mut data  = initial.clone();
+        trail.borrow_mut().push();
+        for _ in 0..500 {
+            match rng.gen_range(0, 4) {
+                0 => {
+                    trail.borrow_mut().push();
+                    saved.push(data.clone());
+                },
+                1 => if let Some(previous) = saved.pop() {
+                    trail.borrow_mut().pop();
+                    data = previous;
+                },
+                _ => {
+                    let i = rng.gen_range(0, n);
+                    let v = rng.gen_range(-100, 100);
+                    data[i] = v;
+                    sum.update(i, v);
+                    min.update(i, v);
+                }
+            }
+
+            let a = rng.gen_range(0, n + 1);
+            let b = rng.gen_range(a, n + 1);
+            assert_eq!(sum.query(a..b), naive_sum(&data, a..b));
+            assert_eq!(min.query(a..b), naive_min(&data, a..b));
+        }
+
+        trail.borrow_mut().pop_all();
+        assert_eq!(sum.query(0..n), naive_sum(&initial, 0..n));
+        assert_eq!(min.query(0..n), naive_min(&initial, 0..n));
+    }
+}
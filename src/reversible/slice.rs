@@ -0,0 +1,136 @@
+//! This module provides aggregate reads over slices of reversibles (e.g. the
+//! `Vec<Reversible<i32>>` holding the variables of a model). The functions
+//! accept slices of reversibles, of readers, of views or of references to any
+//! of these, and they always read the current values.
+use alloc::vec::Vec;
+use core::ops::Add;
+
+use ::reversible::{Reversible, ReversibleReader, ReversibleView};
+
+/// The types whose current value can be read (at no cost)
+pub trait ValueSource<T> {
+    /// Returns the current value
+    fn current(&self) -> T;
+}
+
+impl<'a, T: Copy + PartialEq + 'a> ValueSource<T> for Reversible<'a, T> {
+    fn current(&self) -> T {
+        self.get_value()
+    }
+}
+
+impl<T: Copy> ValueSource<T> for ReversibleReader<T> {
+    fn current(&self) -> T {
+        self.get()
+    }
+}
+
+impl<'a, T: 'a> ValueSource<T> for ReversibleView<'a, T> {
+    fn current(&self) -> T {
+        self.get()
+    }
+}
+
+impl<T, S: ValueSource<T> + ?Sized> ValueSource<T> for &S {
+    fn current(&self) -> T {
+        (**self).current()
+    }
+}
+
+/// Returns the current values of the given cells
+pub fn values_of<T, S: ValueSource<T>>(cells: &[S]) -> Vec<T> {
+    cells.iter().map(|c| c.current()).collect()
+}
+
+/// Returns the sum of the current values of the given cells (the default
+/// value of T when the slice is empty)
+pub fn sum<T, S>(cells: &[S]) -> T
+    where T: Add<Output = T> + Default, S: ValueSource<T> {
+    cells.iter().fold(T::default(), |acc, c| acc + c.current())
+}
+
+/// Returns the index of the cell holding the smallest value (the first one in
+/// case of ties, None when the slice is empty)
+pub fn min_index<T: PartialOrd, S: ValueSource<T>>(cells: &[S]) -> Option<usize> {
+    best_index(cells, |candidate, best| candidate < best)
+}
+
+/// Returns the index of the cell holding the largest value (the first one in
+/// case of ties, None when the slice is empty)
+pub fn max_index<T: PartialOrd, S: ValueSource<T>>(cells: &[S]) -> Option<usize> {
+    best_index(cells, |candidate, best| candidate > best)
+}
+
+/// Returns true iff all the given cells currently hold v (true when the slice
+/// is empty)
+pub fn all_eq<T: PartialEq, S: ValueSource<T>>(cells: &[S], v: T) -> bool {
+    cells.iter().all(|c| c.current() == v)
+}
+
+/// Returns the number of the given cells which currently hold v
+pub fn count_eq<T: PartialEq, S: ValueSource<T>>(cells: &[S], v: T) -> usize {
+    cells.iter().filter(|c| c.current() == v).count()
+}
+
+/// Returns the index of the first cell whose value is better than that of all
+/// the preceding ones
+fn best_index<T, S, F>(cells: &[S], better: F) -> Option<usize>
+    where S: ValueSource<T>, F: Fn(&T, &T) -> bool {
+    let mut best : Option<(usize, T)> = None;
+    for (i, c) in cells.iter().enumerate() {
+        let v = c.current();
+        if best.as_ref().is_none_or(|(_, b)| better(&v, b)) {
+            best = Some((i, v));
+        }
+    }
+    best.map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::context::Trail;
+    use super::*;
+
+    #[test]
+    fn test_aggregates_follow_backtrack() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut xs = Reversible::new_array(&trail, 4, |i| i as i32);
+        let readers : Vec<ReversibleReader<i32>> = xs.iter().map(|x| x.reader()).collect();
+        assert_eq!(values_of(&xs), vec![0, 1, 2, 3]);
+        assert_eq!(sum(&xs), 6);
+        assert_eq!((min_index(&xs), max_index(&xs)), (Some(0), Some(3)));
+
+        trail.borrow_mut().push();
+        xs[0].set_value(5);
+        xs[3].set_value(5);
+        assert_eq!(values_of(&readers), vec![5, 1, 2, 5]);
+        assert_eq!(sum(&readers), 13);
+        assert_eq!((min_index(&xs), max_index(&xs)), (Some(1), Some(0)));
+        assert_eq!(count_eq(&xs, 5), 2);
+        assert!(!all_eq(&xs, 5));
+
+        trail.borrow_mut().push();
+        for x in xs.iter_mut() {
+            x.set_value(7);
+        }
+        let refs : Vec<&Reversible<i32>> = xs.iter().collect();
+        assert!(all_eq(&refs, 7));
+        assert_eq!((min_index(&refs), max_index(&refs)), (Some(0), Some(0)));
+
+        trail.borrow_mut().pop();
+        assert_eq!(values_of(&readers), vec![5, 1, 2, 5]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(values_of(&refs), vec![0, 1, 2, 3]);
+        assert_eq!(count_eq(&readers, 5), 0);
+
+        let doubled : Vec<ReversibleView<i32>> = xs.iter().map(|x| x.map(|v| v * 2)).collect();
+        assert_eq!(sum(&doubled), 12);
+
+        let empty : [Reversible<i32>; 0] = [];
+        assert_eq!((sum(&empty), min_index(&empty)), (0, None));
+        assert!(all_eq(&empty, 1));
+    }
+}
@@ -0,0 +1,198 @@
+//! This module provides a reversible sorted collection of keys.
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::{Ref, RefCell};
+use core::ops::Range;
+
+use ::context::Trail;
+
+/// This structure implements an ordered set of keys (stored in a sorted vector)
+/// whose insertions and removals are automagically undone upon backtrack.
+///
+/// # Duplicates
+/// The collection has set semantics: inserting a key which is already present
+/// is rejected (and `insert` returns false).
+///
+/// # Implementation Notes
+/// Each effective insertion (resp. removal) performed below level zero posts
+/// one entry on the trail which removes (resp. re-inserts) the key at the
+/// position where it was inserted (resp. removed). Since the trail is unwound
+/// in LIFO order, these positions are always valid when the entries are run.
+/// Changes performed at level zero are permanent.
+pub struct ReversibleSortedVec<'a, T>
+    where T: Copy + Ord + 'a {
+    trail: Rc<RefCell<Trail<'a>>>,
+    data : Rc<RefCell<Vec<T>>>
+}
+
+impl<'a, T> ReversibleSortedVec<'a, T>
+    where T: Copy + Ord + 'a {
+    /// Creates a new empty collection associated with the given trail.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleSortedVec<'a, T> {
+        ReversibleSortedVec { trail, data: Rc::new(RefCell::new(vec![])) }
+    }
+
+    /// Returns the number of keys in the collection
+    pub fn len(&self) -> usize {
+        self.data.borrow().len()
+    }
+
+    /// Returns true iff the collection holds no key
+    pub fn is_empty(&self) -> bool {
+        self.data.borrow().is_empty()
+    }
+
+    /// Returns true iff v is present in the collection
+    pub fn contains(&self, v: T) -> bool {
+        self.data.borrow().binary_search(&v).is_ok()
+    }
+
+    /// Returns the number of keys strictly smaller than v
+    pub fn rank(&self, v: T) -> usize {
+        match self.data.borrow().binary_search(&v) {
+            Ok(pos)  => pos,
+            Err(pos) => pos
+        }
+    }
+
+    /// Inserts v at its position in the collection. Returns false (and leaves
+    /// the collection unchanged) if v was already present.
+    pub fn insert(&mut self, v: T) -> bool {
+        let pos = match self.data.borrow().binary_search(&v) {
+            Ok(_)    => return false,
+            Err(pos) => pos
+        };
+        self.data.borrow_mut().insert(pos, v);
+
+        let dst = Rc::clone(&self.data);
+        self.post(Box::new(move || { dst.borrow_mut().remove(pos); }));
+        true
+    }
+
+    /// Removes v from the collection. Returns false if v was not present.
+    pub fn remove(&mut self, v: T) -> bool {
+        let pos = match self.data.borrow().binary_search(&v) {
+            Ok(pos) => pos,
+            Err(_)  => return false
+        };
+        self.data.borrow_mut().remove(pos);
+
+        let dst = Rc::clone(&self.data);
+        self.post(Box::new(move || dst.borrow_mut().insert(pos, v)));
+        true
+    }
+
+    /// Iterates over the keys of the collection in increasing order
+    pub fn iter(&self) -> SortedVecIter<'_, T> {
+        let data = self.data.borrow();
+        let end  = data.len();
+        SortedVecIter { data, pos: 0, end }
+    }
+
+    /// Iterates (in increasing order) over the keys k such that a <= k < b
+    pub fn range(&self, range: Range<T>) -> SortedVecIter<'_, T> {
+        let pos = self.rank(range.start);
+        let end = self.rank(range.end).max(pos);
+        SortedVecIter { data: self.data.borrow(), pos, end }
+    }
+
+    /// Posts the given restoration closure on the trail (unless we are at the
+    /// root level, in which case changes are permanent).
+    fn post(&self, entry: Box<dyn FnMut() + 'a>) {
+        let mut trail = self.trail.borrow_mut();
+        if trail.level() > 0 {
+            trail.push_on_trail(entry);
+        }
+    }
+}
+
+/// The iterator over the keys of a reversible sorted vector.
+///
+/// # Note
+/// The iterator borrows the content of the collection: the collection cannot
+/// be modified (nor the trail popped) as long as the iterator is alive.
+pub struct SortedVecIter<'b, T: 'b> {
+    data: Ref<'b, Vec<T>>,
+    pos : usize,
+    end : usize
+}
+
+impl<'b, T> Iterator for SortedVecIter<'b, T>
+    where T: Copy + 'b {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.pos < self.end {
+            self.pos += 1;
+            Some(self.data[self.pos - 1])
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate rand;
+    use self::rand::Rng;
+    use std::collections::BTreeSet;
+    use super::*;
+
+    #[test]
+    fn test_ok() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut keys = ReversibleSortedVec::new(Rc::clone(&trail));
+        keys.insert(10);
+        keys.insert(30);
+
+        trail.borrow_mut().push();
+        assert!(keys.insert(20));
+        assert!(!keys.insert(20));
+        assert!(keys.remove(10));
+        assert!(!keys.remove(10));
+        assert_eq!(keys.iter().collect::<Vec<i32>>(), vec![20, 30]);
+        assert_eq!(keys.rank(25), 1);
+        assert_eq!(keys.range(15..30).collect::<Vec<i32>>(), vec![20]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(keys.iter().collect::<Vec<i32>>(), vec![10, 30]);
+        assert!(!keys.contains(20));
+        assert_eq!(keys.range(31..40).count(), 0);
+    }
+
+    #[test]
+    fn test_random_against_btreeset() {
+        let mut rng = rand::thread_rng();
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut keys = ReversibleSortedVec::new(Rc::clone(&trail));
+
+        let mut oracle = BTreeSet::new();
+        let mut saved  = vec![];
+        for _ in 0..1000 {
+            match rng.gen_range(0, 10) {
+                0 => {
+                    trail.borrow_mut().push();
+                    saved.push(oracle.clone());
+                },
+                1 => if let Some(previous) = saved.pop() {
+                    trail.borrow_mut().pop();
+                    oracle = previous;
+                },
+                2..=5 => {
+                    let v = rng.gen_range(0, 50);
+                    assert_eq!(keys.insert(v), oracle.insert(v));
+                },
+                _ => {
+                    let v = rng.gen_range(0, 50);
+                    assert_eq!(keys.remove(v), oracle.remove(&v));
+                }
+            }
+
+            assert_eq!(keys.iter().collect::<Vec<u32>>(), oracle.iter().cloned().collect::<Vec<u32>>());
+            let a = rng.gen_range(0, 50);
+            let b = rng.gen_range(a, 51);
+            assert_eq!(keys.range(a..b).collect::<Vec<u32>>(), oracle.range(a..b).cloned().collect::<Vec<u32>>());
+        }
+    }
+}
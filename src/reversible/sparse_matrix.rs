@@ -0,0 +1,138 @@
+//! This module provides a matrix whose rows and columns can be reversibly
+//! removed. This comes in handy for assignment-style reasoning (Hungarian
+//! bounds, alldifferent with costs, ...).
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::iter::Cloned;
+use core::slice::Iter;
+
+use ::context::Trail;
+use ::reversible::ReversibleSparseSet;
+
+/// This structure implements a matrix of `T` whose rows and columns can be
+/// removed during a branch and are automagically restored upon backtrack.
+/// The liveness of the rows and columns is tracked by two sparse sets, which
+/// means that both removal and restoration are O(1).
+pub struct ReversibleSparseMatrix<'a, T>
+    where T: Copy {
+    nb_cols: usize,
+    data   : Vec<T>,
+    rows   : ReversibleSparseSet<'a>,
+    cols   : ReversibleSparseSet<'a>
+}
+
+impl<'a, T> ReversibleSparseMatrix<'a, T>
+    where T: Copy {
+    /// Creates a new matrix associated with the given trail. The `data` slice
+    /// holds the `nb_rows * nb_cols` entries of the matrix in row-major order.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, nb_rows: usize, nb_cols: usize, data: &[T]) -> ReversibleSparseMatrix<'a, T> {
+        assert_eq!(data.len(), nb_rows * nb_cols, "data does not match the matrix dimensions");
+        ReversibleSparseMatrix {
+            nb_cols,
+            data: data.to_vec(),
+            rows: ReversibleSparseSet::new(Rc::clone(&trail), nb_rows),
+            cols: ReversibleSparseSet::new(trail, nb_cols)
+        }
+    }
+
+    /// Returns the entry at row r and column c, or None if either of them has
+    /// been removed.
+    pub fn get(&self, r: usize, c: usize) -> Option<T> {
+        if self.rows.contains(r) && self.cols.contains(c) {
+            Some(self.data[r * self.nb_cols + c])
+        } else {
+            None
+        }
+    }
+
+    /// Removes the r-th row of the matrix
+    pub fn remove_row(&mut self, r: usize) {
+        self.rows.remove(r);
+    }
+
+    /// Removes the c-th column of the matrix
+    pub fn remove_col(&mut self, c: usize) {
+        self.cols.remove(c);
+    }
+
+    /// Returns true iff the r-th row has not been removed
+    pub fn is_row_live(&self, r: usize) -> bool {
+        self.rows.contains(r)
+    }
+
+    /// Returns true iff the c-th column has not been removed
+    pub fn is_col_live(&self, c: usize) -> bool {
+        self.cols.contains(c)
+    }
+
+    /// Returns the number of rows which have not been removed
+    pub fn nb_live_rows(&self) -> usize {
+        self.rows.size()
+    }
+
+    /// Returns the number of columns which have not been removed
+    pub fn nb_live_cols(&self) -> usize {
+        self.cols.size()
+    }
+
+    /// Iterates over the rows which have not been removed (in no particular order)
+    pub fn live_rows(&self) -> Cloned<Iter<'_, usize>> {
+        self.rows.as_slice().iter().cloned()
+    }
+
+    /// Iterates over the columns which have not been removed (in no particular order)
+    pub fn live_cols(&self) -> Cloned<Iter<'_, usize>> {
+        self.cols.as_slice().iter().cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sorted<I: Iterator<Item=usize>>(it: I) -> Vec<usize> {
+        let mut v : Vec<usize> = it.collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_nested_removals() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let data : Vec<isize> = (0..12).collect();
+        let mut m = ReversibleSparseMatrix::new(Rc::clone(&trail), 3, 4, &data);
+
+        assert_eq!(m.get(1, 2), Some(6));
+        assert_eq!(sorted(m.live_rows()), vec![0, 1, 2]);
+        assert_eq!(sorted(m.live_cols()), vec![0, 1, 2, 3]);
+
+        trail.borrow_mut().push();
+        m.remove_row(1);
+        m.remove_col(2);
+        assert_eq!(m.get(1, 0), None);
+        assert_eq!(m.get(0, 2), None);
+        assert_eq!(m.get(2, 3), Some(11));
+        assert_eq!(sorted(m.live_rows()), vec![0, 2]);
+
+        trail.borrow_mut().push();
+        m.remove_row(1);
+        m.remove_row(0);
+        m.remove_col(2);
+        m.remove_col(0);
+        assert_eq!(m.nb_live_rows(), 1);
+        assert_eq!(sorted(m.live_cols()), vec![1, 3]);
+
+        trail.borrow_mut().pop();
+        assert!(m.is_row_live(0));
+        assert!(!m.is_row_live(1));
+        assert!(m.is_col_live(0));
+        assert!(!m.is_col_live(2));
+        assert_eq!(sorted(m.live_cols()), vec![0, 1, 3]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(m.nb_live_rows(), 3);
+        assert_eq!(m.nb_live_cols(), 4);
+        assert_eq!(m.get(1, 2), Some(6));
+    }
+}
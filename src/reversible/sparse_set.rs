@@ -0,0 +1,835 @@
+//! This module provides a reversible sparse set. This is the data structure of
+//! choice to represent the domain of the variables of a CP solver since it
+//! supports the removal of values in O(1) and their restoration upon backtrack
+//! in O(1) as well.
+use alloc::vec::{self, Vec};
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::slice;
+use core::fmt;
+
+use ::context::Trail;
+use ::reversible::{Reversible, Wipeout};
+
+/// The event that results from the removal of values from a domain. This lets
+/// a propagator react precisely to what happened (and detect a failure when the
+/// domain becomes empty).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DomainEvent {
+    /// The domain was left unchanged
+    NoChange,
+    /// The domain lost some values but still holds more than one
+    Changed,
+    /// The domain lost some values and is now reduced to a single value
+    Fixed,
+    /// The domain lost its last value (which means the search must backtrack)
+    Empty
+}
+
+impl DomainEvent {
+    /// Returns true iff the domain was modified
+    pub fn is_change(self) -> bool {
+        self != DomainEvent::NoChange
+    }
+
+    /// Returns true iff the domain was wiped out
+    pub fn is_failure(self) -> bool {
+        self == DomainEvent::Empty
+    }
+
+    /// Converts the event into a result: true iff the domain was modified, and
+    /// an error when it was wiped out. This lets a propagator chain the
+    /// operations with `?`.
+    pub fn into_result(self) -> Result<bool, Wipeout> {
+        match self {
+            DomainEvent::Empty    => Err(Wipeout),
+            DomainEvent::NoChange => Ok(false),
+            _                     => Ok(true)
+        }
+    }
+
+    /// Returns the event which describes a modification that left `size`
+    /// values in the domain
+    pub(crate) fn change(size: usize) -> DomainEvent {
+        match size {
+            0 => DomainEvent::Empty,
+            1 => DomainEvent::Fixed,
+            _ => DomainEvent::Changed
+        }
+    }
+}
+
+/// This structure implements a set of integers in the range `0..capacity`
+/// from which values can be removed and which are automagically restored upon
+/// backtrack.
+///
+/// # Implementation Notes
+/// The structure relies on the classic sparse set trick: the `values` array
+/// holds a permutation of `0..capacity` and `indices` is its inverse. The first
+/// `size` items of `values` are the present elements. Removing a value swaps it
+/// with the last present element and decrements `size`. Since the swaps keep the
+/// removed values beyond the `size` boundary, only `size` needs to be trailed:
+/// restoring it restores the membership of all the values that were removed at
+/// deeper levels (in some order).
+///
+/// The set also caches a lower bound of its min and an upper bound of its max.
+/// Removals never touch these caches: `min()` and `max()` lazily walk from the
+/// cached bound to the actual extremum and trail the value they found. Since
+/// removals can only push the extrema inwards and a pop restores the caches
+/// along with the size, the caches remain valid bounds at all times.
+///
+/// # Memory
+/// Each set owns its own `values` and `indices` vectors. When a model holds
+/// thousands of domains, prefer an `ArenaSparseSet` whose vectors are windows
+/// of one arena supplied by the caller, or a `DomainStore` which lays out all
+/// its domains in one contiguous allocation (and shares one handle to the trail
+/// among all of them).
+pub struct ReversibleSparseSet<'a> {
+    values : Vec<usize>,
+    indices: Vec<usize>,
+    size   : Reversible<'a, usize>,
+    lo     : Reversible<'a, usize>,
+    hi     : Reversible<'a, usize>
+}
+
+impl<'a> ReversibleSparseSet<'a> {
+    /// Creates a new sparse set associated with the given trail and initially
+    /// containing all the values `0..capacity`.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, capacity: usize) -> ReversibleSparseSet<'a> {
+        ReversibleSparseSet {
+            values : (0..capacity).collect(),
+            indices: (0..capacity).collect(),
+            size   : Reversible::new(Rc::clone(&trail), capacity),
+            lo     : Reversible::new(Rc::clone(&trail), 0),
+            hi     : Reversible::new(trail, capacity.saturating_sub(1))
+        }
+    }
+
+    /// Returns the number of values this set can hold (`0..capacity`)
+    pub fn capacity(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the number of values currently present in the set
+    pub fn size(&self) -> usize {
+        self.size.get_value()
+    }
+
+    /// Returns true iff there is no value in the set
+    pub fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
+
+    /// Returns true iff v is present in the set
+    pub fn contains(&self, v: usize) -> bool {
+        v < self.capacity() && self.indices[v] < self.size()
+    }
+
+    /// Returns the smallest value present in the set (None when it is empty).
+    ///
+    /// # Cost
+    /// O(1) unless the cached min has been removed since it was last looked
+    /// up; in which case the gap up to the next present value is scanned (once:
+    /// the new min is trailed and cached). Along a branch of the search the
+    /// scans are thus amortized over the removals.
+    ///
+    /// # Side Effect
+    /// Although it only takes `&self`, this posts an entry on the trail when it
+    /// moves the cached min. This is skipped while the trail is being restored
+    /// (e.g. when called from a post-restore hook or a level finalizer): the
+    /// min is then computed but not cached, hence that call never panics.
+    pub fn min(&self) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut m = self.lo.get_value();
+        while !self.contains(m) {
+            m += 1;
+        }
+        if !self.lo.is_restoring() {
+            self.lo.set(m);
+        }
+        Some(m)
+    }
+
+    /// Returns the largest value present in the set (None when it is empty).
+    /// The cost and the side effect are the same as those of `min()`.
+    pub fn max(&self) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut m = self.hi.get_value();
+        while !self.contains(m) {
+            m -= 1;
+        }
+        if !self.hi.is_restoring() {
+            self.hi.set(m);
+        }
+        Some(m)
+    }
+
+    /// Removes v from the set. Returns `NoChange` if v was not present in the
+    /// set, `Empty` if v was its last value, `Fixed` if a single value remains,
+    /// and `Changed` otherwise.
+    pub fn remove(&mut self, v: usize) -> DomainEvent {
+        if !self.contains(v) {
+            return DomainEvent::NoChange;
+        }
+        let last = self.size() - 1;
+        self.swap(self.indices[v], last);
+        self.size.set_value(last);
+        DomainEvent::change(last)
+    }
+
+    /// Removes all values from the set
+    pub fn remove_all(&mut self) {
+        self.size.set_value(0);
+    }
+
+    /// Removes all values but v from the set. If v was not present in the set,
+    /// the set becomes empty.
+    pub fn remove_all_but(&mut self, v: usize) {
+        if self.contains(v) {
+            self.swap(self.indices[v], 0);
+            self.size.set_value(1);
+        } else {
+            self.remove_all();
+        }
+    }
+
+    /// Removes all the values that do not appear in `keep` from the set. The
+    /// kept values are swapped to the front of the set and its size is changed
+    /// only once (thus posting at most one entry on the trail). Retaining an
+    /// empty slice empties the set.
+    pub fn retain(&mut self, keep: &[usize]) {
+        let size = self.size();
+        let mut kept = 0;
+        for &v in keep {
+            if v < self.capacity() && self.indices[v] >= kept && self.indices[v] < size {
+                self.swap(self.indices[v], kept);
+                kept += 1;
+            }
+        }
+        self.size.set_value(kept);
+    }
+
+    /// Removes all the given values from the set. The size of the set is only
+    /// changed once (thus posting at most one entry on the trail).
+    pub fn remove_values(&mut self, gone: &[usize]) {
+        let mut size = self.size();
+        for &v in gone {
+            if v < self.capacity() && self.indices[v] < size {
+                size -= 1;
+                self.swap(self.indices[v], size);
+            }
+        }
+        self.size.set_value(size);
+    }
+
+    /// Returns the present elements of the set (in no particular order)
+    pub fn as_slice(&self) -> &[usize] {
+        &self.values[..self.size()]
+    }
+
+    /// Iterates over the present elements of the set (in no particular order).
+    ///
+    /// # Note
+    /// The iterator borrows the set, so the borrow checker prevents you from
+    /// removing values while iterating. If that is what you need, iterate over
+    /// the vector returned by `values()` instead. Also note that the iterator
+    /// snapshots the size of the set upon creation: it yields the values that
+    /// were present at that time, even if the trail is popped meanwhile.
+    pub fn iter(&self) -> SparseSetIter<'_> {
+        SparseSetIter { inner: self.as_slice().iter() }
+    }
+
+    /// Returns an owned copy of the present elements of the set (in no
+    /// particular order).
+    pub fn values(&self) -> Vec<usize> {
+        self.as_slice().to_vec()
+    }
+
+    /// Iterates over the present elements of the set in increasing order. The
+    /// order only depends on the contents of the set, not on the history of
+    /// removals (and backtracks) which produced it, which makes it the one to
+    /// use for reproducible branching decisions.
+    ///
+    /// # Cost
+    /// Unlike `iter()` which is free, this copies the present elements into a
+    /// temporary buffer and sorts it: O(size log size) time and O(size) space.
+    pub fn iter_sorted(&self) -> vec::IntoIter<usize> {
+        let mut present = self.values();
+        present.sort_unstable();
+        present.into_iter()
+    }
+
+    /// Iterates over the values which have been removed from the set since
+    /// its size was `marker` (in no particular order). A propagator typically
+    /// saves `size()` as its marker whenever it runs, and consumes the delta
+    /// of the set the next time it is woken up.
+    ///
+    /// # Note
+    /// The delta is only meaningful as long as the level at which the marker
+    /// was captured has not been popped. When the set has regained values
+    /// since then (so that the marker is no greater than the current size),
+    /// the delta is empty.
+    pub fn delta_since(&self, marker: usize) -> SparseSetIter<'_> {
+        let size = self.size();
+        let end  = marker.clamp(size, self.capacity());
+        SparseSetIter { inner: self.values[size..end].iter() }
+    }
+
+    /// Reorders the present elements of the set so that they appear in the
+    /// order of `perm` (e.g. in `as_slice()`). This is a change of
+    /// representation which does not change the membership of any value: it
+    /// posts nothing on the trail. The values which are not present keep their
+    /// positions beyond the size boundary, hence the set is still restored
+    /// correctly upon backtrack (even though a pop does not undo the reorder).
+    ///
+    /// # Panics
+    /// When `perm` is not a permutation of the present elements of the set.
+    pub fn reorder(&mut self, perm: &[usize]) {
+        let size = self.size();
+        assert_eq!(perm.len(), size, "perm must hold each present element exactly once");
+        let mut seen = vec![false; size];
+        for &v in perm {
+            assert!(self.contains(v) && !seen[self.indices[v]], "perm must hold each present element exactly once");
+            seen[self.indices[v]] = true;
+        }
+        for (i, &v) in perm.iter().enumerate() {
+            self.values[i]  = v;
+            self.indices[v] = i;
+        }
+    }
+
+    /// Swaps the items at positions i and j of the values array
+    fn swap(&mut self, i: usize, j: usize) {
+        let vi = self.values[i];
+        let vj = self.values[j];
+        self.values[i]  = vj;
+        self.values[j]  = vi;
+        self.indices[vi]= j;
+        self.indices[vj]= i;
+    }
+}
+
+#[cfg(feature = "std")]
+impl ReversibleSparseSet<'static> {
+    /// Creates a new sparse set associated with the ambient trail of the
+    /// current thread (see `Trail::install`) and initially containing all the
+    /// values `0..capacity`.
+    ///
+    /// # Panics
+    /// When no trail is installed on the current thread.
+    pub fn new_ambient(capacity: usize) -> ReversibleSparseSet<'static> {
+        ReversibleSparseSet::new(Trail::expect_ambient("ReversibleSparseSet"), capacity)
+    }
+}
+
+/// Displays the present elements of the set, sorted: e.g. `{0, 3, 4}`
+impl<'a> fmt::Display for ReversibleSparseSet<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, v) in self.iter_sorted().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", v)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// Shows the present elements along with the internals of the sparse set
+impl<'a> fmt::Debug for ReversibleSparseSet<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReversibleSparseSet")
+            .field("present", &format_args!("{}", self))
+            .field("size", &self.size())
+            .field("values", &self.values)
+            .field("indices", &self.indices)
+            .finish()
+    }
+}
+
+/// The iterator over the present elements of a reversible sparse set
+pub struct SparseSetIter<'b> {
+    pub(crate) inner: slice::Iter<'b, usize>
+}
+
+impl<'b> Iterator for SparseSetIter<'b> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        self.inner.next().cloned()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b ReversibleSparseSet<'a> {
+    type Item     = usize;
+    type IntoIter = SparseSetIter<'b>;
+
+    fn into_iter(self) -> SparseSetIter<'b> {
+        self.iter()
+    }
+}
+
+/// The (de)serialization of a sparse set only deals with its *current* logical
+/// contents: its capacity and the values which are present. The trail history
+/// cannot be serialized, hence it is *not* preserved. A deserialized set is
+/// rebuilt against a freshly provided trail (see `SparseSetSeed`), at level 0.
+#[cfg(feature = "serde")]
+mod serialization {
+    use alloc::vec::Vec;
+    use core::fmt;
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+    use serde::de::DeserializeSeed;
+
+    use ::context::Trail;
+    use super::ReversibleSparseSet;
+
+    #[derive(Serialize)]
+    struct SparseSetRef {
+        capacity: usize,
+        values  : Vec<usize>
+    }
+
+    #[derive(Deserialize)]
+    struct SparseSetData {
+        capacity: usize,
+        values  : Vec<usize>
+    }
+
+    impl<'a> Serialize for ReversibleSparseSet<'a> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut values = self.as_slice().to_vec();
+            values.sort_unstable();
+            SparseSetRef { capacity: self.capacity(), values }.serialize(serializer)
+        }
+    }
+
+    /// The seed to deserialize a sparse set against the given trail
+    pub struct SparseSetSeed<'a> {
+        trail: Rc<RefCell<Trail<'a>>>
+    }
+
+    impl<'a> SparseSetSeed<'a> {
+        /// Creates a seed which binds the deserialized sparse sets to the
+        /// given trail
+        pub fn new(trail: Rc<RefCell<Trail<'a>>>) -> SparseSetSeed<'a> {
+            SparseSetSeed { trail }
+        }
+    }
+
+    impl<'de, 'a> DeserializeSeed<'de> for SparseSetSeed<'a> {
+        type Value = ReversibleSparseSet<'a>;
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+            use serde::de::Error;
+
+            let data = SparseSetData::deserialize(deserializer)?;
+            let mut present = vec![false; data.capacity];
+            for &v in data.values.iter() {
+                if v >= data.capacity {
+                    return Err(D::Error::custom(ValueOutOfBounds(v, data.capacity)));
+                }
+                present[v] = true;
+            }
+
+            let mut set = ReversibleSparseSet::new(self.trail, data.capacity);
+            for (v, &keep) in present.iter().enumerate() {
+                if !keep {
+                    set.remove(v);
+                }
+            }
+            Ok(set)
+        }
+    }
+
+    struct ValueOutOfBounds(usize, usize);
+    impl fmt::Display for ValueOutOfBounds {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "value {} is out of the capacity {} of the set", self.0, self.1)
+        }
+    }
+}
+#[cfg(feature = "serde")]
+pub use self::serialization::SparseSetSeed;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    extern crate rand;
+    use self::rand::Rng;
+
+    #[test]
+    fn test_ok() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 10);
+        assert_eq!(set.size(), 10);
+        assert!(set.contains(9));
+        assert!(!set.contains(10));
+
+        trail.borrow_mut().push();
+        assert_eq!(set.remove(3), DomainEvent::Changed);
+        assert_eq!(set.remove(3), DomainEvent::NoChange);
+        assert_eq!(set.remove(0), DomainEvent::Changed);
+        assert_eq!(set.size(), 8);
+        assert!(!set.contains(3));
+        assert!(!set.contains(0));
+
+        trail.borrow_mut().push();
+        set.remove_all_but(7);
+        assert_eq!(set.as_slice(), &[7]);
+
+        trail.borrow_mut().push();
+        set.remove_all();
+        assert!(set.is_empty());
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.as_slice(), &[7]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.size(), 8);
+        assert!(set.contains(5));
+        assert!(!set.contains(3));
+
+        trail.borrow_mut().pop();
+        let mut values = set.as_slice().to_vec();
+        values.sort();
+        assert_eq!(values, (0..10).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn test_remove_events() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 3);
+
+        trail.borrow_mut().push();
+        assert_eq!(set.remove(42), DomainEvent::NoChange);
+        assert_eq!(set.remove(0), DomainEvent::Changed);
+        assert_eq!(set.remove(2), DomainEvent::Fixed);
+        assert_eq!(set.remove(2), DomainEvent::NoChange);
+        let event = set.remove(1);
+        assert_eq!(event, DomainEvent::Empty);
+        assert!(event.is_failure());
+        assert!(set.is_empty());
+        assert!(!set.remove(1).is_change());
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.size(), 3);
+    }
+
+    #[test]
+    fn test_bulk_removals() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 8);
+
+        trail.borrow_mut().push();
+        set.retain(&[6, 2, 2, 42, 5]);
+        let mut values = set.values();
+        values.sort();
+        assert_eq!(values, vec![2, 5, 6]);
+
+        trail.borrow_mut().push();
+        set.remove_values(&[5, 5, 0, 6]);
+        assert_eq!(set.values(), vec![2]);
+
+        trail.borrow_mut().push();
+        set.retain(&[]);
+        assert_eq!(set.size(), 0);
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.values(), vec![2]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.size(), 3);
+        assert!(set.contains(5));
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.size(), 8);
+        assert!((0..8).all(|v| set.contains(v)));
+    }
+
+    #[test]
+    fn test_iteration() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 6);
+
+        trail.borrow_mut().push();
+        set.remove(1);
+        set.remove(4);
+
+        let mut seen = vec![];
+        for v in &set {
+            seen.push(v);
+        }
+        seen.sort();
+        assert_eq!(seen, vec![0, 2, 3, 5]);
+
+        let mut collected = set.iter().collect::<Vec<usize>>();
+        collected.sort();
+        assert_eq!(collected, seen);
+
+        // removing while iterating goes through the owned values
+        for v in set.values() {
+            if v % 2 == 0 {
+                set.remove(v);
+            }
+        }
+        let mut remaining = set.values();
+        remaining.sort();
+        assert_eq!(remaining, vec![3, 5]);
+
+        // the iterator snapshots the size upon creation
+        let it = set.iter();
+        trail.borrow_mut().pop();
+        assert_eq!(it.count(), 2);
+        assert_eq!(set.iter().count(), 6);
+    }
+
+    #[test]
+    fn test_formatting() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 5);
+        assert_eq!(format!("{}", set), "{0, 1, 2, 3, 4}");
+
+        trail.borrow_mut().push();
+        set.remove(1);
+        set.remove(2);
+        assert_eq!(format!("{}", set), "{0, 3, 4}");
+        assert_eq!(format!("{:?}", set),
+            "ReversibleSparseSet { present: {0, 3, 4}, size: 3, values: [0, 4, 3, 2, 1], indices: [0, 4, 3, 2, 1] }");
+
+        set.remove_all();
+        assert_eq!(format!("{}", set), "{}");
+
+        trail.borrow_mut().pop();
+        assert_eq!(format!("{}", set), "{0, 1, 2, 3, 4}");
+    }
+
+    #[test]
+    fn test_iter_sorted_ignores_history() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut a = ReversibleSparseSet::new(Rc::clone(&trail), 8);
+        let mut b = ReversibleSparseSet::new(Rc::clone(&trail), 8);
+
+        trail.borrow_mut().push();
+        for &v in &[0, 3, 6, 1] {
+            a.remove(v);
+        }
+        trail.borrow_mut().push();
+        b.remove_all_but(4);
+        trail.borrow_mut().pop();
+        for &v in &[6, 1, 5, 0, 3] {
+            b.remove(v);
+        }
+        b.retain(&[7, 2, 4, 5]);
+        a.remove(5);
+
+        assert_ne!(a.as_slice(), b.as_slice());
+        assert_eq!(a.iter_sorted().collect::<Vec<usize>>(), vec![2, 4, 7]);
+        assert_eq!(b.iter_sorted().collect::<Vec<usize>>(), vec![2, 4, 7]);
+    }
+
+    #[test]
+    fn test_reorder() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 6);
+
+        trail.borrow_mut().push();
+        set.remove(1);
+        set.remove(4);
+        let before : Vec<bool> = (0..6).map(|v| set.contains(v)).collect();
+        let len = trail.borrow().len();
+
+        set.reorder(&[5, 3, 0, 2]);
+        assert_eq!(set.as_slice(), &[5, 3, 0, 2]);
+        assert_eq!((0..6).map(|v| set.contains(v)).collect::<Vec<bool>>(), before);
+        assert_eq!(trail.borrow().len(), len);
+
+        set.remove_all_but(0);
+        assert_eq!(set.as_slice(), &[0]);
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.size(), 6);
+        assert!((0..6).all(|v| set.contains(v)));
+    }
+
+    #[test]
+    #[should_panic(expected = "perm must hold each present element exactly once")]
+    fn test_reorder_rejects_absent_values() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 3);
+        trail.borrow_mut().push();
+        set.remove(2);
+        set.reorder(&[2, 0]);
+    }
+
+    #[test]
+    fn test_min_max_are_restored_upon_pop() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 6);
+        assert_eq!((set.min(), set.max()), (Some(0), Some(5)));
+
+        // removing the current min
+        trail.borrow_mut().push();
+        set.remove(0);
+        set.remove(1);
+        assert_eq!((set.min(), set.max()), (Some(2), Some(5)));
+        trail.borrow_mut().pop();
+        assert_eq!((set.min(), set.max()), (Some(0), Some(5)));
+
+        // removing the current max
+        trail.borrow_mut().push();
+        set.remove(5);
+        assert_eq!((set.min(), set.max()), (Some(0), Some(4)));
+        trail.borrow_mut().push();
+        set.remove(4);
+        set.remove(3);
+        assert_eq!(set.max(), Some(2));
+        trail.borrow_mut().pop();
+        assert_eq!(set.max(), Some(4));
+        trail.borrow_mut().pop();
+        assert_eq!((set.min(), set.max()), (Some(0), Some(5)));
+
+        // removing interior values leaves the bounds untouched
+        trail.borrow_mut().push();
+        set.remove_values(&[1, 2, 4]);
+        assert_eq!((set.min(), set.max()), (Some(0), Some(5)));
+        set.remove_all_but(3);
+        assert_eq!((set.min(), set.max()), (Some(3), Some(3)));
+        set.remove_all();
+        assert_eq!((set.min(), set.max()), (None, None));
+        trail.borrow_mut().pop();
+        assert_eq!((set.min(), set.max()), (Some(0), Some(5)));
+
+        let empty = ReversibleSparseSet::new(Rc::clone(&trail), 0);
+        assert_eq!((empty.min(), empty.max()), (None, None));
+    }
+
+    #[test]
+    fn test_min_max_can_be_read_while_restoring() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let set = Rc::new(RefCell::new(ReversibleSparseSet::new(Rc::clone(&trail), 6)));
+        let seen = Rc::new(RefCell::new(vec![]));
+
+        // the cached bounds are stale when the hook and the finalizer run
+        trail.borrow_mut().push();
+        set.borrow_mut().remove(0);
+        set.borrow_mut().remove(5);
+        trail.borrow_mut().push();
+        let (s, v) = (Rc::clone(&set), Rc::clone(&seen));
+        trail.borrow_mut().push_post_restore(Box::new(move || {
+            let s = s.borrow();
+            v.borrow_mut().push((s.min(), s.max()));
+        }));
+        let (s, v) = (Rc::clone(&set), Rc::clone(&seen));
+        trail.borrow_mut().register_level_finalizer(Box::new(move |_| {
+            let s = s.borrow();
+            v.borrow_mut().push((s.min(), s.max()));
+        }));
+        trail.borrow_mut().pop();
+        assert_eq!(*seen.borrow(), vec![(Some(1), Some(4)), (Some(1), Some(4))]);
+
+        // outside of the restoration, the bounds are cached (and restored) as usual
+        assert_eq!((set.borrow().min(), set.borrow().max()), (Some(1), Some(4)));
+        trail.borrow_mut().pop();
+        assert_eq!((set.borrow().min(), set.borrow().max()), (Some(0), Some(5)));
+        assert_eq!(seen.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_min_max_match_brute_force() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 20);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            match rng.gen_range(0, 4) {
+                0 => trail.borrow_mut().push(),
+                1 => if trail.borrow().level() > 0 { trail.borrow_mut().pop() },
+                _ => { set.remove(rng.gen_range(0, 20)); }
+            }
+            assert_eq!(set.min(), set.iter().min());
+            assert_eq!(set.max(), set.iter().max());
+        }
+    }
+
+    #[test]
+    fn test_delta_since() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 30);
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..20 {
+            trail.borrow_mut().push();
+            let marker = set.size();
+            let before = set.values();
+            match rng.gen_range(0, 4) {
+                0 => { let keep : Vec<usize> = (0..10).map(|_| rng.gen_range(0, 30)).collect(); set.retain(&keep); },
+                1 => { let gone : Vec<usize> = (0..5).map(|_| rng.gen_range(0, 30)).collect(); set.remove_values(&gone); },
+                _ => for _ in 0..3 { set.remove(rng.gen_range(0, 30)); }
+            }
+
+            let mut delta : Vec<usize> = set.delta_since(marker).collect();
+            delta.sort_unstable();
+            let mut brute : Vec<usize> = before.into_iter().filter(|&v| !set.contains(v)).collect();
+            brute.sort_unstable();
+            assert_eq!(delta, brute);
+            assert_eq!(set.delta_since(set.size()).count(), 0);
+
+            if rng.gen_range(0, 3) == 0 {
+                trail.borrow_mut().pop();
+                // the set has regained values since the marker was captured
+                assert_eq!(set.delta_since(marker).count(), 0);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod test_serde {
+    extern crate serde_json;
+    use serde::de::DeserializeSeed;
+    use super::*;
+
+    #[test]
+    fn test_round_trip_keeps_current_contents_only() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSet::new(Rc::clone(&trail), 5);
+        trail.borrow_mut().push();
+        set.remove(3);
+        set.remove(0);
+
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, r#"{"capacity":5,"values":[1,2,4]}"#);
+
+        let fresh = Rc::new(RefCell::new(Trail::new()));
+        let seed  = SparseSetSeed::new(Rc::clone(&fresh));
+        let mut copy = seed.deserialize(&mut serde_json::Deserializer::from_str(&json)).unwrap();
+        assert_eq!(copy.size(), 3);
+        assert!(!copy.contains(3));
+
+        // the copy is at level 0 of its own trail and trails normally
+        fresh.borrow_mut().push();
+        copy.remove(1);
+        fresh.borrow_mut().pop();
+        assert_eq!(copy.size(), 3);
+        assert!(!copy.contains(0));
+    }
+
+    #[test]
+    fn test_rejects_out_of_bounds_values() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let seed  = SparseSetSeed::new(trail);
+        let json  = r#"{"capacity":2,"values":[1,2]}"#;
+        assert!(seed.deserialize(&mut serde_json::Deserializer::from_str(json)).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! This module provides a reversible sparse set over arbitrary (hashable) keys.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::hash::Hash;
+use std::collections::HashMap;
+
+use ::context::Trail;
+use ::reversible::{ReversibleSparseSet, SparseSetIter, DomainEvent};
+
+/// This structure implements a set of arbitrary keys supporting the O(1)
+/// removal of keys and their restoration upon backtrack. It behaves exactly
+/// like a `ReversibleSparseSet`, except that its universe is a given set of
+/// keys rather than the dense range `0..capacity`.
+///
+/// # Performance
+/// Each key is mapped to a dense index through a `HashMap`, hence every lookup
+/// costs one hash computation on top of the operation of the underlying sparse
+/// set. When your keys happen to be the dense range `0..n`, stick with the
+/// `ReversibleSparseSet` which is noticeably faster.
+pub struct ReversibleSparseSetMap<'a, K>
+    where K: Copy + Eq + Hash {
+    keys   : Vec<K>,
+    index  : HashMap<K, usize>,
+    present: ReversibleSparseSet<'a>
+}
+
+impl<'a, K> ReversibleSparseSetMap<'a, K>
+    where K: Copy + Eq + Hash {
+    /// Creates a new set associated with the given trail and initially
+    /// containing all the given keys (the duplicate keys are ignored).
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, keys: &[K]) -> ReversibleSparseSetMap<'a, K> {
+        let mut index = HashMap::with_capacity(keys.len());
+        let mut uniq  = Vec::with_capacity(keys.len());
+        for &k in keys {
+            index.entry(k).or_insert_with(|| { uniq.push(k); uniq.len() - 1 });
+        }
+        let present = ReversibleSparseSet::new(trail, uniq.len());
+        ReversibleSparseSetMap { keys: uniq, index, present }
+    }
+
+    /// Returns the number of keys this set can hold
+    pub fn capacity(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns the number of keys currently present in the set
+    pub fn size(&self) -> usize {
+        self.present.size()
+    }
+
+    /// Returns true iff there is no key in the set
+    pub fn is_empty(&self) -> bool {
+        self.present.is_empty()
+    }
+
+    /// Returns true iff k is present in the set
+    pub fn contains(&self, k: K) -> bool {
+        self.index.get(&k).is_some_and(|&i| self.present.contains(i))
+    }
+
+    /// Removes k from the set. The returned event follows the same rules as
+    /// that of `ReversibleSparseSet::remove`.
+    pub fn remove(&mut self, k: K) -> DomainEvent {
+        match self.index.get(&k) {
+            Some(&i) => self.present.remove(i),
+            None     => DomainEvent::NoChange
+        }
+    }
+
+    /// Removes all keys from the set
+    pub fn remove_all(&mut self) {
+        self.present.remove_all()
+    }
+
+    /// Removes all keys but k from the set. If k was not present in the set,
+    /// the set becomes empty.
+    pub fn remove_all_but(&mut self, k: K) {
+        match self.index.get(&k) {
+            Some(&i) => self.present.remove_all_but(i),
+            None     => self.present.remove_all()
+        }
+    }
+
+    /// Iterates over the present keys of the set (in no particular order)
+    pub fn iter(&self) -> SparseSetMapIter<'_, K> {
+        SparseSetMapIter { keys: &self.keys, inner: self.present.iter() }
+    }
+}
+
+/// The iterator over the present keys of a reversible sparse set map
+pub struct SparseSetMapIter<'b, K: 'b> {
+    keys : &'b [K],
+    inner: SparseSetIter<'b>
+}
+
+impl<'b, K: Copy> Iterator for SparseSetMapIter<'b, K> {
+    type Item = K;
+
+    fn next(&mut self) -> Option<K> {
+        let keys = self.keys;
+        self.inner.next().map(|i| keys[i])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn content(set: &ReversibleSparseSetMap<u32>) -> Vec<u32> {
+        let mut v : Vec<u32> = set.iter().collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_sparse_keys() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut set = ReversibleSparseSetMap::new(Rc::clone(&trail), &[1000, 7, 42_000, 7, 13]);
+        assert_eq!(set.capacity(), 4);
+        assert_eq!(content(&set), vec![7, 13, 1000, 42_000]);
+        assert!(!set.contains(8));
+
+        trail.borrow_mut().push();
+        assert_eq!(set.remove(7), DomainEvent::Changed);
+        assert_eq!(set.remove(7), DomainEvent::NoChange);
+        assert_eq!(set.remove(8), DomainEvent::NoChange);
+        assert_eq!(set.remove(1000), DomainEvent::Changed);
+        assert_eq!(set.remove(13), DomainEvent::Fixed);
+        assert_eq!(content(&set), vec![42_000]);
+
+        trail.borrow_mut().push();
+        set.remove_all_but(5);
+        assert!(set.is_empty());
+
+        trail.borrow_mut().pop();
+        assert_eq!(set.size(), 1);
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&set), vec![7, 13, 1000, 42_000]);
+        set.remove_all_but(13);
+        assert_eq!(content(&set), vec![13]);
+    }
+}
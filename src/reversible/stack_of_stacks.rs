@@ -0,0 +1,172 @@
+//! This module provides a reversible stack per key, all backed by one vector.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// The head of an empty stack
+const EMPTY: usize = usize::MAX;
+
+/// One item of one of the stacks
+struct Node<T> {
+    value: T,
+    /// The position of the item below this one in the same stack (or EMPTY)
+    below: usize,
+    /// The number of items of the stack up to (and including) this one
+    depth: usize
+}
+
+/// This structure implements one stack per key in `0..n_keys` (e.g. the
+/// support lists of the values of a domain). All the stacks can grow and shrink
+/// in O(1) and are restored upon backtrack.
+///
+/// # Implementation Notes
+/// The items of all the stacks are stored in one single backing vector: each
+/// item points to the one below it in its stack, and the position of the top
+/// of each stack is a reversible. Pushing appends the item to the backing
+/// vector; popping merely moves the head of the stack down. The items which
+/// are popped stay in the backing vector until the level in which they were
+/// pushed is popped, at which point the number of used items (which is
+/// reversible as well) is restored and the space gets reused.
+pub struct ReversibleStackOfStacks<'a, T: Copy> {
+    nodes: Vec<Node<T>>,
+    used : Reversible<'a, usize>,
+    heads: Vec<Reversible<'a, usize>>
+}
+
+impl<'a, T: Copy> ReversibleStackOfStacks<'a, T> {
+    /// Creates n_keys empty stacks associated with the given trail
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, n_keys: usize) -> ReversibleStackOfStacks<'a, T> {
+        ReversibleStackOfStacks {
+            nodes: vec![],
+            used : Reversible::new(Rc::clone(&trail), 0),
+            heads: Reversible::filled(&trail, n_keys, EMPTY)
+        }
+    }
+
+    /// Returns the number of keys (hence of stacks)
+    pub fn n_keys(&self) -> usize {
+        self.heads.len()
+    }
+
+    /// Returns the number of items of the stack of the given key
+    pub fn len(&self, key: usize) -> usize {
+        match self.heads[key].get_value() {
+            EMPTY => 0,
+            head  => self.nodes[head].depth
+        }
+    }
+
+    /// Returns true iff the stack of the given key is empty
+    pub fn is_empty(&self, key: usize) -> bool {
+        self.heads[key].get_value() == EMPTY
+    }
+
+    /// Returns the item on top of the stack of the given key (None when it is
+    /// empty)
+    pub fn top(&self, key: usize) -> Option<T> {
+        match self.heads[key].get_value() {
+            EMPTY => None,
+            head  => Some(self.nodes[head].value)
+        }
+    }
+
+    /// Pushes v on top of the stack of the given key
+    pub fn push(&mut self, key: usize, v: T) {
+        let used  = self.used.get_value();
+        let below = self.heads[key].get_value();
+        let depth = self.len(key) + 1;
+        self.nodes.truncate(used);
+        self.nodes.push(Node { value: v, below, depth });
+        self.used.set_value(used + 1);
+        self.heads[key].set_value(used);
+    }
+
+    /// Removes the item on top of the stack of the given key and returns it
+    /// (None when the stack is empty)
+    pub fn pop(&mut self, key: usize) -> Option<T> {
+        match self.heads[key].get_value() {
+            EMPTY => None,
+            head  => {
+                self.heads[key].set_value(self.nodes[head].below);
+                Some(self.nodes[head].value)
+            }
+        }
+    }
+
+    /// Iterates over the items of the stack of the given key, from the top
+    /// to the bottom.
+    pub fn iter(&self, key: usize) -> StackIter<'_, T> {
+        StackIter { nodes: &self.nodes, current: self.heads[key].get_value() }
+    }
+}
+
+/// The iterator over the items of one of the stacks, from the top to the bottom
+pub struct StackIter<'b, T: 'b> {
+    nodes  : &'b [Node<T>],
+    current: usize
+}
+
+impl<'b, T: Copy> Iterator for StackIter<'b, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.current == EMPTY {
+            return None;
+        }
+        let node = &self.nodes[self.current];
+        self.current = node.below;
+        Some(node.value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn content(stacks: &ReversibleStackOfStacks<char>, key: usize) -> Vec<char> {
+        stacks.iter(key).collect()
+    }
+
+    #[test]
+    fn test_stacks_are_restored_independently() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut stacks = ReversibleStackOfStacks::new(Rc::clone(&trail), 3);
+        stacks.push(0, 'a');
+        stacks.push(1, 'b');
+
+        trail.borrow_mut().push();
+        stacks.push(0, 'c');
+        stacks.push(2, 'd');
+        assert_eq!(stacks.pop(1), Some('b'));
+        assert_eq!(stacks.pop(1), None);
+        assert_eq!((stacks.len(0), stacks.len(1), stacks.len(2)), (2, 0, 1));
+
+        trail.borrow_mut().push();
+        stacks.push(1, 'e');
+        stacks.push(1, 'f');
+        assert_eq!(stacks.pop(0), Some('c'));
+        stacks.push(0, 'g');
+        assert_eq!(content(&stacks, 0), vec!['g', 'a']);
+        assert_eq!(content(&stacks, 1), vec!['f', 'e']);
+        assert_eq!(stacks.top(2), Some('d'));
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&stacks, 0), vec!['c', 'a']);
+        assert!(stacks.is_empty(1));
+        assert_eq!(content(&stacks, 2), vec!['d']);
+
+        // the space of the popped level is reused
+        stacks.push(1, 'h');
+        assert_eq!(content(&stacks, 1), vec!['h']);
+        assert_eq!(content(&stacks, 0), vec!['c', 'a']);
+
+        trail.borrow_mut().pop();
+        assert_eq!(content(&stacks, 0), vec!['a']);
+        assert_eq!(content(&stacks, 1), vec!['b']);
+        assert_eq!(stacks.top(2), None);
+        assert_eq!(stacks.n_keys(), 3);
+    }
+}
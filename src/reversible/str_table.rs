@@ -0,0 +1,127 @@
+//! This module provides the reversible state of a table constraint filtered
+//! with Simple Tabular Reduction (STR2-style).
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::{ReversibleSparseSet, SparseSetIter};
+
+/// This structure holds the tuples of a table constraint along with the set of
+/// those tuples which are still valid. The valid tuples are kept in a sparse
+/// set: invalidating a tuple swaps its position past the reversible limit, so
+/// only the limit is trailed (at most once per level). Upon backtrack, the
+/// previously invalidated tuples become valid again (in some order).
+pub struct ReversibleStrTable<'a> {
+    arity : usize,
+    tuples: Vec<Vec<isize>>,
+    valid : ReversibleSparseSet<'a>
+}
+
+impl<'a> ReversibleStrTable<'a> {
+    /// Creates a new table associated with the given trail. All the tuples
+    /// must have the same arity and are initially valid.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, tuples: Vec<Vec<isize>>) -> ReversibleStrTable<'a> {
+        let arity = tuples.first().map_or(0, |t| t.len());
+        assert!(tuples.iter().all(|t| t.len() == arity), "all tuples must have the same arity");
+        let valid = ReversibleSparseSet::new(trail, tuples.len());
+        ReversibleStrTable { arity, tuples, valid }
+    }
+
+    /// Returns the arity of the tuples of the table
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Returns the total number of tuples in the table (valid or not)
+    pub fn nb_tuples(&self) -> usize {
+        self.tuples.len()
+    }
+
+    /// Returns the number of tuples which are still valid
+    pub fn valid_count(&self) -> usize {
+        self.valid.size()
+    }
+
+    /// Returns true iff the given tuple is still valid
+    pub fn is_valid(&self, tuple_index: usize) -> bool {
+        self.valid.contains(tuple_index)
+    }
+
+    /// Returns the tuple at the given index
+    pub fn tuple(&self, tuple_index: usize) -> &[isize] {
+        &self.tuples[tuple_index]
+    }
+
+    /// Invalidates the given tuple. Returns true iff it was valid.
+    pub fn invalidate(&mut self, tuple_index: usize) -> bool {
+        self.valid.remove(tuple_index).is_change()
+    }
+
+    /// Iterates (in no particular order) over the tuples which are still valid.
+    /// Each item is a pair (tuple index, tuple).
+    pub fn iter<'b>(&'b self) -> StrTableIter<'a, 'b> {
+        StrTableIter { table: self, inner: self.valid.iter() }
+    }
+}
+
+/// The iterator over the valid tuples of a table
+pub struct StrTableIter<'a: 'b, 'b> {
+    table: &'b ReversibleStrTable<'a>,
+    inner: SparseSetIter<'b>
+}
+
+impl<'a, 'b> Iterator for StrTableIter<'a, 'b> {
+    type Item = (usize, &'b [isize]);
+
+    fn next(&mut self) -> Option<(usize, &'b [isize])> {
+        let table = self.table;
+        self.inner.next().map(|i| (i, table.tuple(i)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn valid(table: &ReversibleStrTable) -> Vec<usize> {
+        let mut v : Vec<usize> = table.iter().map(|(i, _)| i).collect();
+        v.sort();
+        v
+    }
+
+    #[test]
+    fn test_repeated_cycles() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let tuples = vec![vec![0, 0], vec![0, 1], vec![1, 0], vec![1, 1], vec![2, 2]];
+        let mut table = ReversibleStrTable::new(Rc::clone(&trail), tuples);
+        assert_eq!(table.arity(), 2);
+        assert_eq!(table.valid_count(), 5);
+
+        for _ in 0..3 {
+            trail.borrow_mut().push();
+            // x = 1
+            let stale : Vec<usize> = table.iter()
+                .filter(|&(_, t)| t[0] != 1)
+                .map(|(i, _)| i)
+                .collect();
+            for i in stale {
+                assert!(table.invalidate(i));
+            }
+            assert!(!table.invalidate(0));
+            assert_eq!(valid(&table), vec![2, 3]);
+
+            trail.borrow_mut().push();
+            table.invalidate(3);
+            assert!(!table.is_valid(3));
+            assert_eq!(table.iter().collect::<Vec<_>>(), vec![(2, &[1, 0][..])]);
+
+            trail.borrow_mut().pop();
+            assert_eq!(valid(&table), vec![2, 3]);
+
+            trail.borrow_mut().pop();
+            assert_eq!(valid(&table), vec![0, 1, 2, 3, 4]);
+            assert!(table.is_valid(4));
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! This module provides the thread-safe counterpart of `Reversible`.
+use alloc::boxed::Box;
+use std::sync::{Arc, Mutex};
+use core::fmt;
+
+use ::context::SyncTrail;
+
+/// This is the thread-safe flavor of the reversible object abstraction. It
+/// behaves exactly like a `Reversible` except that it is bound to a `SyncTrail`
+/// and stores its value in an `Arc<Mutex<T>>`, which makes it `Send + Sync`.
+///
+/// # Note
+/// Each operation acquires (at least) one lock. This type is thus meant for
+/// coarse-grained parallelism where the reversibles must cross thread boundaries;
+/// not for sharing one trail among several concurrently running workers.
+pub struct SyncReversible<'a, T>
+    where T: Copy + PartialEq + Send + 'a {
+    trail: SyncTrail<'a>,
+    clock: usize,
+    value: Arc<Mutex<T>>
+}
+
+impl<'a, T> SyncReversible<'a, T>
+    where T: Copy + PartialEq + Send + 'a {
+    /// Creates a new reversible object associated with the given trail and
+    /// initialized with the given value.
+    pub fn new(trail: SyncTrail<'a>, initial: T) -> SyncReversible<'a, T> {
+        let clock = trail.clock();
+        let value = Arc::new(Mutex::new(initial));
+        SyncReversible {
+            trail,
+            clock,
+            value
+        }
+    }
+
+    /// This private method takes care of posting an entry on the trail
+    /// so as to easily restore the current state.
+    fn trail(&mut self) {
+        let trail_time = self.trail.clock();
+
+        if trail_time != self.clock {
+            self.clock = trail_time;
+
+            let val = self.get_value();
+            let dst = Arc::clone(&self.value);
+            self.trail.push_on_trail(Box::new(move || *dst.lock().expect("the value lock was poisoned") = val));
+        }
+    }
+
+    /// Returns the current value of the reversible object
+    pub fn get_value(&self) -> T {
+        *self.value.lock().expect("the value lock was poisoned")
+    }
+
+    /// Changes the current value of the reversible object.
+    /// Returns the previous value (which equals v when nothing changed).
+    pub fn set_value(&mut self, v: T) -> T {
+        let old = self.get_value();
+        if v != old {
+            self.trail();
+            *self.value.lock().expect("the value lock was poisoned") = v;
+        }
+        old
+    }
+}
+
+impl<'a, T> fmt::Display for SyncReversible<'a, T>
+    where T: fmt::Display + Copy + PartialEq + Send + 'a {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SyncReversible({})", self.get_value())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_types_are_send_and_sync() {
+        assert_send_sync::<SyncTrail<'static>>();
+        assert_send_sync::<SyncReversible<'static, isize>>();
+    }
+
+    #[test]
+    fn test_ok() {
+        let trail = SyncTrail::new();
+        let mut a = SyncReversible::new(trail.clone(), 0);
+
+        trail.push();
+        a.set_value(1);
+        trail.push();
+        assert_eq!(a.set_value(2), 1);
+        assert_eq!(a.set_value(42), 2);
+        assert_eq!(trail.level(), 2);
+        assert_eq!(a.get_value(), 42);
+
+        trail.pop();
+        assert_eq!(a.get_value(), 1);
+        trail.pop();
+        assert_eq!(a.get_value(), 0);
+    }
+
+    #[test]
+    fn test_cross_thread() {
+        let workers : Vec<_> = (0..4).map(|i| {
+            let trail = SyncTrail::new();
+            let mut a = SyncReversible::new(trail.clone(), i);
+            thread::spawn(move || {
+                trail.push();
+                a.set_value(100 + i);
+                let inside = a.get_value();
+                trail.pop_all();
+                (inside, a.get_value())
+            })
+        }).collect();
+
+        for (i, worker) in workers.into_iter().enumerate() {
+            assert_eq!(worker.join().unwrap(), (100 + i, i));
+        }
+    }
+}
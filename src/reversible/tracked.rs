@@ -0,0 +1,131 @@
+//! This module provides a reversible cell which remembers its value at each of
+//! the levels of the trail.
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+
+/// This structure implements a reversible cell which remembers the value it had
+/// at every level still on the trail (see `value_at_level`), e.g. for debugging
+/// or for learning schemes.
+///
+/// # Implementation Notes
+/// The cell keeps a stack of `(level, value)` pairs: the last value it held at
+/// each level where it changed, sorted by level. The current value is that of
+/// the last pair. Changing the value at a level which has no pair yet pushes a
+/// new pair along with the trail entry that pops it. Hence, the memory grows
+/// with the number of levels where the cell actually changed, and each of these
+/// levels costs one single trail entry.
+pub struct TrackedReversible<'a, T: Copy + PartialEq + 'a> {
+    trail  : Rc<RefCell<Trail<'a>>>,
+    history: Rc<RefCell<Vec<(usize, T)>>>
+}
+
+impl<'a, T: Copy + PartialEq + 'a> TrackedReversible<'a, T> {
+    /// Creates a new cell associated with the given trail and initialized with
+    /// the given value.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, initial: T) -> TrackedReversible<'a, T> {
+        let level = trail.borrow().level();
+        TrackedReversible { trail, history: Rc::new(RefCell::new(vec![(level, initial)])) }
+    }
+
+    /// Returns the current value of the cell
+    pub fn get_value(&self) -> T {
+        self.history.borrow().last().unwrap().1
+    }
+
+    /// Changes the current value of the cell and returns the previous one
+    pub fn set_value(&mut self, v: T) -> T {
+        let old = self.get_value();
+        if v == old {
+            return old;
+        }
+        let mut trail = self.trail.borrow_mut();
+        let level = trail.level();
+        let mut history = self.history.borrow_mut();
+        // a cell which outlives the level at which it was created behaves as if
+        // it had been created at the current level
+        if history[0].0 > level {
+            history[0].0 = level;
+        }
+        if history.last().unwrap().0 == level {
+            history.last_mut().unwrap().1 = v;
+        } else {
+            history.push((level, v));
+            let dst = Rc::clone(&self.history);
+            trail.push_on_trail(Box::new(move || { dst.borrow_mut().pop(); }));
+        }
+        old
+    }
+
+    /// Returns the value this cell has at the given level, that is, the value
+    /// it had when `level + 1` was pushed (or its current value if `level` is
+    /// the current level). A level where the cell did not change inherits the
+    /// value of the closest earlier level. Returns None when the given level is
+    /// deeper than the current level of the trail. The levels which precede
+    /// the creation of the cell get its initial value.
+    pub fn value_at_level(&self, level: usize) -> Option<T> {
+        if level > self.trail.borrow().level() {
+            return None;
+        }
+        let history = self.history.borrow();
+        let pos = history.iter().rposition(|&(l, _)| l <= level).unwrap_or(0);
+        Some(history[pos].1)
+    }
+
+    /// Returns the number of levels at which the value of the cell is recorded
+    pub fn history_len(&self) -> usize {
+        self.history.borrow().len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_value_at_level() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = TrackedReversible::new(Rc::clone(&trail), 0);
+        x.set_value(1);
+
+        trail.borrow_mut().push();
+        x.set_value(2);
+        x.set_value(3);
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        x.set_value(4);
+        trail.borrow_mut().push();
+
+        assert_eq!(x.history_len(), 3);
+        assert_eq!((0..=4).map(|l| x.value_at_level(l).unwrap()).collect::<Vec<i32>>(), vec![1, 3, 3, 4, 4]);
+        assert_eq!(x.value_at_level(5), None);
+
+        trail.borrow_mut().pop();
+        trail.borrow_mut().pop();
+        assert_eq!((x.get_value(), x.history_len()), (3, 2));
+        assert_eq!(x.value_at_level(3), None);
+        x.set_value(5);
+        assert_eq!((x.value_at_level(1), x.value_at_level(2)), (Some(3), Some(5)));
+
+        trail.borrow_mut().pop_all();
+        assert_eq!((x.get_value(), x.history_len()), (1, 1));
+    }
+
+    #[test]
+    fn test_outliving_the_creation_level() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        let mut x = TrackedReversible::new(Rc::clone(&trail), 'a');
+        trail.borrow_mut().pop();
+        x.set_value('b');
+        trail.borrow_mut().push();
+        x.set_value('c');
+        assert_eq!((x.value_at_level(0), x.value_at_level(1), x.value_at_level(2)), (Some('b'), Some('b'), Some('c')));
+        trail.borrow_mut().pop();
+        assert_eq!(x.get_value(), 'b');
+    }
+}
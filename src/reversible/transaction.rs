@@ -0,0 +1,134 @@
+//! This module provides a way to group the writes to several reversibles so
+//! that they are restored by one single trail entry.
+use alloc::vec::Vec;
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// Runs f with a fresh transaction and posts the combined restoration entry of
+/// all the reversibles it modified on the trail (nothing is posted when none
+/// of them needed to be trailed). Returns the result of f.
+///
+/// This is not to be confused with `Trail::transaction`, which pushes a level
+/// to probe (and discard) some changes: this function does not change the
+/// level of the trail.
+///
+/// # Example
+/// ```
+/// use std::rc::Rc;
+/// use std::cell::RefCell;
+/// use trail::context::Trail;
+/// use trail::reversible::{Reversible, transaction};
+///
+/// let trail = Rc::new(RefCell::new(Trail::new()));
+/// let lo = Reversible::new(Rc::clone(&trail), 0);
+/// let hi = Reversible::new(Rc::clone(&trail), 9);
+///
+/// trail.borrow_mut().push();
+/// transaction(&trail, |tx| { tx.set(&lo, 3); tx.set(&hi, 5); });
+/// assert_eq!(trail.borrow().len(), 1);
+/// trail.borrow_mut().pop();
+/// assert_eq!((lo.get_value(), hi.get_value()), (0, 9));
+/// ```
+pub fn transaction<'a, F, R>(trail: &Rc<RefCell<Trail<'a>>>, f: F) -> R
+    where F: FnOnce(&mut Transaction<'a>) -> R {
+    let mut tx = Transaction { undo: vec![] };
+    let result = f(&mut tx);
+    if !tx.undo.is_empty() {
+        let mut undo = tx.undo;
+        trail.borrow_mut().push_on_trail(Box::new(move || {
+            for entry in undo.iter_mut().rev() {
+                entry();
+            }
+        }));
+    }
+    result
+}
+
+/// The writes of a transaction (see `transaction`). Each reversible written
+/// through the transaction is trailed as usual (at most once per level, and
+/// not at all if it has already been trailed in the current level), except that
+/// all the restoration closures are gathered in one single trail entry. Upon
+/// pop, that entry runs the closures in the reverse order of the writes.
+///
+/// # Note
+/// The combined entry bears no id, hence the reversibles written through a
+/// transaction are not reported by `Trail::changed_since`. Also, the trail must
+/// neither be pushed nor popped while the transaction is running.
+pub struct Transaction<'a> {
+    undo: Vec<Box<dyn FnMut() + 'a>>
+}
+
+impl<'a> Transaction<'a> {
+    /// Changes the value of the given reversible and returns its previous
+    /// value, exactly like `Reversible::set` does (nothing happens when v is
+    /// the current value).
+    pub fn set<T>(&mut self, r: &Reversible<'a, T>, v: T) -> T
+        where T: Copy + PartialEq + 'a {
+        r.check(&v);
+        let old = r.value.get();
+        if v != old {
+            if let Some(entry) = r.claim() {
+                self.undo.push(entry);
+            }
+            r.value.set(v);
+            r.changed(old, v);
+        }
+        old
+    }
+
+    /// Returns the number of reversibles trailed by this transaction so far
+    pub fn len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Returns true iff this transaction has trailed no reversible so far
+    pub fn is_empty(&self) -> bool {
+        self.undo.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_overlapping_cells() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let a = Reversible::new(Rc::clone(&trail), 1);
+        let b = Reversible::new(Rc::clone(&trail), 2);
+        let c = Reversible::new(Rc::clone(&trail), 3);
+        let alias = a.clone();
+
+        trail.borrow_mut().push();
+        c.set(30);
+        let trailed = transaction(&trail, |tx| {
+            tx.set(&a, 10);
+            tx.set(&b, 20);
+            tx.set(&alias, 11);
+            tx.set(&a, 12);
+            tx.set(&c, 31);
+            tx.set(&b, 2);
+            tx.len()
+        });
+        assert_eq!(trailed, 2);
+        assert_eq!((a.get_value(), b.get_value(), c.get_value()), (12, 2, 31));
+        assert_eq!(trail.borrow().len(), 2);
+
+        // nothing to trail: no entry is posted
+        transaction(&trail, |tx| tx.set(&a, 13));
+        assert_eq!(trail.borrow().len(), 2);
+
+        trail.borrow_mut().push();
+        transaction(&trail, |tx| { tx.set(&a, 100); tx.set(&a, 101); });
+        trail.borrow_mut().pop();
+        assert_eq!(a.get_value(), 13);
+
+        trail.borrow_mut().pop();
+        assert_eq!((a.get_value(), b.get_value(), c.get_value()), (1, 2, 3));
+        assert!(trail.borrow().is_empty());
+    }
+}
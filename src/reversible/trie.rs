@@ -0,0 +1,274 @@
+//! This module provides a reversible trie over a small alphabet. The trie is
+//! built once (at the root of the search) from a set of words and its edges can
+//! then be deactivated during filtering. These deactivations are automagically
+//! undone upon backtrack.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// This structure implements a trie whose edges can be reversibly deactivated.
+///
+/// The symbols of the alphabet are identified by the integers `0..alphabet`.
+/// Every node except the root has exactly one incoming edge, hence the edges
+/// are identified by the node they point to. A node is said to be *active* iff
+/// it can be reached from the root following active edges only.
+pub struct ReversibleTrie<'a> {
+    /// The size of the alphabet
+    alphabet: usize,
+    /// The transitions: children[node * alphabet + symbol] is the target of
+    /// the edge labeled `symbol` leaving `node` (if there is one).
+    children: Vec<Option<usize>>,
+    /// The parent of each node (the root is its own parent)
+    parent  : Vec<usize>,
+    /// Whether or not a word ends at the given node
+    terminal: Vec<bool>,
+    /// Whether or not the edge leading to the given node is still active
+    active  : Vec<Reversible<'a, bool>>
+}
+
+impl<'a> ReversibleTrie<'a> {
+    /// Creates a new trie associated with the given trail which contains all
+    /// the given words. All symbols of these words must be smaller than
+    /// `alphabet`.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, alphabet: usize, words: &[&[usize]]) -> ReversibleTrie<'a> {
+        let mut children = vec![None; alphabet];
+        let mut parent   = vec![0];
+        let mut terminal = vec![false];
+
+        for word in words {
+            let mut node = 0;
+            for &symbol in word.iter() {
+                assert!(symbol < alphabet, "symbol out of the alphabet");
+                node = match children[node * alphabet + symbol] {
+                    Some(child) => child,
+                    None => {
+                        let child = parent.len();
+                        children[node * alphabet + symbol] = Some(child);
+                        children.extend(vec![None; alphabet]);
+                        parent.push(node);
+                        terminal.push(false);
+                        child
+                    }
+                };
+            }
+            terminal[node] = true;
+        }
+
+        let active = (0..parent.len())
+            .map(|_| Reversible::new(Rc::clone(&trail), true))
+            .collect();
+
+        ReversibleTrie { alphabet, children, parent, terminal, active }
+    }
+
+    /// Returns the identifier of the root node
+    pub fn root(&self) -> usize {
+        0
+    }
+
+    /// Returns the total number of nodes in the trie (including the inactive ones)
+    pub fn nb_nodes(&self) -> usize {
+        self.parent.len()
+    }
+
+    /// Returns true iff some word of the trie ends at the given node
+    pub fn is_terminal(&self, node: usize) -> bool {
+        self.terminal[node]
+    }
+
+    /// Returns the target of the active edge labeled `symbol` leaving `node`
+    /// (or None when there is no such active edge, which is in particular the
+    /// case when `symbol` is not part of the alphabet).
+    pub fn child(&self, node: usize, symbol: usize) -> Option<usize> {
+        self.edge(node, symbol)
+            .filter(|&child| self.active[child].get_value())
+    }
+
+    /// Iterates over the active edges leaving the given node. Each item is a
+    /// pair (symbol, target).
+    pub fn children<'b>(&'b self, node: usize) -> Children<'a, 'b> {
+        Children { trie: self, node, symbol: 0 }
+    }
+
+    /// Deactivates the edge labeled `symbol` leaving `node` (if it exists:
+    /// nothing happens when `symbol` is not part of the alphabet). This
+    /// deactivation will be undone upon backtrack.
+    pub fn deactivate_edge(&mut self, node: usize, symbol: usize) {
+        if let Some(child) = self.edge(node, symbol) {
+            self.active[child].set_value(false);
+        }
+    }
+
+    /// Returns true iff the given node can be reached from the root using
+    /// active edges only.
+    pub fn is_active(&self, mut node: usize) -> bool {
+        while node != 0 {
+            if !self.active[node].get_value() {
+                return false;
+            }
+            node = self.parent[node];
+        }
+        true
+    }
+
+    /// Returns true iff there exists a word which starts with the given prefix
+    /// and can be entirely spelled using active edges only.
+    pub fn has_word_with_prefix(&self, prefix: &[usize]) -> bool {
+        let mut node = 0;
+        for &symbol in prefix {
+            match self.child(node, symbol) {
+                Some(child) => node = child,
+                None        => return false
+            }
+        }
+        self.has_word_below(node)
+    }
+
+    /// Returns the target of the edge labeled `symbol` leaving `node`, be it
+    /// active or not (None when there is no such edge). A symbol out of the
+    /// alphabet has no edge: it must not be looked up, as it would designate
+    /// an edge of another node.
+    fn edge(&self, node: usize, symbol: usize) -> Option<usize> {
+        if symbol < self.alphabet {
+            self.children[node * self.alphabet + symbol]
+        } else {
+            None
+        }
+    }
+
+    /// Returns true iff a terminal node can be reached from `node` using
+    /// active edges only.
+    fn has_word_below(&self, node: usize) -> bool {
+        let mut stack = vec![node];
+        while let Some(current) = stack.pop() {
+            if self.terminal[current] {
+                return true;
+            }
+            stack.extend(self.children(current).map(|(_, child)| child));
+        }
+        false
+    }
+}
+
+/// The iterator over the active edges leaving some node of a trie
+pub struct Children<'a: 'b, 'b> {
+    trie  : &'b ReversibleTrie<'a>,
+    node  : usize,
+    symbol: usize
+}
+
+impl<'a, 'b> Iterator for Children<'a, 'b> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        while self.symbol < self.trie.alphabet {
+            let symbol = self.symbol;
+            self.symbol += 1;
+            if let Some(child) = self.trie.child(self.node, symbol) {
+                return Some((symbol, child));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const A : usize = 0;
+    const B : usize = 1;
+    const C : usize = 2;
+
+    fn trie(trail: &Rc<RefCell<Trail<'static>>>) -> ReversibleTrie<'static> {
+        ReversibleTrie::new(Rc::clone(trail), 3, &[&[A, B, C], &[A, C], &[B, B], &[B, C, A]])
+    }
+
+    #[test]
+    fn test_construction() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let trie  = trie(&trail);
+
+        assert_eq!(trie.nb_nodes(), 9);
+        assert!(trie.has_word_with_prefix(&[]));
+        assert!(trie.has_word_with_prefix(&[A, B]));
+        assert!(trie.has_word_with_prefix(&[B, C, A]));
+        assert!(!trie.has_word_with_prefix(&[C]));
+        assert!(!trie.has_word_with_prefix(&[A, B, C, A]));
+
+        let root = trie.root();
+        let kids : Vec<usize> = trie.children(root).map(|(s, _)| s).collect();
+        assert_eq!(kids, vec![A, B]);
+    }
+
+    #[test]
+    fn test_deactivation_is_reversible() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut trie = trie(&trail);
+        let root = trie.root();
+        let a    = trie.child(root, A).unwrap();
+        let ab   = trie.child(a, B).unwrap();
+        let b    = trie.child(root, B).unwrap();
+
+        // level 1: deactivate a deep edge
+        trail.borrow_mut().push();
+        trie.deactivate_edge(ab, C);
+        assert!(!trie.has_word_with_prefix(&[A, B]));
+        assert!(trie.has_word_with_prefix(&[A]));
+        assert!(trie.is_active(ab));
+
+        // level 2: deactivate a shallow edge
+        trail.borrow_mut().push();
+        trie.deactivate_edge(root, A);
+        trie.deactivate_edge(root, A);
+        assert!(!trie.is_active(a));
+        assert!(!trie.is_active(ab));
+        assert!(!trie.has_word_with_prefix(&[A]));
+        let kids : Vec<usize> = trie.children(root).map(|(_, c)| c).collect();
+        assert_eq!(kids, vec![b]);
+
+        // level 3: deactivate the remaining words
+        trail.borrow_mut().push();
+        trie.deactivate_edge(b, B);
+        trie.deactivate_edge(b, C);
+        assert!(trie.is_active(b));
+        assert!(!trie.has_word_with_prefix(&[]));
+
+        trail.borrow_mut().pop();
+        assert!(trie.has_word_with_prefix(&[]));
+        assert!(trie.has_word_with_prefix(&[B, B]));
+        assert!(!trie.has_word_with_prefix(&[A]));
+
+        trail.borrow_mut().pop();
+        assert!(trie.is_active(ab));
+        assert!(trie.has_word_with_prefix(&[A, C]));
+        assert!(!trie.has_word_with_prefix(&[A, B]));
+
+        trail.borrow_mut().pop();
+        assert!(trie.has_word_with_prefix(&[A, B]));
+        assert!(trie.has_word_with_prefix(&[A, B, C]));
+    }
+
+    #[test]
+    fn test_symbols_out_of_the_alphabet() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut trie = trie(&trail);
+        let root = trie.root();
+
+        // root * 3 + 4 is the slot of the B edge leaving the A node
+        assert_eq!(trie.child(root, 4), None);
+        assert_eq!(trie.child(root, usize::MAX), None);
+        assert!(!trie.has_word_with_prefix(&[4]));
+        assert!(!trie.has_word_with_prefix(&[A, 3]));
+
+        trail.borrow_mut().push();
+        trie.deactivate_edge(root, 4);
+        trie.deactivate_edge(root, usize::MAX);
+        assert!(trie.has_word_with_prefix(&[A, B]));
+        assert!(trail.borrow().is_empty());
+        trail.borrow_mut().pop();
+    }
+}
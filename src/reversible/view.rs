@@ -0,0 +1,88 @@
+//! This module provides derived read-only views over reversible state.
+use alloc::rc::Rc;
+
+use ::reversible::Reversible;
+
+/// A read-only view computing its value from one or more reversibles (see
+/// `Reversible::map` and `zip_map`). The value is recomputed upon each call to
+/// `get()`, hence it always reflects the current value of the sources,
+/// including after a backtrack. Since a view only reads its sources, it never
+/// posts anything on the trail.
+///
+/// # Implementation Notes
+/// The mapping closure is kept behind an `Rc<dyn Fn>` rather than a generic
+/// parameter. This costs one dynamic call per level of chaining but keeps the
+/// type of a view nameable (`ReversibleView<'a, U>`) no matter how many maps
+/// have been chained. It also makes cloning a view as cheap as cloning an Rc.
+pub struct ReversibleView<'a, U> {
+    compute: Rc<dyn Fn() -> U + 'a>
+}
+
+impl<'a, U: 'a> ReversibleView<'a, U> {
+    /// Creates a view whose value is computed by the given closure
+    pub(crate) fn from_fn<F>(compute: F) -> ReversibleView<'a, U>
+        where F: Fn() -> U + 'a {
+        ReversibleView { compute: Rc::new(compute) }
+    }
+
+    /// Returns the current value of the view
+    pub fn get(&self) -> U {
+        (self.compute)()
+    }
+
+    /// Returns a view whose value is that of this view mapped through f
+    pub fn map<V, F>(&self, f: F) -> ReversibleView<'a, V>
+        where V: 'a, F: Fn(U) -> V + 'a {
+        let inner = Rc::clone(&self.compute);
+        ReversibleView::from_fn(move || f(inner()))
+    }
+}
+
+impl<'a, U> Clone for ReversibleView<'a, U> {
+    fn clone(&self) -> ReversibleView<'a, U> {
+        ReversibleView { compute: Rc::clone(&self.compute) }
+    }
+}
+
+/// Returns a view combining the current values of a and b through f (e.g. to
+/// observe the expression `x + y`).
+pub fn zip_map<'a, A, B, U, F>(a: &Reversible<'a, A>, b: &Reversible<'a, B>, f: F) -> ReversibleView<'a, U>
+    where A: Copy + PartialEq + 'a,
+          B: Copy + PartialEq + 'a,
+          U: 'a,
+          F: Fn(A, B) -> U + 'a {
+    let (ra, rb) = (a.reader(), b.reader());
+    ReversibleView::from_fn(move || f(ra.get(), rb.get()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use ::context::Trail;
+
+    #[test]
+    fn test_views_follow_backtracks() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = Reversible::new(Rc::clone(&trail), 3);
+        let mut y = Reversible::new(Rc::clone(&trail), 4);
+
+        let doubled = x.map(|v| v * 2);
+        let label   = doubled.map(|v| format!("2x = {}", v));
+        let sum     = zip_map(&x, &y, |a, b| a + b);
+        let copy    = sum.clone();
+        assert_eq!((doubled.get(), sum.get()), (6, 7));
+
+        trail.borrow_mut().push();
+        x.set_value(10);
+        y.set_value(-1);
+        assert_eq!(doubled.get(), 20);
+        assert_eq!(label.get(), "2x = 20");
+        assert_eq!(copy.get(), 9);
+
+        trail.borrow_mut().pop();
+        assert_eq!(label.get(), "2x = 6");
+        assert_eq!(sum.get(), 7);
+        assert_eq!(trail.borrow().len(), 0);
+    }
+}
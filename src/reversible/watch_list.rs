@@ -0,0 +1,160 @@
+//! This module provides reversible watch lists as used by the two-watched
+//! literals schemes of SAT solvers (and by some CP propagators).
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// The value of a watcher's home when it is not watching any value
+const NOWHERE : (usize, usize) = (usize::MAX, usize::MAX);
+
+/// An entry of a watch list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchEntry {
+    /// The value whose list holds this entry
+    pub value  : usize,
+    /// The watcher that registered this entry
+    pub watcher: usize,
+    /// The stamp which uniquely identifies this entry
+    stamp      : usize
+}
+
+/// This structure implements one watch list per value (identified by the
+/// integers `0..nb_values`). Watchers (identified by `0..nb_watchers`) move from
+/// one list to another during propagation; and these moves are undone upon
+/// backtrack.
+///
+/// # Invalidation policy
+/// Moves are not undone eagerly. Instead, each watcher remembers (in a
+/// reversible cell) the stamp of the one entry which is currently valid for it.
+/// Each entry carries a unique stamp drawn from a monotonic counter, so an
+/// entry is *stale* iff it is not the one currently designated by its watcher.
+/// Stale entries are simply skipped when the lists are drained. Upon backtrack,
+/// the watcher's cell is restored, which makes the entry it was designating at
+/// that time valid again. Besides, the lengths of the lists are reversible, so
+/// the entries added at a deeper level are dropped upon backtrack.
+///
+/// Stale entries are only physically removed when a list is drained at level
+/// zero, since there is no state left to restore them at that point.
+pub struct ReversibleWatchList<'a> {
+    lists: Vec<Vec<WatchEntry>>,
+    lens : Vec<Reversible<'a, usize>>,
+    homes: Vec<Reversible<'a, (usize, usize)>>,
+    stamp: usize,
+    trail: Rc<RefCell<Trail<'a>>>
+}
+
+impl<'a> ReversibleWatchList<'a> {
+    /// Creates a new set of empty watch lists associated with the given trail.
+    pub fn new(trail: Rc<RefCell<Trail<'a>>>, nb_values: usize, nb_watchers: usize) -> ReversibleWatchList<'a> {
+        ReversibleWatchList {
+            lists: vec![vec![]; nb_values],
+            lens : (0..nb_values)  .map(|_| Reversible::new(Rc::clone(&trail), 0)).collect(),
+            homes: (0..nb_watchers).map(|_| Reversible::new(Rc::clone(&trail), NOWHERE)).collect(),
+            stamp: 0,
+            trail
+        }
+    }
+
+    /// Makes `watcher` watch the given value. If the watcher was already
+    /// watching some other value, it stops watching it.
+    pub fn add_watch(&mut self, value: usize, watcher: usize) {
+        self.stamp += 1;
+        let entry = WatchEntry { value, watcher, stamp: self.stamp };
+
+        let len = self.lens[value].get_value();
+        self.lists[value].truncate(len);
+        self.lists[value].push(entry);
+        self.lens [value].set_value(len + 1);
+        self.homes[watcher].set_value((value, entry.stamp));
+    }
+
+    /// Returns true iff the given entry is not the valid entry of its watcher
+    pub fn is_stale(&self, entry: &WatchEntry) -> bool {
+        self.homes[entry.watcher].get_value() != (entry.value, entry.stamp)
+    }
+
+    /// Returns the value currently watched by the given watcher (if any)
+    pub fn watched_by(&self, watcher: usize) -> Option<usize> {
+        let (value, _) = self.homes[watcher].get_value();
+        if value == NOWHERE.0 { None } else { Some(value) }
+    }
+
+    /// Returns the entries of the given list, including the stale ones
+    pub fn entries(&self, value: usize) -> &[WatchEntry] {
+        &self.lists[value][..self.lens[value].get_value()]
+    }
+
+    /// Removes all the watchers of the given value and returns them. The
+    /// caller is expected to register them to other values (or to the same
+    /// one) with `add_watch`. All of this is undone upon backtrack.
+    pub fn drain_watchers(&mut self, value: usize) -> Vec<usize> {
+        let len = self.lens[value].get_value();
+        let mut drained = vec![];
+        for i in 0..len {
+            let entry = self.lists[value][i];
+            if !self.is_stale(&entry) {
+                drained.push(entry.watcher);
+                self.homes[entry.watcher].set_value(NOWHERE);
+            }
+        }
+
+        if self.trail.borrow().level() == 0 {
+            self.lists[value].clear();
+            self.lens [value].set_value(0);
+        }
+        drained
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_no_stale_watcher_fires() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut watches = ReversibleWatchList::new(Rc::clone(&trail), 3, 2);
+        watches.add_watch(0, 0);
+        watches.add_watch(0, 1);
+
+        trail.borrow_mut().push();
+        let moved = watches.drain_watchers(0);
+        assert_eq!(moved, vec![0, 1]);
+        watches.add_watch(1, 0);
+        watches.add_watch(2, 1);
+        assert_eq!(watches.watched_by(0), Some(1));
+        assert!(watches.entries(0).iter().all(|e| watches.is_stale(e)));
+
+        trail.borrow_mut().push();
+        assert_eq!(watches.drain_watchers(1), vec![0]);
+        watches.add_watch(2, 0);
+        assert_eq!(watches.drain_watchers(1), Vec::<usize>::new());
+
+        trail.borrow_mut().pop();
+        assert_eq!(watches.watched_by(0), Some(1));
+        assert_eq!(watches.entries(2).len(), 1);
+
+        trail.borrow_mut().pop();
+        assert_eq!(watches.drain_watchers(1), Vec::<usize>::new());
+        assert_eq!(watches.drain_watchers(2), Vec::<usize>::new());
+        assert_eq!(watches.drain_watchers(0), vec![0, 1]);
+    }
+
+    #[test]
+    fn test_cleanup_at_root() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut watches = ReversibleWatchList::new(Rc::clone(&trail), 2, 1);
+        watches.add_watch(0, 0);
+        watches.add_watch(1, 0);
+        assert_eq!(watches.entries(0).len(), 1);
+        assert!(watches.is_stale(&watches.entries(0)[0]));
+
+        assert_eq!(watches.drain_watchers(0), Vec::<usize>::new());
+        assert!(watches.entries(0).is_empty());
+        assert_eq!(watches.drain_watchers(1), vec![0]);
+        assert_eq!(watches.watched_by(0), None);
+    }
+}
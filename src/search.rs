@@ -0,0 +1,510 @@
+//! This module provides the utilities you will be willing to use when writing
+//! a search procedure on top of the trail and of the reversible types.
+use alloc::vec::Vec;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+
+use ::context::Trail;
+use ::reversible::Reversible;
+
+/// A solution is an owned snapshot of the values of a set of variables.
+///
+/// # Ordering guarantee
+/// The values of the variables are read at the time `capture` is called. A
+/// search procedure is expected to call it from its solution callback, that is
+/// *before* it pops the trail to backtrack to the next branch. The returned
+/// vector does not borrow anything from the variables nor from the trail: it
+/// keeps the values of the solution even as the search backtracks and mutates
+/// the variables further.
+pub struct Solution;
+
+impl Solution {
+    /// Reads the current values of the given variables into an owned vector
+    pub fn capture<'a, T>(vars: &[&Reversible<'a, T>]) -> Vec<T>
+        where T: Copy + PartialEq + 'a {
+        vars.iter().map(|v| v.get_value()).collect()
+    }
+}
+
+/// The objective of a branch-and-bound search: it remembers the best value of
+/// the solutions found so far (the incumbent) along with the bound which the
+/// next solutions must satisfy to be an improvement.
+///
+/// # Backtrack
+/// Unlike an ordinary reversible, the incumbent is *not* reverted when the
+/// trail is popped. A solution found deep in the tree remains the best solution
+/// after the search has backtracked out of its subtree, and the bound it
+/// implies (strictly better than the incumbent) keeps applying to the rest of
+/// the search: that part of the bound only ever gets tighter. On top of it,
+/// the objective holds a reversible bound which `restrict` tightens for the
+/// current subtree only (e.g. with a bound derived from a relaxation at some
+/// node) and which is reverted as usual upon backtrack.
+///
+/// # Note
+/// The incumbent deliberately is a plain field rather than a `Reversible`: it
+/// must survive every pop, down to the root, whereas a reversible modified at
+/// some level is restored when that level is popped. Keeping it off the trail
+/// also means that recording a solution posts nothing on the trail.
+///
+/// # Search
+/// There is no search driver in the crate: the search procedure calls
+/// `tighten` when it finds a solution and prunes the branches whose optimistic
+/// value is not `accepts`ed.
+pub struct ReversibleObjective<'a> {
+    maximize : bool,
+    incumbent: Option<isize>,
+    /// The worst value a solution of the current subtree may have to be
+    /// accepted, regardless of the incumbent (expressed in terms of a
+    /// minimization, hence negated when maximizing)
+    bound    : Reversible<'a, isize>
+}
+
+impl<'a> ReversibleObjective<'a> {
+    /// Creates an objective to minimize, whose bound is associated with the
+    /// given trail
+    pub fn minimize(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleObjective<'a> {
+        ReversibleObjective { maximize: false, incumbent: None, bound: Reversible::new(trail, isize::MAX) }
+    }
+
+    /// Creates an objective to maximize, whose bound is associated with the
+    /// given trail
+    pub fn maximize(trail: Rc<RefCell<Trail<'a>>>) -> ReversibleObjective<'a> {
+        ReversibleObjective { maximize: true, incumbent: None, bound: Reversible::new(trail, isize::MAX) }
+    }
+
+    /// Returns the value of the best solution found so far (None when no
+    /// solution has been found yet)
+    pub fn incumbent(&self) -> Option<isize> {
+        self.incumbent
+    }
+
+    /// Returns the worst value a solution may have to be accepted in the
+    /// current subtree (None as long as any value is accepted)
+    pub fn bound(&self) -> Option<isize> {
+        let from_incumbent = self.incumbent.map(|v| self.normalized(v).saturating_sub(1));
+        let bound = from_incumbent.map_or(self.bound.get_value(), |b| b.min(self.bound.get_value()));
+        if bound == isize::MAX { None } else { Some(self.normalized(bound)) }
+    }
+
+    /// Returns true iff a solution having the given value would be accepted
+    /// (that is, it is strictly better than the incumbent and satisfies the
+    /// bound of the current subtree). When value is the best value which can
+    /// be reached below some node, the search may fail early as soon as it is
+    /// not accepted.
+    pub fn accepts(&self, value: isize) -> bool {
+        let value = self.normalized(value);
+        value <= self.bound.get_value() && self.incumbent.is_none_or(|v| value < self.normalized(v))
+    }
+
+    /// Records a solution of the given value. When it is accepted, it becomes
+    /// the incumbent (permanently, this posts nothing on the trail) and the
+    /// bound is tightened so that only strictly better solutions are accepted
+    /// from now on, in every branch. Returns true iff the solution was
+    /// accepted.
+    pub fn tighten(&mut self, value: isize) -> bool {
+        if !self.accepts(value) {
+            return false;
+        }
+        self.incumbent = Some(value);
+        true
+    }
+
+    /// Tightens the bound of the current subtree: from now on and until the
+    /// current level is popped, only the solutions whose value is at least as
+    /// good as the given one are accepted.
+    pub fn restrict(&mut self, value: isize) {
+        let bound = self.normalized(value);
+        if bound < self.bound.get_value() {
+            self.bound.set_value(bound);
+        }
+    }
+
+    /// Returns the value as it is seen by a minimization
+    fn normalized(&self, value: isize) -> isize {
+        if self.maximize { value.saturating_neg() } else { value }
+    }
+}
+
+/// The metrics of one run of a search procedure. The procedure calls
+/// `observe` at each node (after it has pushed the level of the node) and
+/// `failure` / `solution` whenever a node fails or yields a solution.
+///
+/// # Note
+/// There is no search driver in the crate, hence no search object to expose
+/// the statistics through an accessor: the search procedure owns its
+/// `Statistics` and updates them itself.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// The number of nodes explored
+    pub nodes         : usize,
+    /// The number of nodes which failed
+    pub failures      : usize,
+    /// The number of solutions found
+    pub solutions     : usize,
+    /// The deepest level of the trail reached at a node
+    pub max_depth     : usize,
+    /// The largest number of entries on the trail observed at a node
+    pub peak_trail_len: usize
+}
+
+impl Statistics {
+    /// Creates empty statistics
+    pub fn new() -> Statistics {
+        Statistics::default()
+    }
+
+    /// Accounts for a new node, whose depth and trail length are read from
+    /// the given trail (`Trail::level()` and `Trail::len()`).
+    pub fn observe(&mut self, trail: &Trail) {
+        self.nodes         += 1;
+        self.max_depth      = self.max_depth.max(trail.level());
+        self.peak_trail_len = self.peak_trail_len.max(trail.len());
+    }
+
+    /// Accounts for a failed node
+    pub fn failure(&mut self) {
+        self.failures += 1;
+    }
+
+    /// Accounts for a solution
+    pub fn solution(&mut self) {
+        self.solutions += 1;
+    }
+}
+
+/// Displays a one-line summary: e.g. `nodes: 7, failures: 2, solutions: 1,
+/// max depth: 3, peak trail length: 5`
+impl fmt::Display for Statistics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "nodes: {}, failures: {}, solutions: {}, max depth: {}, peak trail length: {}",
+            self.nodes, self.failures, self.solutions, self.max_depth, self.peak_trail_len)
+    }
+}
+
+/// The way a search procedure ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOutcome {
+    /// The whole search tree was explored
+    Exhausted,
+    /// The search was stopped because it exceeded its `Limit`
+    LimitReached
+}
+
+/// The budget of a search procedure. Each bound is optional (None meaning
+/// unlimited): e.g. `Limit { max_nodes: Some(1000), ..Limit::default() }`.
+///
+/// # Search
+/// There is no search driver in the crate: the search procedure calls
+/// `check` at each node (right after `Statistics::observe`). When it reports
+/// an outcome, the procedure returns at once without popping the levels it
+/// pushed, and its entry point calls `unwind` which brings the trail back to
+/// level 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limit {
+    /// The maximum number of nodes explored
+    pub max_nodes   : Option<usize>,
+    /// The maximum number of failed nodes
+    pub max_failures: Option<usize>,
+    /// The maximum level of the trail at a node
+    pub max_depth   : Option<usize>
+}
+
+impl Limit {
+    /// Returns `LimitReached` iff the given statistics, or the current level
+    /// of the given trail, exceed one of the bounds of this limit (and None
+    /// when the search may go on).
+    pub fn check(&self, stats: &Statistics, trail: &Trail) -> Option<SearchOutcome> {
+        let exceeds = |max: Option<usize>, actual: usize| max.is_some_and(|max| actual > max);
+        if exceeds(self.max_nodes, stats.nodes)
+            || exceeds(self.max_failures, stats.failures)
+            || exceeds(self.max_depth, trail.level()) {
+            Some(SearchOutcome::LimitReached)
+        } else {
+            None
+        }
+    }
+}
+
+/// Brings the trail back to level 0 (see `Trail::pop_all`), which leaves the
+/// reversibles in a consistent state after a search has been stopped halfway,
+/// and returns the given outcome.
+pub fn unwind(trail: &mut Trail, outcome: SearchOutcome) -> SearchOutcome {
+    trail.pop_all();
+    outcome
+}
+
+/// Runs a search made of successive descents from the root, each of which is
+/// given a budget of failures: `budget` for the first descent, and twice the
+/// budget of the previous one for the next ones (so that the search
+/// eventually completes). `descent` runs one descent under the given limit
+/// (see `Limit::check`) and reports how it ended. When a descent exhausts
+/// its budget, the trail is popped down to level 0 (`pop_all`), the
+/// optional `reorder` callback is called with the number of the restart that
+/// is about to begin (e.g. to change the variable order) and a fresh descent
+/// begins. Returns `Exhausted` as soon as a descent completes, and
+/// `LimitReached` when the last of the `max_restarts` restarts has exhausted
+/// its budget as well. In both cases, the trail is left at level 0.
+///
+/// # What survives a restart
+/// A restart undoes everything that was done above level 0: the domains and
+/// all the other reversibles are back to their root state. What must be kept
+/// across the restarts (the incumbent of a `ReversibleObjective`, nogoods,
+/// ...) must thus either live outside of the trail, as the incumbent does, or
+/// be recorded at level 0.
+pub fn restart<D>(trail: &Rc<RefCell<Trail>>, budget: usize, max_restarts: usize,
+                  mut descent: D, mut reorder: Option<&mut dyn FnMut(usize)>) -> SearchOutcome
+    where D: FnMut(&Limit) -> SearchOutcome {
+    let mut budget = budget;
+    let mut run    = 0;
+    loop {
+        let outcome = descent(&Limit { max_failures: Some(budget), ..Limit::default() });
+        trail.borrow_mut().pop_all();
+        if outcome == SearchOutcome::Exhausted || run == max_restarts {
+            return outcome;
+        }
+        run   += 1;
+        budget = budget.saturating_mul(2);
+        if let Some(reorder) = reorder.as_mut() {
+            reorder(run);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use ::context::Trail;
+    use ::reversible::TrailedIntVar;
+    use super::*;
+
+    #[test]
+    fn test_incumbent_survives_pop() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut obj = ReversibleObjective::maximize(Rc::clone(&trail));
+        assert_eq!((obj.incumbent(), obj.bound()), (None, None));
+        assert!(obj.accepts(isize::MIN));
+
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        assert!(obj.tighten(5));
+        assert!(!obj.tighten(5));
+        assert!(!obj.accepts(3));
+        trail.borrow_mut().pop();
+        trail.borrow_mut().pop();
+
+        // the incumbent and the bound it implies are not reverted
+        assert_eq!((obj.incumbent(), obj.bound()), (Some(5), Some(6)));
+        assert!(!obj.tighten(4));
+        assert!(obj.tighten(7));
+        assert_eq!(obj.incumbent(), Some(7));
+
+        // a restriction only holds in the current subtree
+        trail.borrow_mut().push();
+        obj.restrict(10);
+        assert_eq!(obj.bound(), Some(10));
+        assert!(!obj.accepts(9) && obj.accepts(10));
+        obj.restrict(2); // looser than the incumbent: no effect
+        assert_eq!(obj.bound(), Some(10));
+        trail.borrow_mut().pop();
+        assert_eq!(obj.bound(), Some(8));
+        assert!(obj.accepts(9));
+
+        let mut min = ReversibleObjective::minimize(Rc::clone(&trail));
+        trail.borrow_mut().push();
+        assert!(min.tighten(3));
+        trail.borrow_mut().pop();
+        assert_eq!((min.incumbent(), min.bound()), (Some(3), Some(2)));
+        assert!(min.accepts(2) && !min.accepts(3));
+    }
+
+    #[test]
+    fn test_statistics() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = Reversible::new(Rc::clone(&trail), 0);
+        let mut y = Reversible::new(Rc::clone(&trail), 0);
+        let mut stats = Statistics::new();
+
+        stats.observe(&trail.borrow());
+        for v in 1..3 {
+            trail.borrow_mut().push();
+            x.set_value(v);
+            y.set_value(v);
+            stats.observe(&trail.borrow());
+            if v == 1 { stats.failure() } else { stats.solution() }
+            trail.borrow_mut().pop();
+        }
+        trail.borrow_mut().push();
+        trail.borrow_mut().push();
+        x.set_value(3);
+        stats.observe(&trail.borrow());
+        trail.borrow_mut().pop_all();
+
+        let expected = Statistics { nodes: 4, failures: 1, solutions: 1, max_depth: 2, peak_trail_len: 2 };
+        assert_eq!(stats, expected);
+        assert_eq!(format!("{}", stats), "nodes: 4, failures: 1, solutions: 1, max depth: 2, peak trail length: 2");
+    }
+
+    /// Enumerates the assignments of xs[var..] to 0 or 1 (fixing a variable
+    /// to 1 fails when the sum would exceed 3), unless the limit is exceeded.
+    fn dfs(trail: &Rc<RefCell<Trail>>, xs: &mut [Reversible<isize>], var: usize,
+           limit: &Limit, stats: &mut Statistics) -> Option<SearchOutcome> {
+        stats.observe(&trail.borrow());
+        if let Some(outcome) = limit.check(stats, &trail.borrow()) {
+            return Some(outcome);
+        }
+        if var == xs.len() {
+            stats.solution();
+            return None;
+        }
+        for v in 0..2 {
+            trail.borrow_mut().push();
+            xs[var].set_value(v);
+            if xs.iter().map(|x| x.get_value()).sum::<isize>() > 3 {
+                stats.failure();
+            } else if let Some(outcome) = dfs(trail, xs, var + 1, limit, stats) {
+                return Some(outcome);
+            }
+            trail.borrow_mut().pop();
+        }
+        None
+    }
+
+    fn search(trail: &Rc<RefCell<Trail>>, xs: &mut [Reversible<isize>], limit: Limit) -> (SearchOutcome, Statistics) {
+        let mut stats = Statistics::new();
+        let outcome = match dfs(trail, xs, 0, &limit, &mut stats) {
+            Some(outcome) => unwind(&mut trail.borrow_mut(), outcome),
+            None          => SearchOutcome::Exhausted
+        };
+        (outcome, stats)
+    }
+
+    #[test]
+    fn test_limits_stop_the_search() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut xs = Reversible::new_array(&trail, 10, |_| 0_isize);
+
+        let (outcome, stats) = search(&trail, &mut xs, Limit::default());
+        assert_eq!(outcome, SearchOutcome::Exhausted);
+        assert_eq!(stats.solutions, 176); // the subsets of at most 3 out of 10
+        let full = stats.nodes;
+
+        let limit = Limit { max_nodes: Some(20), ..Limit::default() };
+        trail.borrow_mut().push();
+        xs[0].set_value(1);
+        let (outcome, stats) = search(&trail, &mut xs, limit);
+        assert_eq!(outcome, SearchOutcome::LimitReached);
+        assert_eq!(stats.nodes, 21);
+        assert!(stats.nodes < full);
+        // the trail is unwound down to the root, including the levels pushed
+        // before the search started
+        assert_eq!(trail.borrow().level(), 0);
+        assert!(xs.iter().all(|x| x.get_value() == 0));
+
+        let limit = Limit { max_failures: Some(2), ..Limit::default() };
+        let (outcome, stats) = search(&trail, &mut xs, limit);
+        assert_eq!((outcome, stats.failures), (SearchOutcome::LimitReached, 3));
+        assert_eq!(trail.borrow().level(), 0);
+
+        let limit = Limit { max_depth: Some(4), ..Limit::default() };
+        let (outcome, stats) = search(&trail, &mut xs, limit);
+        assert_eq!((outcome, stats.max_depth), (SearchOutcome::LimitReached, 5));
+        assert_eq!(trail.borrow().level(), 0);
+    }
+
+    const WEIGHTS : [isize; 4] = [4, 3, 2, 1];
+
+    /// Assigns the variables (all different) in the given order, so as to
+    /// maximize the weighted sum of their values
+    fn descend(trail: &Rc<RefCell<Trail>>, xs: &mut [TrailedIntVar], order: &[usize], k: usize,
+               obj: &mut ReversibleObjective, limit: &Limit, stats: &mut Statistics) -> Option<SearchOutcome> {
+        stats.observe(&trail.borrow());
+        if let Some(outcome) = limit.check(stats, &trail.borrow()) {
+            return Some(outcome);
+        }
+        let weighted = |f: &dyn Fn(&TrailedIntVar) -> isize| -> isize {
+            xs.iter().zip(WEIGHTS.iter()).map(|(x, w)| f(x) * w).sum()
+        };
+        if k == xs.len() {
+            stats.solution();
+            obj.tighten(weighted(&|x| x.min()));
+            return None;
+        }
+        if !obj.accepts(weighted(&|x| x.max())) {
+            stats.failure();
+            return None;
+        }
+        let var = order[k];
+        let values : Vec<isize> = (xs[var].min()..=xs[var].max()).filter(|&v| xs[var].contains(v)).collect();
+        for v in values {
+            trail.borrow_mut().push();
+            let mut ok = !xs[var].fix(v).is_failure();
+            for (i, x) in xs.iter_mut().enumerate() {
+                ok = ok && (i == var || !x.remove(v).is_failure());
+            }
+            if !ok {
+                stats.failure();
+            } else if let Some(outcome) = descend(trail, xs, order, k + 1, obj, limit, stats) {
+                return Some(outcome);
+            }
+            trail.borrow_mut().pop();
+        }
+        None
+    }
+
+    #[test]
+    fn test_restart_resets_the_domains_and_keeps_the_incumbent() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let xs : RefCell<Vec<TrailedIntVar>> = RefCell::new((0..4).map(|_| TrailedIntVar::new(Rc::clone(&trail), 0, 3)).collect());
+        let obj   = RefCell::new(ReversibleObjective::maximize(Rc::clone(&trail)));
+        let order = RefCell::new(vec![0, 1, 2, 3]);
+        let mut seen = vec![];
+
+        let descent = |limit: &Limit| {
+            let mut stats = Statistics::new();
+            descend(&trail, &mut xs.borrow_mut(), &order.borrow(), 0, &mut obj.borrow_mut(), limit, &mut stats)
+                .unwrap_or(SearchOutcome::Exhausted)
+        };
+        let mut reorder = |run: usize| {
+            let at_root = xs.borrow().iter().all(|x| (x.min(), x.max(), x.size()) == (0, 3, 4));
+            seen.push((run, trail.borrow().level(), at_root, obj.borrow().incumbent()));
+            order.borrow_mut().rotate_left(1);
+        };
+        let outcome = restart(&trail, 2, 10, descent, Some(&mut reorder));
+
+        assert_eq!(outcome, SearchOutcome::Exhausted);
+        assert_eq!(obj.borrow().incumbent(), Some(20));
+        // each restart begins at the root, with the incumbent of the previous
+        // descents
+        assert_eq!(seen, vec![(1, 0, true, Some(14)), (2, 0, true, Some(17)), (3, 0, true, Some(20))]);
+
+        let outcome = restart(&trail, 1, 0, |_| SearchOutcome::LimitReached, None);
+        assert_eq!(outcome, SearchOutcome::LimitReached);
+        assert_eq!(trail.borrow().level(), 0);
+    }
+
+    #[test]
+    fn test_capture_survives_backtrack() {
+        let trail = Rc::new(RefCell::new(Trail::new()));
+        let mut x = Reversible::new(Rc::clone(&trail), 0_isize);
+        let mut y = Reversible::new(Rc::clone(&trail), 0_isize);
+
+        // enumerate all the assignments of x, y in 1..3
+        let mut solutions = vec![];
+        for vx in 1..3 {
+            trail.borrow_mut().push();
+            x.set_value(vx);
+            for vy in 1..3 {
+                trail.borrow_mut().push();
+                y.set_value(vy);
+                solutions.push(Solution::capture(&[&x, &y]));
+                trail.borrow_mut().pop();
+            }
+            trail.borrow_mut().pop();
+        }
+
+        assert_eq!(solutions, vec![vec![1, 1], vec![1, 2], vec![2, 1], vec![2, 2]]);
+        assert_eq!(Solution::capture(&[&x, &y]), vec![0, 0]);
+    }
+}